@@ -0,0 +1,109 @@
+// cargo bench, results end up in target/criterion. Compare two commits with
+//
+//  git checkout A && cargo bench -- --save-baseline a
+//  git checkout B && cargo bench -- --baseline a
+//
+// The ROMs start from a fresh, identically seeded machine on every iteration
+// with no keys pressed, so each run executes the same instructions, CXNN
+// included.
+
+use chip8_rs::jit::Jit;
+use chip8_rs::palette::Palette;
+use chip8_rs::phosphor::DEFAULT_DECAY;
+use chip8_rs::renderer::Renderer;
+use chip8_rs::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH, TICKS_PER_FRAME};
+use criterion::{BatchSize, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use std::fs;
+
+const ROMS: [&str; 4] = ["TETRIS", "BRIX", "INVADERS", "SYZYGY"];
+
+// Instructions per iteration, 100 frames
+const CYCLES: u64 = 1000;
+
+fn load(name: &str) -> Chip8 {
+    let rom = fs::read(format!("{}/rom/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom).unwrap();
+    chip8.seed(1);
+    chip8
+}
+
+fn run(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run");
+    group.throughput(Throughput::Elements(CYCLES));
+
+    for name in ROMS {
+        // Decoding every cycle
+        group.bench_function(format!("{}/decode", name), |b| {
+            b.iter_batched_ref(
+                || load(name),
+                |chip8| {
+                    for cycle in 1..=CYCLES {
+                        let op = chip8.fetch_instruction();
+                        chip8.execute_instruction(op).unwrap();
+                        if cycle % TICKS_PER_FRAME as u64 == 0 {
+                            chip8.tick_timers();
+                        }
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+
+        // Decoded instruction cache
+        group.bench_function(format!("{}/step", name), |b| {
+            b.iter_batched_ref(
+                || load(name),
+                |chip8| {
+                    for _ in 0..CYCLES / TICKS_PER_FRAME as u64 {
+                        chip8.run_frame().unwrap();
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+
+        // Blocks stay translated between iterations and only get rechecked
+        let mut jit = Jit::new();
+        group.bench_function(format!("{}/jit", name), |b| {
+            b.iter_batched_ref(
+                || load(name),
+                |chip8| {
+                    for _ in 0..CYCLES / TICKS_PER_FRAME as u64 {
+                        jit.run_frame(chip8).unwrap();
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+// The screen to pixels conversion done by draw every frame
+fn render(c: &mut Criterion) {
+    // A busy screen to convert, INVADERS after its title screen is drawn
+    let mut chip8 = load("INVADERS");
+    for _ in 0..100 {
+        chip8.run_frame().unwrap();
+    }
+    let palette = Palette::classic();
+
+    let mut group = c.benchmark_group("render");
+    group.throughput(Throughput::Elements((SCREEN_WIDTH * SCREEN_HEIGHT) as u64));
+
+    for (name, decay) in [("sharp", 0.0), ("phosphor", DEFAULT_DECAY)] {
+        let mut renderer = Renderer::new(decay, SCREEN_WIDTH * SCREEN_HEIGHT);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                black_box(renderer.render(black_box(&chip8.screen), &palette));
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, run, render);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+// Measures headless throughput on the bundled ROMs, in millions of
+// instructions per second: decoding every cycle, with the decoded instruction
+// cache (Chip8::step) and with the block translator (Jit).
+//
+//  cargo run --release --example mips [FRAMES]
+
+use chip8_rs::jit::Jit;
+use chip8_rs::{Chip8, TICKS_PER_FRAME};
+use std::{env, fs, time::Instant};
+
+#[derive(Clone, Copy)]
+enum Backend {
+    Decode,
+    Cached,
+    Jit,
+}
+
+// Runs until the frame count is reached or the ROM stops, returns MIPS
+fn measure(rom: &[u8], frames: u64, backend: Backend) -> f64 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom).unwrap();
+    let mut jit = Jit::new();
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        let result = match backend {
+            Backend::Decode => (0..TICKS_PER_FRAME).try_for_each(|_| {
+                // What step did before the cache
+                let op = chip8.fetch_instruction();
+                chip8.opcode = op;
+                chip8.cycles += 1;
+                chip8.execute_instruction(op)
+            }),
+            Backend::Cached => (0..TICKS_PER_FRAME).try_for_each(|_| chip8.step()),
+            Backend::Jit => jit.run(&mut chip8, TICKS_PER_FRAME as u64),
+        };
+        if result.is_err() {
+            break;
+        }
+        chip8.tick_timers();
+    }
+
+    chip8.cycles as f64 / start.elapsed().as_secs_f64() / 1e6
+}
+
+fn main() {
+    let frames = env::args()
+        .nth(1)
+        .map(|frames| frames.parse().expect("Invalid frame count"))
+        .unwrap_or(100_000);
+
+    let mut names: Vec<String> = fs::read_dir("rom")
+        .expect("Run from the repository root")
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+
+    println!(
+        "{:<16} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "ROM", "decode", "cached", "jit", "cached x", "jit x"
+    );
+    let mut totals = [0.0; 3];
+    for name in &names {
+        let rom = fs::read(format!("rom/{}", name)).unwrap();
+        let mips = [Backend::Decode, Backend::Cached, Backend::Jit]
+            .map(|backend| measure(&rom, frames, backend));
+        for (total, mips) in totals.iter_mut().zip(mips) {
+            *total += mips;
+        }
+        print_row(name, mips);
+    }
+
+    print_row("mean", totals.map(|total| total / names.len() as f64));
+}
+
+fn print_row(name: &str, [decode, cached, jit]: [f64; 3]) {
+    println!(
+        "{:<16} {:>8.1} {:>8.1} {:>8.1} {:>7.2}x {:>7.2}x",
+        name,
+        decode,
+        cached,
+        jit,
+        cached / decode,
+        jit / decode
+    );
+}
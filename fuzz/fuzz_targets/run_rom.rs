@@ -0,0 +1,37 @@
+#![no_main]
+
+// Runs arbitrary bytes as a ROM for a bounded number of frames. The core must
+// never panic, bad programs have to end in an Error instead.
+
+use chip8_rs::{Chip8, RAM_SIZE, START_ADDR};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_FRAMES: usize = 60;
+
+// CXNN gives the same numbers on every run, so crashes reproduce
+const SEED: u64 = 0xC8;
+
+fuzz_target!(|data: &[u8]| {
+    // The first two bytes are the keypad state, so key opcodes see presses too
+    let Some((keys, rom)) = data.split_at_checked(2) else {
+        return;
+    };
+    let keys = u16::from_be_bytes([keys[0], keys[1]]);
+
+    let mut chip8 = Chip8::new();
+    chip8.seed(SEED);
+    if chip8.load_rom(rom).is_err() {
+        assert!(rom.len() > RAM_SIZE - START_ADDR as usize);
+        return;
+    }
+
+    for key in 0..16 {
+        chip8.key_press(key, keys & (1 << key) != 0);
+    }
+
+    for _ in 0..MAX_FRAMES {
+        if chip8.run_frame().is_err() {
+            break;
+        }
+    }
+});
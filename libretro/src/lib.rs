@@ -0,0 +1,515 @@
+// libretro core, for RetroArch and other libretro frontends. Build with
+// `cargo build --release -p chip8-libretro` and load
+// target/release/libchip8_libretro.so (.dll, .dylib) as a core.
+//
+// The frontend calls in from one thread, the state lives behind mutexes only
+// because statics need them.
+
+pub mod ffi;
+
+use chip8_rs::cheat::{Cheat, Cheats};
+use chip8_rs::database::Database;
+use chip8_rs::palette::Palette;
+use chip8_rs::quirks::Quirks;
+use chip8_rs::snapshot::{self, Snapshot};
+use chip8_rs::{Chip8, NUM_KEYS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, TICKS_PER_FRAME};
+use ffi::*;
+use log::warn;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, c_char, c_uint, c_void};
+use std::sync::Mutex;
+
+pub const FPS: f64 = 60.0;
+pub const SAMPLE_RATE: f64 = 44100.0;
+pub const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FPS) as usize;
+
+// Square wave while the sound timer runs
+const TONE: f64 = 440.0;
+const VOLUME: i16 = 0x1000;
+
+// Keyboard keys (RETROK_*, ASCII for these) for keypad keys 0-F, laid out as
+// 1234/QWER/ASDF/ZXCV like the SDL frontend
+const KEYPAD: [u8; NUM_KEYS] = *b"x123qweasdzc4rfv";
+
+// Joypad buttons for the ROM database's button names, the second joypad has
+// the player2 ones
+const BUTTONS: [(&str, c_uint); 6] = [
+    ("up", DEVICE_ID_JOYPAD_UP),
+    ("down", DEVICE_ID_JOYPAD_DOWN),
+    ("left", DEVICE_ID_JOYPAD_LEFT),
+    ("right", DEVICE_ID_JOYPAD_RIGHT),
+    ("a", DEVICE_ID_JOYPAD_A),
+    ("b", DEVICE_ID_JOYPAD_B),
+];
+
+// For ROMs the database doesn't know: the keys under WASD on the keypad layout,
+// with Q and E as A and B
+const DEFAULT_BUTTONS: [(&str, u8); 6] = [
+    ("up", 0x5),
+    ("down", 0x8),
+    ("left", 0x7),
+    ("right", 0x9),
+    ("a", 0x6),
+    ("b", 0x4),
+];
+
+// Keypad keys held on a device, or None when nothing maps to it
+fn keypad_key(
+    buttons: &HashMap<String, u8>,
+    port: c_uint,
+    device: c_uint,
+    id: c_uint,
+) -> Option<usize> {
+    match device {
+        DEVICE_KEYBOARD if port == 0 => KEYPAD.iter().position(|key| *key as c_uint == id),
+        DEVICE_JOYPAD if port < 2 => {
+            let (name, _) = BUTTONS.iter().find(|(_, button)| *button == id)?;
+            let name = match port {
+                0 => name.to_string(),
+                _ => format!("player2{}{}", name[..1].to_uppercase(), &name[1..]),
+            };
+            buttons.get(&name).map(|key| *key as usize & 0xF)
+        }
+        _ => None,
+    }
+}
+
+// A cheat code: ADDR:VALUE in hex, several joined with +, e.g. "3F0:05+3F1:00"
+fn parse_code(code: &str) -> Result<Cheats, String> {
+    let mut cheats = Cheats::default();
+    for part in code.split('+') {
+        let (addr, value) = part
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid cheat {:?}, expected ADDR:VALUE", part))?;
+        let number = |text: &str| u16::from_str_radix(text.trim(), 16);
+        match (number(addr), number(value)) {
+            (Ok(addr), Ok(value)) if (addr as usize) < RAM_SIZE && value <= 0xFF => {
+                cheats.list.push(Cheat {
+                    name: String::new(),
+                    addr,
+                    value: value as u8,
+                })
+            }
+            _ => return Err(format!("Invalid cheat {:?}", part)),
+        }
+    }
+    Ok(cheats)
+}
+
+pub struct Core {
+    pub chip8: Chip8,
+    rom: Vec<u8>,
+    buttons: HashMap<String, u8>,
+    palette: Palette,
+    // Stopped after an error, until the next reset
+    halted: bool,
+    frame: Vec<u32>,
+    audio: Vec<i16>,
+    // Audio samples played since the tone started, keeps the wave continuous
+    // across frames
+    tone_samples: u64,
+    // The frontend's enabled cheats by index, kept across resets
+    cheats: BTreeMap<c_uint, Cheats>,
+}
+
+impl Core {
+    pub fn new(rom: &[u8]) -> Result<Self, chip8_rs::Error> {
+        let mut core = Self {
+            chip8: Chip8::new(),
+            rom: rom.to_vec(),
+            buttons: HashMap::new(),
+            palette: Palette::classic(),
+            halted: false,
+            frame: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            audio: Vec::with_capacity(SAMPLES_PER_FRAME * 2),
+            tone_samples: 0,
+            cheats: BTreeMap::new(),
+        };
+        core.reset()?;
+        Ok(core)
+    }
+
+    // Reloads the ROM, with the quirks, speed, buttons and palette from the ROM
+    // database when it knows the ROM
+    pub fn reset(&mut self) -> Result<(), chip8_rs::Error> {
+        self.chip8.reset();
+        self.chip8.load_rom(&self.rom)?;
+        self.chip8.quirks = Quirks::default();
+        self.chip8.tick_rate = TICKS_PER_FRAME;
+        self.buttons = DEFAULT_BUTTONS
+            .iter()
+            .map(|(name, key)| (name.to_string(), *key))
+            .collect();
+        self.palette = Palette::classic();
+        if let Some(info) = Database::builtin().lookup(&self.rom) {
+            info.apply(&mut self.chip8);
+            self.buttons = info.keys;
+            if let Some(palette) = info.palette {
+                self.palette = palette;
+            }
+        }
+        self.halted = false;
+        self.tone_samples = 0;
+        Ok(())
+    }
+
+    // One frame with the keys pressed according to input(port, device, id).
+    // Returns the picture as XRGB8888, row by row, and the stereo audio.
+    pub fn run(&mut self, input: impl Fn(c_uint, c_uint, c_uint) -> bool) -> (&[u32], &[i16]) {
+        let mut keys = [false; NUM_KEYS];
+        for port in 0..2 {
+            for (_, id) in BUTTONS {
+                if let Some(key) = keypad_key(&self.buttons, port, DEVICE_JOYPAD, id)
+                    && input(port, DEVICE_JOYPAD, id)
+                {
+                    keys[key] = true;
+                }
+            }
+        }
+        for (key, id) in KEYPAD.iter().enumerate() {
+            if input(0, DEVICE_KEYBOARD, *id as c_uint) {
+                keys[key] = true;
+            }
+        }
+        for (key, pressed) in keys.into_iter().enumerate() {
+            self.chip8.key_press(key, pressed);
+        }
+
+        let sounding = self.chip8.sound_timer > 0;
+        if !self.halted {
+            for cheats in self.cheats.values() {
+                cheats.apply(&mut self.chip8);
+            }
+            if let Err(err) = self.chip8.run_frame() {
+                warn!("{}", err);
+                self.halted = true;
+            }
+        }
+
+        let [background, foreground, ..] = self
+            .palette
+            .colors
+            .map(|[r, g, b]| (r as u32) << 16 | (g as u32) << 8 | b as u32);
+        for (pixel, lit) in self.frame.iter_mut().zip(self.chip8.screen) {
+            *pixel = if lit { foreground } else { background };
+        }
+
+        self.audio.clear();
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = if sounding {
+                let phase = self.tone_samples as f64 * TONE / SAMPLE_RATE;
+                self.tone_samples += 1;
+                if phase.fract() < 0.5 { VOLUME } else { -VOLUME }
+            } else {
+                self.tone_samples = 0;
+                0
+            };
+            self.audio.extend_from_slice(&[sample, sample]);
+        }
+
+        (&self.frame, &self.audio)
+    }
+
+    // Enables or disables the cheat at index, see parse_code for the format
+    pub fn set_cheat(&mut self, index: c_uint, enabled: bool, code: &str) -> Result<(), String> {
+        if enabled {
+            self.cheats.insert(index, parse_code(code)?);
+        } else {
+            self.cheats.remove(&index);
+        }
+        Ok(())
+    }
+
+    pub fn reset_cheats(&mut self) {
+        self.cheats.clear();
+    }
+}
+
+struct Frontend {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static FRONTEND: Mutex<Frontend> = Mutex::new(Frontend {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn frontend() -> std::sync::MutexGuard<'static, Frontend> {
+    FRONTEND.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn core() -> std::sync::MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    frontend().environment = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    frontend().video_refresh = Some(callback);
+}
+
+// Audio goes out in batches, single samples aren't used
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    frontend().audio_sample_batch = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    frontend().input_poll = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    frontend().input_state = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+/// # Safety
+/// info must point to a retro_system_info.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    let system_info = SystemInfo {
+        library_name: c"chip8-rs".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+        valid_extensions: c"ch8|c8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+    unsafe { info.write(system_info) };
+}
+
+/// # Safety
+/// info must point to a retro_system_av_info.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let av_info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: SCREEN_WIDTH as c_uint,
+            base_height: SCREEN_HEIGHT as c_uint,
+            max_width: SCREEN_WIDTH as c_uint,
+            max_height: SCREEN_HEIGHT as c_uint,
+            aspect_ratio: (SCREEN_WIDTH / SCREEN_HEIGHT) as f32,
+        },
+        timing: SystemTiming {
+            fps: FPS,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+    unsafe { info.write(av_info) };
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut()
+        && let Err(err) = core.reset()
+    {
+        warn!("{}", err);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    let frontend = frontend();
+    let mut core = core();
+    let Some(core) = core.as_mut() else {
+        return;
+    };
+
+    if let Some(input_poll) = frontend.input_poll {
+        unsafe { input_poll() };
+    }
+    let input_state = frontend.input_state;
+    let (frame, audio) = core.run(|port, device, id| match input_state {
+        Some(input_state) => unsafe { input_state(port, device, 0, id) != 0 },
+        None => false,
+    });
+
+    if let Some(video_refresh) = frontend.video_refresh {
+        let pitch = SCREEN_WIDTH * size_of::<u32>();
+        unsafe {
+            video_refresh(
+                frame.as_ptr().cast(),
+                SCREEN_WIDTH as c_uint,
+                SCREEN_HEIGHT as c_uint,
+                pitch,
+            )
+        };
+    }
+
+    // The frontend may take fewer frames than offered, and none when it's
+    // not playing audio
+    if let Some(audio_sample_batch) = frontend.audio_sample_batch {
+        let mut audio = audio;
+        while !audio.is_empty() {
+            let taken = unsafe { audio_sample_batch(audio.as_ptr(), audio.len() / 2) };
+            if taken == 0 {
+                break;
+            }
+            audio = &audio[(taken * 2).min(audio.len())..];
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    snapshot::SIZE
+}
+
+/// # Safety
+/// data must point to size writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = core();
+    let Some(core) = core.as_ref() else {
+        return false;
+    };
+    if size < snapshot::SIZE {
+        return false;
+    }
+
+    let bytes = core.chip8.snapshot().to_bytes();
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), data.cast(), bytes.len()) };
+    true
+}
+
+/// # Safety
+/// data must point to size readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut core = core();
+    let Some(core) = core.as_mut() else {
+        return false;
+    };
+
+    let bytes = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), size) };
+    match Snapshot::from_bytes(bytes) {
+        Ok(snapshot) => {
+            core.chip8.restore(&snapshot);
+            core.halted = false;
+            true
+        }
+        Err(err) => {
+            warn!("{}", err);
+            false
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {
+    if let Some(core) = core().as_mut() {
+        core.reset_cheats();
+    }
+}
+
+/// # Safety
+/// code must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_cheat_set(index: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+    let code = unsafe { CStr::from_ptr(code) }.to_string_lossy();
+    if let Some(core) = core().as_mut()
+        && let Err(err) = core.set_cheat(index, enabled, &code)
+    {
+        warn!("{}", err);
+    }
+}
+
+/// # Safety
+/// game must point to a retro_game_info with size bytes at data.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+
+    let mut format = PIXEL_FORMAT_XRGB8888;
+    if let Some(environment) = frontend().environment {
+        let cmd = ENVIRONMENT_SET_PIXEL_FORMAT;
+        if !unsafe { environment(cmd, (&raw mut format).cast()) } {
+            warn!("XRGB8888 isn't supported by the frontend");
+            return false;
+        }
+    }
+
+    let rom = unsafe { std::slice::from_raw_parts(game.data.cast::<u8>(), game.size) };
+    match Core::new(rom) {
+        Ok(new) => {
+            *core() = Some(new);
+            true
+        }
+        Err(err) => {
+            warn!("{}", err);
+            false
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+// Memory isn't exposed, writes from the frontend would get past the cache of
+// decoded instructions
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
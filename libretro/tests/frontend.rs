@@ -0,0 +1,320 @@
+// A minimal libretro frontend: loads a ROM through the C API, feeds it input
+// and collects what the core sends back
+
+use chip8_libretro::ffi::*;
+use chip8_libretro::*;
+use std::ffi::{CStr, c_uint, c_void};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct Frontend {
+    pixel_format: Option<c_uint>,
+    frames: Vec<Vec<u32>>,
+    audio_frames: usize,
+    audio_peak: i16,
+    // (port, device, id) held down
+    pressed: Vec<(c_uint, c_uint, c_uint)>,
+    polls: usize,
+}
+
+static FRONTEND: Mutex<Option<Frontend>> = Mutex::new(None);
+
+// The core is global, so tests take turns
+static LOCK: Mutex<()> = Mutex::new(());
+
+fn with_frontend<T>(f: impl FnOnce(&mut Frontend) -> T) -> T {
+    f(FRONTEND
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Frontend::default))
+}
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        ENVIRONMENT_SET_PIXEL_FORMAT => {
+            let format = unsafe { *data.cast::<c_uint>() };
+            with_frontend(|frontend| frontend.pixel_format = Some(format));
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(
+    data: *const c_void,
+    width: c_uint,
+    height: c_uint,
+    pitch: usize,
+) {
+    let mut frame = Vec::new();
+    for row in 0..height as usize {
+        let line = unsafe { data.cast::<u8>().add(row * pitch).cast::<u32>() };
+        frame.extend_from_slice(unsafe { std::slice::from_raw_parts(line, width as usize) });
+    }
+    with_frontend(|frontend| frontend.frames.push(frame));
+}
+
+unsafe extern "C" fn audio_sample(_left: i16, _right: i16) {}
+
+// Takes at most 500 frames at a time, like a frontend with a small buffer
+unsafe extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    let frames = frames.min(500);
+    let samples = unsafe { std::slice::from_raw_parts(data, frames * 2) };
+    with_frontend(|frontend| {
+        frontend.audio_frames += frames;
+        frontend.audio_peak = samples
+            .iter()
+            .fold(frontend.audio_peak, |peak, s| peak.max(*s));
+    });
+    frames
+}
+
+unsafe extern "C" fn input_poll() {
+    with_frontend(|frontend| frontend.polls += 1);
+}
+
+unsafe extern "C" fn input_state(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    with_frontend(|frontend| frontend.pressed.contains(&(port, device, id)) as i16)
+}
+
+fn rom(name: &str) -> Vec<u8> {
+    std::fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../rom")
+            .join(name),
+    )
+    .unwrap()
+}
+
+// Sets up the callbacks and loads the ROM, like a frontend starting a game
+fn start(rom: &[u8]) -> bool {
+    *FRONTEND.lock().unwrap_or_else(|err| err.into_inner()) = None;
+    assert_eq!(retro_api_version(), 1);
+    retro_set_environment(environment);
+    retro_set_video_refresh(video_refresh);
+    retro_set_audio_sample(audio_sample);
+    retro_set_audio_sample_batch(audio_sample_batch);
+    retro_set_input_poll(input_poll);
+    retro_set_input_state(input_state);
+    retro_init();
+
+    let game = GameInfo {
+        path: std::ptr::null(),
+        data: rom.as_ptr().cast(),
+        size: rom.len(),
+        meta: std::ptr::null(),
+    };
+    unsafe { retro_load_game(&game) }
+}
+
+fn run(frames: usize) {
+    for _ in 0..frames {
+        retro_run();
+    }
+}
+
+fn last_frame() -> Vec<u32> {
+    with_frontend(|frontend| frontend.frames.last().unwrap().clone())
+}
+
+fn stop() {
+    retro_unload_game();
+    retro_deinit();
+}
+
+#[test]
+fn info() {
+    let mut info = std::mem::MaybeUninit::<SystemInfo>::uninit();
+    let info = unsafe {
+        retro_get_system_info(info.as_mut_ptr());
+        info.assume_init()
+    };
+    assert_eq!(unsafe { CStr::from_ptr(info.library_name) }, c"chip8-rs");
+    assert!(!info.need_fullpath);
+
+    let mut av_info = std::mem::MaybeUninit::<SystemAvInfo>::uninit();
+    let av_info = unsafe {
+        retro_get_system_av_info(av_info.as_mut_ptr());
+        av_info.assume_init()
+    };
+    assert_eq!(
+        (av_info.geometry.base_width, av_info.geometry.base_height),
+        (64, 32)
+    );
+    assert_eq!(av_info.geometry.aspect_ratio, 2.0);
+    assert_eq!(av_info.timing.fps, 60.0);
+}
+
+#[test]
+fn video_and_audio() {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    assert!(start(&rom("ibm.ch8")));
+    run(30);
+
+    with_frontend(|frontend| {
+        assert_eq!(frontend.pixel_format, Some(PIXEL_FORMAT_XRGB8888));
+        assert_eq!(frontend.frames.len(), 30);
+        assert_eq!(frontend.polls, 30);
+        // Every sample is taken even though the frontend takes them in parts
+        assert_eq!(frontend.audio_frames, 30 * SAMPLES_PER_FRAME);
+        assert_eq!(frontend.audio_peak, 0);
+    });
+    let frame = last_frame();
+    assert_eq!(frame.len(), 64 * 32);
+    assert!(
+        frame
+            .iter()
+            .all(|pixel| [0x000000, 0xFFFFFF].contains(pixel))
+    );
+    assert!(frame.iter().filter(|pixel| **pixel == 0xFFFFFF).count() > 100);
+
+    // LD V0, 10; LD ST, V0; JP 204
+    stop();
+    assert!(start(&[0x60, 0x10, 0xF0, 0x18, 0x12, 0x04]));
+    run(2);
+    with_frontend(|frontend| assert!(frontend.audio_peak > 0));
+    stop();
+}
+
+// Draws the font glyph of a key, as the input program does for the key it reads
+fn glyph(key: u8) -> Vec<u32> {
+    // LD V0, key; LD F, V0; DRW V1, V1, 5; JP 206
+    assert!(start(&[0x60, key, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06]));
+    run(1);
+    stop();
+    last_frame()
+}
+
+#[test]
+fn input() {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    // LD V0, K; LD F, V0; CLS; DRW V1, V1, 5; JP 200
+    let program = [0xF0, 0x0A, 0xF0, 0x29, 0x00, 0xE0, 0xD1, 0x15, 0x12, 0x00];
+    let press = |port, device, id| {
+        assert!(start(&program));
+        with_frontend(|frontend| frontend.pressed = vec![(port, device, id)]);
+        run(2);
+        with_frontend(|frontend| frontend.pressed.clear());
+        run(2);
+        stop();
+        last_frame()
+    };
+
+    // The keypad on the keyboard
+    for (key, id) in [(0x0, b'x'), (0xC, b'4'), (0xF, b'v')] {
+        let screen = press(0, DEVICE_KEYBOARD, id as c_uint);
+        assert!(screen == glyph(key), "{:X}", key);
+    }
+
+    // Joypad with the default buttons
+    let screen = press(0, DEVICE_JOYPAD, DEVICE_ID_JOYPAD_LEFT);
+    assert!(screen == glyph(0x7));
+    assert!(screen != glyph(0x8));
+}
+
+#[test]
+fn database_buttons() {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    // BRIX moves the paddle with 4 and 6
+    assert!(start(&rom("BRIX")));
+    run(10);
+    let before = last_frame();
+    with_frontend(|frontend| frontend.pressed = vec![(0, DEVICE_JOYPAD, DEVICE_ID_JOYPAD_LEFT)]);
+    run(10);
+    let after = last_frame();
+    // The paddle is on the bottom row
+    assert!(before[31 * 64..] != after[31 * 64..]);
+    stop();
+}
+
+// Offset of SP in a save state, after the magic, RAM, V0-VF and I. The PC and
+// the stack follow it
+const SP: usize = 4 + 4096 + 16 + 2;
+
+#[test]
+fn save_states() {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    assert!(start(&rom("BRIX")));
+    run(120);
+
+    let size = retro_serialize_size();
+    let mut state = vec![0u8; size];
+    assert!(unsafe { retro_serialize(state.as_mut_ptr().cast(), size) });
+    run(300);
+    let later = last_frame();
+
+    assert!(unsafe { retro_unserialize(state.as_ptr().cast(), size) });
+    run(300);
+    assert!(last_frame() == later);
+
+    // Truncated or foreign states are refused
+    assert!(!unsafe { retro_serialize(state.as_mut_ptr().cast(), size - 1) });
+    assert!(!unsafe { retro_unserialize(state.as_ptr().cast(), size - 1) });
+    state[0] ^= 0xFF;
+    assert!(!unsafe { retro_unserialize(state.as_ptr().cast(), size) });
+    state[0] ^= 0xFF;
+
+    // A RET to a return address past the end of RAM, at the saved PC
+    let pc = u16::from_le_bytes([state[SP + 2], state[SP + 3]]) as usize;
+    state[4 + pc..4 + pc + 2].copy_from_slice(&[0x00, 0xEE]);
+    state[SP..SP + 2].copy_from_slice(&1u16.to_le_bytes());
+    state[SP + 4..SP + 6].copy_from_slice(&0xFFFFu16.to_le_bytes());
+    assert!(!unsafe { retro_unserialize(state.as_ptr().cast(), size) });
+    run(1);
+    state[SP + 4..SP + 6].copy_from_slice(&0x300u16.to_le_bytes());
+    assert!(unsafe { retro_unserialize(state.as_ptr().cast(), size) });
+    run(1);
+    stop();
+
+    // Nothing to save without a game
+    assert!(!unsafe { retro_serialize(state.as_mut_ptr().cast(), size) });
+}
+
+#[test]
+fn cheats() {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let (zero, ten) = (glyph(0x0), glyph(0xA));
+    // Draws the glyph of the byte at 300 once a frame: LD V2, 1; LD DT, V2;
+    // CLS; LD I, 300; LD V0, [I]; LD F, V0; DRW V1, V1, 5; then waits for the
+    // timer and starts over
+    let program = [
+        0x62, 0x01, 0xF2, 0x15, 0x00, 0xE0, 0xA3, 0x00, 0xF0, 0x65, 0xF0, 0x29, 0xD1, 0x15, 0xF2,
+        0x07, 0x32, 0x00, 0x12, 0x0E, 0x12, 0x00,
+    ];
+    assert!(start(&program));
+    run(1);
+    assert!(last_frame() == zero);
+
+    unsafe {
+        retro_cheat_set(0, true, c"301:07 + 300:0a".as_ptr());
+        // Ignored, the others still apply
+        retro_cheat_set(1, true, c"1000:01".as_ptr());
+        retro_cheat_set(2, true, c"300".as_ptr());
+    }
+    run(1);
+    assert!(last_frame() == ten);
+
+    // Still frozen after a reset, until the frontend clears them
+    retro_reset();
+    run(1);
+    assert!(last_frame() == ten);
+    retro_cheat_reset();
+    retro_reset();
+    run(1);
+    assert!(last_frame() == zero);
+    stop();
+}
+
+#[test]
+fn bad_roms() {
+    let _lock = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    assert!(!start(&vec![0; 4096]));
+
+    // Runs into an invalid opcode and stops there, the frontend keeps getting
+    // frames
+    assert!(start(&[0x00, 0xEE]));
+    run(3);
+    with_frontend(|frontend| assert_eq!(frontend.frames.len(), 3));
+    stop();
+}
@@ -0,0 +1,219 @@
+// Python bindings for the core, built with maturin:
+//
+//     cd python && maturin develop --release
+//
+//     import chip8, numpy as np
+//     emu = chip8.Chip8()
+//     emu.load(open("rom/BRIX", "rb").read())
+//     emu.run_frame()
+//     screen = np.asarray(emu.screen())  # 32x64 uint8, 0 or 1
+
+use chip8_rs::database::Database;
+use chip8_rs::quirks::Quirks;
+use chip8_rs::snapshot::Snapshot;
+use chip8_rs::{Chip8, NUM_KEYS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, TICKS_PER_FRAME};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyMemoryView};
+
+// Raised when the program can't go on, e.g. an invalid opcode
+create_exception!(chip8, Chip8Error, PyException);
+
+fn to_py(error: chip8_rs::Error) -> PyErr {
+    Chip8Error::new_err(error.to_string())
+}
+
+// Read-only buffer over a copy of the bytes, shaped like the screen or memory
+fn buffer<'py>(py: Python<'py>, bytes: &[u8], shape: &[usize]) -> PyResult<Bound<'py, PyAny>> {
+    let view = PyMemoryView::from(&PyBytes::new(py, bytes))?;
+    view.call_method1("cast", ("B", shape.to_vec()))
+}
+
+// A saved machine, from Chip8.save_state
+#[pyclass(name = "State", frozen)]
+struct State(Snapshot);
+
+#[pyclass(name = "Chip8", unsendable)]
+struct PyChip8 {
+    chip8: Chip8,
+}
+
+#[pymethods]
+impl PyChip8 {
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<u64>) -> Self {
+        let mut chip8 = Chip8::new();
+        if let Some(seed) = seed {
+            chip8.seed(seed);
+        }
+        Self { chip8 }
+    }
+
+    // Resets and loads a ROM with the default quirks and tick rate. With
+    // database, those of the platform the ROM was written for are used instead
+    // when the ROM database knows it.
+    #[pyo3(signature = (rom, database=true))]
+    fn load(&mut self, rom: &[u8], database: bool) -> PyResult<()> {
+        self.chip8.reset();
+        self.chip8.load_rom(rom).map_err(to_py)?;
+        self.chip8.quirks = Quirks::default();
+        self.chip8.tick_rate = TICKS_PER_FRAME;
+        if database && let Some(info) = Database::builtin().lookup(rom) {
+            info.apply(&mut self.chip8);
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.chip8.reset();
+    }
+
+    // Makes CXNN return the same numbers on every run with the same seed
+    fn seed(&mut self, seed: u64) {
+        self.chip8.seed(seed);
+    }
+
+    // One instruction
+    fn step(&mut self) -> PyResult<()> {
+        self.chip8.step().map_err(to_py)
+    }
+
+    // tick_rate instructions and a timer tick per frame
+    #[pyo3(signature = (frames=1))]
+    fn run_frame(&mut self, frames: u32) -> PyResult<()> {
+        for _ in 0..frames {
+            self.chip8.run_frame().map_err(to_py)?;
+        }
+        Ok(())
+    }
+
+    fn key_press(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        if key >= NUM_KEYS {
+            return Err(PyValueError::new_err(format!("No key {:X}", key)));
+        }
+        self.chip8.key_press(key, pressed);
+        Ok(())
+    }
+
+    // 0 or 1 per pixel, 32 rows of 64
+    fn screen<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let pixels: Vec<u8> = self.chip8.screen.iter().map(|pixel| *pixel as u8).collect();
+        buffer(py, &pixels, &[SCREEN_HEIGHT, SCREEN_WIDTH])
+    }
+
+    fn memory<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let memory = self.chip8.memory();
+        buffer(py, memory, &[memory.len()])
+    }
+
+    // Wraps around the end of RAM like the program's own writes
+    fn write_memory(&mut self, addr: u16, data: &[u8]) {
+        self.chip8.write_memory(addr, data);
+    }
+
+    fn save_state(&self) -> State {
+        State(self.chip8.snapshot())
+    }
+
+    fn load_state(&mut self, state: &State) {
+        self.chip8.restore(&state.0);
+    }
+
+    // Quirks by their name in the ROM database, e.g. "vblank"
+    fn set_quirk(&mut self, name: &str, value: bool) -> PyResult<()> {
+        self.chip8
+            .quirks
+            .set(name, value)
+            .map_err(PyValueError::new_err)
+    }
+
+    #[getter]
+    fn v(&self) -> Vec<u8> {
+        self.chip8.v_reg.to_vec()
+    }
+
+    #[setter]
+    fn set_v(&mut self, v: Vec<u8>) -> PyResult<()> {
+        self.chip8.v_reg = v
+            .try_into()
+            .map_err(|_| PyValueError::new_err("V needs 16 registers"))?;
+        Ok(())
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.chip8.i_reg
+    }
+
+    #[setter]
+    fn set_i(&mut self, i: u16) {
+        self.chip8.i_reg = i;
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.chip8.pc
+    }
+
+    // Wraps at the end of RAM like jumps do
+    #[setter]
+    fn set_pc(&mut self, pc: u16) {
+        self.chip8.pc = pc % RAM_SIZE as u16;
+    }
+
+    #[getter]
+    fn sp(&self) -> u16 {
+        self.chip8.sp
+    }
+
+    #[getter]
+    fn stack(&self) -> Vec<u16> {
+        self.chip8.stack[..self.chip8.sp as usize].to_vec()
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.chip8.delay_timer
+    }
+
+    #[setter]
+    fn set_delay_timer(&mut self, value: u8) {
+        self.chip8.delay_timer = value;
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.chip8.sound_timer
+    }
+
+    #[setter]
+    fn set_sound_timer(&mut self, value: u8) {
+        self.chip8.sound_timer = value;
+    }
+
+    #[getter]
+    fn tick_rate(&self) -> u16 {
+        self.chip8.tick_rate
+    }
+
+    #[setter]
+    fn set_tick_rate(&mut self, value: u16) {
+        self.chip8.tick_rate = value;
+    }
+
+    // Instructions run since the last reset
+    #[getter]
+    fn cycles(&self) -> u64 {
+        self.chip8.cycles
+    }
+}
+
+#[pymodule]
+fn chip8(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyChip8>()?;
+    m.add_class::<State>()?;
+    m.add("Chip8Error", m.py().get_type::<Chip8Error>())?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+# Run from python/ after `maturin develop`: python -m unittest discover tests
+
+import pathlib
+import unittest
+
+import chip8
+
+ROMS = pathlib.Path(__file__).resolve().parents[2] / "rom"
+
+
+def rom(name):
+    return (ROMS / name).read_bytes()
+
+
+class Chip8Test(unittest.TestCase):
+    def test_registers(self):
+        emu = chip8.Chip8()
+        emu.load(bytes([0x60, 0x2A, 0xA3, 0x00, 0x23, 0x00]))
+        emu.step()
+        emu.step()
+        self.assertEqual(emu.v[0], 0x2A)
+        self.assertEqual(emu.i, 0x300)
+        self.assertEqual(emu.pc, 0x204)
+
+        emu.step()
+        self.assertEqual((emu.sp, emu.stack), (1, [0x206]))
+        self.assertEqual(emu.cycles, 3)
+
+        emu.pc = 0x1202
+        self.assertEqual(emu.pc, 0x202)
+
+        emu.v = list(range(16))
+        self.assertEqual(emu.v[15], 15)
+        with self.assertRaises(ValueError):
+            emu.v = [0]
+
+    def test_screen(self):
+        emu = chip8.Chip8()
+        emu.load(rom("ibm.ch8"))
+        emu.run_frame(60)
+        screen = emu.screen()
+        self.assertEqual(screen.shape, (32, 64))
+        self.assertEqual(screen.format, "B")
+        self.assertTrue(screen.readonly)
+        self.assertTrue(all(pixel in (0, 1) for row in screen.tolist() for pixel in row))
+        self.assertGreater(sum(map(sum, screen.tolist())), 100)
+
+    def test_memory(self):
+        emu = chip8.Chip8()
+        emu.load(bytes([0x12, 0x00]))
+        memory = emu.memory()
+        self.assertEqual(memory.shape, (4096,))
+        self.assertEqual(memory[0x200:0x202].tobytes(), b"\x12\x00")
+
+        emu.write_memory(0xFFF, b"\xAB\xCD")
+        self.assertEqual(emu.memory()[0xFFF], 0xAB)
+        self.assertEqual(emu.memory()[0], 0xCD)
+
+    def test_keys(self):
+        emu = chip8.Chip8()
+        # LD V0, K
+        emu.load(bytes([0xF0, 0x0A, 0x12, 0x02]))
+        emu.run_frame()
+        emu.key_press(0xB, True)
+        emu.run_frame()
+        emu.key_press(0xB, False)
+        emu.run_frame()
+        self.assertEqual(emu.v[0], 0xB)
+        with self.assertRaises(ValueError):
+            emu.key_press(16, True)
+
+    def test_state(self):
+        emu = chip8.Chip8(seed=1)
+        emu.load(rom("BRIX"))
+        emu.run_frame(100)
+        cycles = emu.cycles
+        state = emu.save_state()
+        emu.run_frame(200)
+        later = (emu.screen().tobytes(), emu.memory().tobytes(), emu.v)
+
+        emu.load_state(state)
+        self.assertEqual(emu.cycles, cycles)
+        emu.run_frame(200)
+        self.assertEqual((emu.screen().tobytes(), emu.memory().tobytes(), emu.v), later)
+
+    def test_quirks(self):
+        emu = chip8.Chip8()
+        emu.load(rom("BRIX"))
+        # From the ROM database
+        self.assertEqual(emu.tick_rate, 30)
+        emu.load(rom("BRIX"), database=False)
+        self.assertEqual(emu.tick_rate, 10)
+
+        emu.set_quirk("vblank", True)
+        with self.assertRaises(ValueError):
+            emu.set_quirk("turbo", True)
+
+    def test_errors(self):
+        emu = chip8.Chip8()
+        emu.load(bytes([0x00, 0xEE]))
+        with self.assertRaises(chip8.Chip8Error) as error:
+            emu.step()
+        self.assertIn("Return with an empty stack", str(error.exception))
+        with self.assertRaises(chip8.Chip8Error):
+            emu.load(bytes(4096))
+
+
+if __name__ == "__main__":
+    unittest.main()
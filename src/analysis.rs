@@ -0,0 +1,427 @@
+// Static control-flow analysis: follows jumps, calls and skips from 0x200
+// without running anything, to tell the ROM's code from its sprites.
+//
+// Calls are assumed to return to the instruction after them. BNNN jumps
+// depend on V0 and aren't followed, they're listed as unresolved and whatever
+// they lead to shows up as unreachable. Bytes that nothing reaches are data
+// from the addresses LD I points at, and otherwise unreachable code when
+// they decode as instructions or data when they don't.
+
+use crate::disasm::disassemble;
+use crate::instruction::Instruction;
+use crate::{ADDR_MASK, Chip8, Error, RAM_SIZE, START_ADDR};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EdgeKind {
+    // On to the next instruction, including after a call or a skip that
+    // isn't taken
+    Next,
+    Jump,
+    // A skip that is taken
+    Skip,
+    Call,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub to: u16,
+}
+
+// Instructions that run one after the other, only entered at the start
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub start: u16,
+    // Address after the last instruction
+    pub end: u16,
+    // None after a return, a BNNN or an invalid opcode
+    pub edges: Vec<Edge>,
+}
+
+impl Block {
+    pub fn instructions(&self) -> impl Iterator<Item = u16> + use<> {
+        (self.start..self.end).step_by(2)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subroutine {
+    pub entry: u16,
+    // Starts of the blocks reached from the entry without going into calls
+    pub blocks: Vec<u16>,
+    pub returns: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Code,
+    Data,
+    Unreachable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub kind: Kind,
+    pub range: Range<u16>,
+}
+
+pub struct Cfg {
+    pub blocks: BTreeMap<u16, Block>,
+    // By entry
+    pub subroutines: Vec<Subroutine>,
+    // BNNN instructions
+    pub unresolved: Vec<u16>,
+    // Invalid opcodes that would run
+    pub invalid: Vec<u16>,
+    // Where LD I points
+    pub data_refs: BTreeSet<u16>,
+    // The ROM split up, in order
+    pub regions: Vec<Region>,
+    memory: [u8; RAM_SIZE],
+}
+
+fn opcode(memory: &[u8; RAM_SIZE], addr: u16) -> u16 {
+    let next = (addr.wrapping_add(1) & ADDR_MASK) as usize;
+    (memory[addr as usize] as u16) << 8 | memory[next] as u16
+}
+
+// Where an instruction goes, None when it goes on to the next one
+fn edges(addr: u16, instruction: Instruction) -> Option<Vec<Edge>> {
+    let next = addr.wrapping_add(2) & ADDR_MASK;
+    let edge = |kind, to| Edge { kind, to };
+    match instruction {
+        Instruction::Jump(nnn) => Some(vec![edge(EdgeKind::Jump, nnn)]),
+        Instruction::Call(nnn) => Some(vec![edge(EdgeKind::Call, nnn), edge(EdgeKind::Next, next)]),
+        Instruction::Return | Instruction::JumpOffset(_) | Instruction::Invalid(_) => Some(vec![]),
+        Instruction::SkipEqual(..)
+        | Instruction::SkipNotEqual(..)
+        | Instruction::SkipEqualReg(..)
+        | Instruction::SkipNotEqualReg(..)
+        | Instruction::SkipKey(_)
+        | Instruction::SkipNotKey(_) => Some(vec![
+            edge(EdgeKind::Next, next),
+            edge(EdgeKind::Skip, next.wrapping_add(2) & ADDR_MASK),
+        ]),
+        _ => None,
+    }
+}
+
+// Whether bytes nothing reaches look like code: whole instructions that
+// decode, and not just zeros
+fn looks_like_code(memory: &[u8; RAM_SIZE], range: &Range<u16>) -> bool {
+    range.len() >= 2
+        && range.len().is_multiple_of(2)
+        && range.clone().step_by(2).all(|addr| {
+            let op = opcode(memory, addr);
+            op != 0 && !matches!(Instruction::decode(op), Instruction::Invalid(_))
+        })
+}
+
+pub fn analyze(rom: &[u8]) -> Result<Cfg, Error> {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom)?;
+    let memory = *chip8.memory();
+    let decode = |addr| Instruction::decode(opcode(&memory, addr));
+
+    // Every instruction that can run, and where blocks start
+    let mut reached = vec![false; RAM_SIZE];
+    let mut leaders = BTreeSet::from([START_ADDR]);
+    let mut calls = BTreeSet::new();
+    let mut queue = vec![START_ADDR];
+    while let Some(addr) = queue.pop() {
+        if reached[addr as usize] {
+            continue;
+        }
+        reached[addr as usize] = true;
+        match edges(addr, decode(addr)) {
+            Some(edges) => {
+                for edge in edges {
+                    if edge.kind == EdgeKind::Call {
+                        calls.insert(edge.to);
+                    }
+                    leaders.insert(edge.to);
+                    queue.push(edge.to);
+                }
+            }
+            None => {
+                let next = addr.wrapping_add(2) & ADDR_MASK;
+                // Blocks don't wrap around the end of RAM
+                if next < addr {
+                    leaders.insert(next);
+                }
+                queue.push(next);
+            }
+        }
+    }
+
+    let mut cfg = Cfg {
+        blocks: BTreeMap::new(),
+        subroutines: Vec::new(),
+        unresolved: Vec::new(),
+        invalid: Vec::new(),
+        data_refs: BTreeSet::new(),
+        regions: Vec::new(),
+        memory,
+    };
+    let mut code = vec![false; RAM_SIZE];
+    for start in &leaders {
+        let mut addr = *start;
+        let block = loop {
+            let instruction = decode(addr);
+            code[addr as usize] = true;
+            code[(addr.wrapping_add(1) & ADDR_MASK) as usize] = true;
+            match instruction {
+                Instruction::JumpOffset(_) => cfg.unresolved.push(addr),
+                Instruction::Invalid(_) => cfg.invalid.push(addr),
+                Instruction::SetIndex(nnn) => {
+                    cfg.data_refs.insert(nnn);
+                }
+                _ => {}
+            }
+
+            let next = addr.wrapping_add(2) & ADDR_MASK;
+            if let Some(edges) = edges(addr, instruction) {
+                break Block {
+                    start: *start,
+                    end: addr + 2,
+                    edges,
+                };
+            }
+            if leaders.contains(&next) {
+                break Block {
+                    start: *start,
+                    end: addr + 2,
+                    edges: vec![Edge {
+                        kind: EdgeKind::Next,
+                        to: next,
+                    }],
+                };
+            }
+            addr = next;
+        };
+        cfg.blocks.insert(*start, block);
+    }
+
+    for entry in calls {
+        let mut blocks = BTreeSet::new();
+        let mut queue = vec![entry];
+        while let Some(start) = queue.pop() {
+            if blocks.insert(start) {
+                queue.extend(
+                    cfg.blocks[&start]
+                        .edges
+                        .iter()
+                        .filter(|edge| edge.kind != EdgeKind::Call)
+                        .map(|edge| edge.to),
+                );
+            }
+        }
+        let returns = blocks.iter().any(|start| {
+            let block = &cfg.blocks[start];
+            decode(block.end - 2) == Instruction::Return
+        });
+        cfg.subroutines.push(Subroutine {
+            entry,
+            blocks: blocks.into_iter().collect(),
+            returns,
+        });
+    }
+
+    // The ROM in runs of code and of everything else. The rest is split
+    // where LD I points.
+    let end = START_ADDR + rom.len() as u16;
+    let mut addr = START_ADDR;
+    while addr < end {
+        let start = addr;
+        let is_code = code[addr as usize];
+        addr += 1;
+        while addr < end
+            && code[addr as usize] == is_code
+            && (is_code || !cfg.data_refs.contains(&addr))
+        {
+            addr += 1;
+        }
+        let range = start..addr;
+        let kind = if is_code {
+            Kind::Code
+        } else if !cfg.data_refs.contains(&start) && looks_like_code(&memory, &range) {
+            Kind::Unreachable
+        } else {
+            Kind::Data
+        };
+        match cfg.regions.last_mut() {
+            Some(last) if last.kind == kind && last.range.end == start => last.range.end = addr,
+            _ => cfg.regions.push(Region { kind, range }),
+        }
+    }
+
+    Ok(cfg)
+}
+
+impl Cfg {
+    pub fn kind(&self, addr: u16) -> Option<Kind> {
+        self.regions
+            .iter()
+            .find(|region| region.range.contains(&addr))
+            .map(|region| region.kind)
+    }
+
+    // The block an instruction is in
+    pub fn block(&self, addr: u16) -> Option<&Block> {
+        let (_, block) = self.blocks.range(..=addr).next_back()?;
+        (addr < block.end).then_some(block)
+    }
+
+    // What was found, and the regions
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "{} blocks, {} subroutines\n",
+            self.blocks.len(),
+            self.subroutines.len()
+        );
+        for subroutine in &self.subroutines {
+            text += &format!(
+                "Subroutine {:03X}: {} block{}{}\n",
+                subroutine.entry,
+                subroutine.blocks.len(),
+                if subroutine.blocks.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                if subroutine.returns {
+                    ""
+                } else {
+                    ", doesn't return"
+                }
+            );
+        }
+        for addr in &self.unresolved {
+            let op = opcode(&self.memory, *addr);
+            text += &format!("Unresolved {:03X}: {}\n", addr, disassemble(op));
+        }
+        for addr in &self.invalid {
+            text += &format!(
+                "Invalid opcode {:03X}: {:04X}\n",
+                addr,
+                opcode(&self.memory, *addr)
+            );
+        }
+        for region in &self.regions {
+            let kind = match region.kind {
+                Kind::Code => "code",
+                Kind::Data => "data",
+                Kind::Unreachable => "unreachable",
+            };
+            text += &format!(
+                "{:03X}-{:03X} {}\n",
+                region.range.start,
+                region.range.end - 1,
+                kind
+            );
+        }
+        text
+    }
+
+    // The ROM as code, unreachable code and data, e.g.
+    //
+    //  sub 2A0:
+    //  2A0 6001 LD V0, 01
+    //  2A2 00EE RET
+    //  2A4   3C ..####..         data
+    //  2A6 1300 JP 300           unreachable
+    pub fn listing(&self) -> String {
+        let subroutines: BTreeSet<u16> = self.subroutines.iter().map(|sub| sub.entry).collect();
+        let starts: BTreeSet<u16> = self.blocks.values().flat_map(Block::instructions).collect();
+        let mut text = String::new();
+        for region in &self.regions {
+            let mut addr = region.range.start;
+            while addr < region.range.end {
+                let op = opcode(&self.memory, addr);
+                match region.kind {
+                    Kind::Code if starts.contains(&addr) => {
+                        if subroutines.contains(&addr) {
+                            text += &format!("sub {:03X}:\n", addr);
+                        }
+                        let note = if self.unresolved.contains(&addr) {
+                            "unresolved"
+                        } else if self.invalid.contains(&addr) {
+                            "invalid"
+                        } else {
+                            ""
+                        };
+                        let line =
+                            format!("{:03X} {:04X} {:<16} {}", addr, op, disassemble(op), note);
+                        text += line.trim_end();
+                        text += "\n";
+                        addr += 2;
+                    }
+                    Kind::Unreachable => {
+                        text += &format!(
+                            "{:03X} {:04X} {:<16} unreachable\n",
+                            addr,
+                            op,
+                            disassemble(op)
+                        );
+                        addr += 2;
+                    }
+                    // Data, or a byte of code only reached from a misaligned
+                    // instruction
+                    _ => {
+                        let byte = self.memory[addr as usize];
+                        let pixels: String = (0..8)
+                            .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+                            .collect();
+                        text += &format!("{:03X}   {:02X} {:<16} data\n", addr, byte, pixels);
+                        addr += 1;
+                    }
+                }
+            }
+        }
+        text
+    }
+
+    // Graphviz: one node per block with its instructions, subroutine entries
+    // in bold and blocks ending in an unresolved jump in red
+    pub fn dot(&self) -> String {
+        let entries: BTreeSet<u16> = self.subroutines.iter().map(|sub| sub.entry).collect();
+        let mut text =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if entries.contains(&block.start) {
+                label += &format!("sub {:03X}\\l", block.start);
+            }
+            for addr in block.instructions() {
+                label += &format!(
+                    "{:03X} {}\\l",
+                    addr,
+                    disassemble(opcode(&self.memory, addr))
+                );
+            }
+            let mut style = String::new();
+            if entries.contains(&block.start) {
+                style += ", style=bold";
+            }
+            if self.unresolved.contains(&(block.end - 2)) {
+                style += ", color=red";
+            }
+            text += &format!("    b{:03X} [label=\"{}\"{}];\n", block.start, label, style);
+        }
+        for block in self.blocks.values() {
+            for edge in &block.edges {
+                let label = match edge.kind {
+                    EdgeKind::Next => String::new(),
+                    EdgeKind::Jump => " [label=\"jump\"]".to_string(),
+                    EdgeKind::Skip => " [label=\"skip\"]".to_string(),
+                    EdgeKind::Call => " [label=\"call\", style=dashed]".to_string(),
+                };
+                text += &format!("    b{:03X} -> b{:03X}{};\n", block.start, edge.to, label);
+            }
+        }
+        text += "}\n";
+        text
+    }
+}
@@ -0,0 +1,135 @@
+use crate::renderer::BYTES_PER_PIXEL;
+use std::{fs, fs::File, io::BufWriter, path::Path, path::PathBuf};
+
+// Frames per second the emulator runs at, used for GIF frame delays
+const FPS: u64 = 60;
+
+// NeuQuant speed for GIF frames, 1 is slowest. Screens only have a few colours.
+const GIF_QUANTIZE_SPEED: i32 = 20;
+
+// Nearest neighbour upscale of RGB24 pixels
+pub fn scale_pixels(pixels: &[u8], width: usize, height: usize, scale: usize) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity(pixels.len() * scale * scale);
+    for y in 0..height {
+        let row = &pixels[y * width * BYTES_PER_PIXEL..(y + 1) * width * BYTES_PER_PIXEL];
+        let mut scaled_row = Vec::with_capacity(row.len() * scale);
+        for pixel in row.chunks_exact(BYTES_PER_PIXEL) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..scale {
+            scaled.extend_from_slice(&scaled_row);
+        }
+    }
+    scaled
+}
+
+pub fn save_png(
+    path: &Path,
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    scale: usize,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        (width * scale) as u32,
+        (height * scale) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer
+        .write_image_data(&scale_pixels(pixels, width, height, scale))
+        .map_err(|err| err.to_string())
+}
+
+// Size of a scaled frame, GIF sizes are 16 bit
+fn gif_size(width: usize, height: usize, scale: usize) -> Result<(u16, u16), String> {
+    let size = |len: usize| {
+        len.checked_mul(scale)
+            .and_then(|len| u16::try_from(len).ok())
+    };
+    match (size(width), size(height)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(format!(
+            "{}x{} at scale {} is too large for a GIF",
+            width, height, scale
+        )),
+    }
+}
+
+enum Target {
+    Gif(gif::Encoder<BufWriter<File>>),
+    // Numbered PNGs in a directory
+    Sequence(PathBuf),
+}
+
+// Records frames to an animated GIF when the path ends in .gif,
+// otherwise to a directory of numbered PNGs
+pub struct Recorder {
+    target: Target,
+    width: usize,
+    height: usize,
+    scale: usize,
+    frames: u64,
+}
+
+impl Recorder {
+    pub fn new(path: &Path, width: usize, height: usize, scale: usize) -> Result<Self, String> {
+        let is_gif = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+
+        let target = if is_gif {
+            let (gif_width, gif_height) = gif_size(width, height, scale)?;
+            let file = File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), gif_width, gif_height, &[])
+                .map_err(|err| err.to_string())?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(|err| err.to_string())?;
+            Target::Gif(encoder)
+        } else {
+            fs::create_dir_all(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            Target::Sequence(path.to_path_buf())
+        };
+
+        Ok(Self {
+            target,
+            width,
+            height,
+            scale,
+            frames: 0,
+        })
+    }
+
+    pub fn add_frame(&mut self, pixels: &[u8]) -> Result<(), String> {
+        match &mut self.target {
+            Target::Gif(encoder) => {
+                let (gif_width, gif_height) = gif_size(self.width, self.height, self.scale)?;
+                let scaled = scale_pixels(pixels, self.width, self.height, self.scale);
+                let mut frame =
+                    gif::Frame::from_rgb_speed(gif_width, gif_height, &scaled, GIF_QUANTIZE_SPEED);
+                // GIF delays are in 1/100 s, spread the rounding over the frames
+                frame.delay = ((self.frames + 1) * 100 / FPS - self.frames * 100 / FPS) as u16;
+                encoder.write_frame(&frame).map_err(|err| err.to_string())?;
+            }
+            Target::Sequence(dir) => {
+                let path = dir.join(format!("{:06}.png", self.frames));
+                save_png(&path, pixels, self.width, self.height, self.scale)?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
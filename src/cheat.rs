@@ -0,0 +1,181 @@
+// Cheats: finding where a game keeps something and freezing it there.
+//
+// A search starts out with every address and narrows them down by comparing
+// memory with what it was at the previous step. To find the lives: start a
+// search, lose a life, keep what decreased, play on a bit, keep what stayed
+// equal, and so on until a few addresses are left.
+//
+// Frozen bytes are written back every frame. Each ROM has its own list of
+// them, saved as JSON in a directory, named after the ROM's SHA-1 like the
+// ROM database.
+
+use crate::database::sha1_hex;
+use crate::{Chip8, RAM_SIZE};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// How a value compares with the previous step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    // Is this value now
+    Value(u8),
+}
+
+impl Filter {
+    pub fn parse(name: &str, value: Option<u8>) -> Result<Self, String> {
+        Ok(match name {
+            "equal" => Filter::Equal,
+            "changed" => Filter::Changed,
+            "increased" => Filter::Increased,
+            "decreased" => Filter::Decreased,
+            "value" => Filter::Value(value.ok_or("value needs a value")?),
+            _ => return Err(format!("Unknown filter {}", name)),
+        })
+    }
+
+    fn keeps(self, before: u8, now: u8) -> bool {
+        match self {
+            Filter::Equal => now == before,
+            Filter::Changed => now != before,
+            Filter::Increased => now > before,
+            Filter::Decreased => now < before,
+            Filter::Value(value) => now == value,
+        }
+    }
+}
+
+pub struct Search {
+    // Memory at the previous step
+    memory: [u8; RAM_SIZE],
+    candidates: Vec<u16>,
+}
+
+impl Search {
+    pub fn new(chip8: &Chip8) -> Self {
+        Self {
+            memory: *chip8.memory(),
+            candidates: (0..RAM_SIZE as u16).collect(),
+        }
+    }
+
+    // Keeps the addresses that pass and takes the next step from here.
+    // Returns how many are left.
+    pub fn narrow(&mut self, chip8: &Chip8, filter: Filter) -> usize {
+        let memory = chip8.memory();
+        self.candidates.retain(|addr| {
+            let addr = *addr as usize;
+            filter.keeps(self.memory[addr], memory[addr])
+        });
+        self.memory = *memory;
+        self.candidates.len()
+    }
+
+    // Addresses left, with their values at the last step
+    pub fn results(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.candidates
+            .iter()
+            .map(|addr| (*addr, self.memory[*addr as usize]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cheat {
+    #[serde(default)]
+    pub name: String,
+    pub addr: u16,
+    pub value: u8,
+}
+
+// The frozen bytes of one ROM
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cheats {
+    pub list: Vec<Cheat>,
+    // Where changes are saved, None to keep them in memory
+    path: Option<PathBuf>,
+}
+
+impl Cheats {
+    pub fn path(dir: &Path, rom: &[u8]) -> PathBuf {
+        dir.join(format!("{}.json", sha1_hex(rom)))
+    }
+
+    // The ROM's cheats from dir, none when it has no file yet
+    pub fn load(dir: &Path, rom: &[u8]) -> Result<Self, String> {
+        let path = Self::path(dir, rom);
+        let list: Vec<Cheat> = match fs::read_to_string(&path) {
+            Ok(json) => {
+                serde_json::from_str(&json).map_err(|err| format!("{}: {}", path.display(), err))?
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(format!("{}: {}", path.display(), err)),
+        };
+        if let Some(cheat) = list.iter().find(|cheat| cheat.addr as usize >= RAM_SIZE) {
+            return Err(format!(
+                "{}: address {:X} out of range",
+                path.display(),
+                cheat.addr
+            ));
+        }
+        Ok(Self {
+            list,
+            path: Some(path),
+        })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        }
+        let json = serde_json::to_string_pretty(&self.list).unwrap();
+        fs::write(path, json + "\n").map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    // Adds a cheat, or changes the one at the same address, and saves
+    pub fn freeze(&mut self, cheat: Cheat) -> Result<(), String> {
+        if (cheat.addr as usize) >= RAM_SIZE {
+            return Err(format!("Address {:X} out of range", cheat.addr));
+        }
+        match self.list.iter_mut().find(|old| old.addr == cheat.addr) {
+            Some(old) => *old = cheat,
+            None => self.list.push(cheat),
+        }
+        self.save()
+    }
+
+    // Removes the cheat at an address and saves, false when there's none
+    pub fn unfreeze(&mut self, addr: u16) -> Result<bool, String> {
+        let count = self.list.len();
+        self.list.retain(|cheat| cheat.addr != addr);
+        if self.list.len() == count {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    // Writes the frozen values, once a frame. Bytes that already have their
+    // value are left alone so code that's been decoded stays decoded.
+    pub fn apply(&self, chip8: &mut Chip8) {
+        for cheat in &self.list {
+            if chip8.memory()[cheat.addr as usize] != cheat.value {
+                chip8.write_memory(cheat.addr, &[cheat.value]);
+            }
+        }
+    }
+}
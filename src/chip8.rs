@@ -0,0 +1,674 @@
+use crate::instruction::Instruction;
+use crate::profile::Profile;
+use crate::quirks::Quirks;
+use crate::snapshot::Snapshot;
+use crate::trace::Trace;
+use log::{Level, info, log_enabled, trace};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const RAM_SIZE: usize = 4096;
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+
+pub const NUM_REGS: usize = 16;
+pub const STACK_SIZE: usize = 16;
+
+pub const NUM_KEYS: usize = 16;
+pub const START_ADDR: u16 = 0x200;
+
+// Addresses wrap around at the end of RAM
+pub(crate) const ADDR_MASK: u16 = (RAM_SIZE - 1) as u16;
+
+// Default number of instructions per frame
+pub const TICKS_PER_FRAME: u16 = 10;
+
+const FONTSET_SIZE: usize = 80;
+const FONTSET: [u8; FONTSET_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// Out of range accesses never panic. Memory addresses, including the PC and
+// anything relative to I, wrap around at 4 KiB and keys use the low nibble of
+// VX. Everything that can't sensibly wrap stops the program with an error.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    InvalidOpcode { pc: u16, op: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    RomTooLarge { size: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidOpcode { pc, op } => write!(f, "Invalid opcode {:04X} at {:03X}", op, pc),
+            Error::StackOverflow { pc } => write!(f, "Stack overflow at {:03X}", pc),
+            Error::StackUnderflow { pc } => write!(f, "Return with an empty stack at {:03X}", pc),
+            Error::RomTooLarge { size } => write!(
+                f,
+                "ROM is {} bytes, at most {} fit in memory",
+                size,
+                RAM_SIZE - START_ADDR as usize
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Code versions are unique across all machines, so a Jit that is moved to
+// another Chip8 can't mistake that memory for the one it translated
+static CODE_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_code_version() -> u64 {
+    CODE_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+pub struct Chip8 {
+    // Private so every write goes through write_byte and invalidates the cache
+    memory: [u8; RAM_SIZE],
+    // Opcode and decoded instruction for each address that has been executed
+    decoded: Vec<Option<(u16, Instruction)>>,
+    // Changes with every write to a byte the JIT built a block from, or to all
+    // of memory, lets the JIT tell when to recheck blocks
+    pub(crate) code_version: u64,
+    // Bytes the JIT built blocks from. Only set, a stale one costs a recheck.
+    pub(crate) translated: Vec<bool>,
+    pub v_reg: [u8; NUM_REGS],
+    pub i_reg: u16,
+    pub sp: u16,
+    pub pc: u16,
+    pub stack: [u16; STACK_SIZE],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: [bool; NUM_KEYS],
+    pub screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    pub opcode: u16,
+    // Instructions executed since the last reset
+    pub cycles: u64,
+    pub trace: Option<Trace>,
+    // Counts what runs while set, kept across resets
+    pub profile: Option<Profile>,
+    pub quirks: Quirks,
+    // Instructions per 60Hz frame
+    pub tick_rate: u16,
+    // SplitMix64 state for CXNN, random unless seeded. A single number so it
+    // fits in a save state. Without a source of randomness, as on wasm, every
+    // run starts the same unless seeded.
+    rng: u64,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Self {
+        let mut emu = Self {
+            memory: [0; RAM_SIZE],
+            decoded: vec![None; RAM_SIZE],
+            code_version: next_code_version(),
+            translated: vec![false; RAM_SIZE],
+            sp: 0,
+            pc: START_ADDR,
+            v_reg: [0; NUM_REGS],
+            i_reg: 0,
+            stack: [0; STACK_SIZE],
+            delay_timer: 0,
+            keys: [false; NUM_KEYS],
+            sound_timer: 0,
+            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            opcode: 0,
+            cycles: 0,
+            trace: None,
+            profile: None,
+            quirks: Quirks::default(),
+            tick_rate: TICKS_PER_FRAME,
+            rng: RandomState::new().build_hasher().finish(),
+        };
+
+        emu.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        emu
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Error> {
+        let size = rom.len() + START_ADDR as usize;
+        if size > RAM_SIZE {
+            return Err(Error::RomTooLarge { size: rom.len() });
+        }
+
+        self.memory[START_ADDR as usize..size].copy_from_slice(rom);
+        self.decoded.fill(None);
+        self.code_version = next_code_version();
+        Ok(())
+    }
+
+    pub fn memory(&self) -> &[u8; RAM_SIZE] {
+        &self.memory
+    }
+
+    // Writes bytes starting at addr, wrapping around the end of RAM
+    pub fn write_memory(&mut self, addr: u16, bytes: &[u8]) {
+        for (offset, value) in bytes.iter().enumerate() {
+            self.write_byte(addr.wrapping_add(offset as u16), *value);
+        }
+    }
+
+    fn read_byte(&self, addr: u16) -> u8 {
+        self.memory[(addr & ADDR_MASK) as usize]
+    }
+
+    fn write_byte(&mut self, addr: u16, value: u8) {
+        let addr = addr & ADDR_MASK;
+        self.memory[addr as usize] = value;
+
+        // The byte is part of the instructions starting here and one before
+        self.decoded[addr as usize] = None;
+        self.decoded[(addr.wrapping_sub(1) & ADDR_MASK) as usize] = None;
+        if self.translated[addr as usize] {
+            self.code_version = next_code_version();
+        }
+    }
+
+    // Whether step has run an instruction that includes the byte at addr since
+    // the byte was last written
+    pub fn ran_at(&self, addr: u16) -> bool {
+        let addr = addr & ADDR_MASK;
+        self.decoded[addr as usize].is_some()
+            || self.decoded[(addr.wrapping_sub(1) & ADDR_MASK) as usize].is_some()
+    }
+
+    // Address of the instruction being executed
+    fn instruction_addr(&self) -> u16 {
+        self.pc.wrapping_sub(2) & ADDR_MASK
+    }
+
+    fn skip(&mut self) {
+        self.pc = self.pc.wrapping_add(2) & ADDR_MASK;
+    }
+
+    fn push_to_stack(&mut self, value: u16) -> Result<(), Error> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(Error::StackOverflow {
+                pc: self.instruction_addr(),
+            });
+        }
+
+        self.stack[self.sp as usize] = value;
+        self.sp += 1;
+        Ok(())
+    }
+    fn pop_from_stack(&mut self) -> Result<u16, Error> {
+        if self.sp == 0 {
+            return Err(Error::StackUnderflow {
+                pc: self.instruction_addr(),
+            });
+        }
+
+        self.sp -= 1;
+        let value = self.stack[self.sp as usize];
+        self.stack[self.sp as usize] = 0;
+        Ok(value)
+    }
+
+    // Makes CXNN return the same numbers on every run with the same seed
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    pub fn reset(&mut self) {
+        self.pc = START_ADDR;
+        self.sp = 0;
+        self.i_reg = 0;
+        self.sound_timer = 0;
+        self.delay_timer = 0;
+        // Nothing of the previous ROM is left for the next one to run into
+        self.memory = [0; RAM_SIZE];
+        self.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        self.decoded.fill(None);
+        self.code_version = next_code_version();
+        self.v_reg = [0; NUM_REGS];
+        self.stack = [0; STACK_SIZE];
+        self.keys = [false; NUM_KEYS];
+        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.opcode = 0;
+        self.cycles = 0;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory,
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            sp: self.sp,
+            pc: self.pc,
+            stack: self.stack,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            keys: self.keys,
+            screen: self.screen,
+            opcode: self.opcode,
+            cycles: self.cycles,
+            quirks: self.quirks,
+            tick_rate: self.tick_rate,
+            rng: self.rng,
+        }
+    }
+
+    // Goes back to a snapshot, including where CXNN was in its sequence
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory = snapshot.memory;
+        self.decoded.fill(None);
+        self.code_version = next_code_version();
+        self.v_reg = snapshot.v_reg;
+        self.i_reg = snapshot.i_reg;
+        self.sp = snapshot.sp;
+        self.pc = snapshot.pc;
+        self.stack = snapshot.stack;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.keys = snapshot.keys;
+        self.screen = snapshot.screen;
+        self.opcode = snapshot.opcode;
+        self.cycles = snapshot.cycles;
+        self.quirks = snapshot.quirks;
+        self.tick_rate = snapshot.tick_rate;
+        self.rng = snapshot.rng;
+    }
+
+    pub fn fetch_instruction(&mut self) -> u16 {
+        let first_byte: u16 = self.read_byte(self.pc) as u16;
+        let second_byte: u16 = self.read_byte(self.pc.wrapping_add(1)) as u16;
+
+        let op = (first_byte << 8) | second_byte;
+        self.pc = self.pc.wrapping_add(2) & ADDR_MASK;
+        op
+    }
+
+    // Like fetch_instruction, but decodes each address only once until it's
+    // written to
+    fn fetch_decoded(&mut self) -> (u16, Instruction) {
+        let pc = (self.pc & ADDR_MASK) as usize;
+        match self.decoded[pc] {
+            Some(decoded) => {
+                self.pc = self.pc.wrapping_add(2) & ADDR_MASK;
+                decoded
+            }
+            None => {
+                let op = self.fetch_instruction();
+                let decoded = (op, Instruction::decode(op));
+                self.decoded[pc] = Some(decoded);
+                decoded
+            }
+        }
+    }
+
+    // Fetch, decode and execute one instruction
+    pub fn step(&mut self) -> Result<(), Error> {
+        let pc = self.pc & ADDR_MASK;
+        let i_reg = self.i_reg;
+        let (op, instruction) = self.fetch_decoded();
+        self.opcode = op;
+
+        let tracing = match &self.trace {
+            Some(trace) => trace.range.contains(&pc) && log_enabled!(Level::Trace),
+            None => false,
+        };
+
+        if tracing {
+            let before = self.v_reg;
+            self.execute(instruction)?;
+            if let Some(trace) = &self.trace {
+                trace!("{}", trace.line(self, pc, op, &before));
+            }
+        } else {
+            self.execute(instruction)?;
+        }
+
+        if let Some(profile) = &mut self.profile {
+            profile.record(pc, instruction, i_reg);
+        }
+        self.cycles += 1;
+        Ok(())
+    }
+
+    // One 60Hz frame: a batch of instructions, then the timers
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        for _ in 0..self.tick_rate {
+            self.step()?;
+            if self.waiting_for_vblank() {
+                break;
+            }
+        }
+
+        self.tick_timers();
+        Ok(())
+    }
+
+    // With the vblank quirk nothing runs after a draw until the next frame
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.quirks.vblank && self.opcode >> 12 == 0xD
+    }
+
+    pub fn execute_instruction(&mut self, op: u16) -> Result<(), Error> {
+        self.execute(Instruction::decode(op))
+    }
+
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), Error> {
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::Clear => {
+                // CLEAR SCREEN
+                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            }
+            Instruction::Return => {
+                // RET
+                self.pc = self.pop_from_stack()? & ADDR_MASK;
+            }
+            Instruction::Jump(nnn) => {
+                // JUMP
+                self.pc = nnn;
+            }
+            Instruction::Call(nnn) => {
+                // CALL
+                self.push_to_stack(self.pc)?;
+                self.pc = nnn;
+            }
+            Instruction::SkipEqual(x, nn) => {
+                // Skip if VX = NN
+                if self.v_reg[x as usize] == nn {
+                    self.skip();
+                }
+            }
+            Instruction::SkipNotEqual(x, nn) => {
+                // Skip if VX != NN
+                if self.v_reg[x as usize] != nn {
+                    self.skip();
+                }
+            }
+            Instruction::SkipEqualReg(x, y) => {
+                // Skip if VX = VY
+                if self.v_reg[x as usize] == self.v_reg[y as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::Set(x, nn) => {
+                // SET REGISTER TO VX
+                self.v_reg[x as usize] = nn;
+            }
+            Instruction::AddValue(x, nn) => {
+                // ADD VALUE REGISTER TO VX
+                self.v_reg[x as usize] = self.v_reg[x as usize].wrapping_add(nn);
+            }
+            Instruction::Copy(x, y) => {
+                // Set VX to VY
+                self.v_reg[x as usize] = self.v_reg[y as usize];
+            }
+            Instruction::Or(x, y) => {
+                // Set VX to bitwise OR of VX and VY
+                self.v_reg[x as usize] |= self.v_reg[y as usize];
+                self.reset_flag_after_logic();
+            }
+            Instruction::And(x, y) => {
+                // Set VX to bitwise AND of VX and VY
+                self.v_reg[x as usize] &= self.v_reg[y as usize];
+                self.reset_flag_after_logic();
+            }
+            Instruction::Xor(x, y) => {
+                // Set VX to bitwise XOR of VX and VY
+                self.v_reg[x as usize] ^= self.v_reg[y as usize];
+                self.reset_flag_after_logic();
+            }
+            Instruction::Add(x, y) => {
+                // Set VX to VX + VY
+                let (new_vx, carry) =
+                    self.v_reg[x as usize].overflowing_add(self.v_reg[y as usize]);
+
+                // Set carry bit. VF is written last so the flag wins when X is F
+                self.v_reg[x as usize] = new_vx;
+                self.v_reg[0xF] = if carry { 1 } else { 0 };
+            }
+            Instruction::Sub(x, y) => {
+                // Set VX to VX - VY
+                let (new_vx, borrow) =
+                    self.v_reg[x as usize].overflowing_sub(self.v_reg[y as usize]);
+
+                // Set carry bit
+                self.v_reg[x as usize] = new_vx;
+                self.v_reg[0xF] = if borrow { 0 } else { 1 };
+            }
+            Instruction::ShiftRight(x, y) => {
+                // Right shift VX in place, or VY into VX without the shift quirk.
+                // Set flag register to the bit shiftet out
+                let value = self.shift_source(x, y);
+                self.v_reg[x as usize] = value >> 1;
+                self.v_reg[0xF] = value & 1;
+            }
+            Instruction::ShiftLeft(x, y) => {
+                // Left shift
+                let value = self.shift_source(x, y);
+                self.v_reg[x as usize] = value << 1;
+                self.v_reg[0xF] = (value >> 7) & 1;
+            }
+            Instruction::SubReverse(x, y) => {
+                // Set VX to VY - VX
+                let (new_vx, borrow) =
+                    self.v_reg[y as usize].overflowing_sub(self.v_reg[x as usize]);
+
+                // Set carry bit
+                self.v_reg[x as usize] = new_vx;
+                self.v_reg[0xF] = if borrow { 0 } else { 1 };
+            }
+            Instruction::SkipNotEqualReg(x, y) => {
+                // Skip if VX != VY
+                if self.v_reg[x as usize] != self.v_reg[y as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::SetIndex(nnn) => {
+                // SET INDEX REGISTER I
+                self.i_reg = nnn;
+            }
+            Instruction::JumpOffset(nnn) => {
+                // JUMP to NNN + V0, or XNN + VX with the jump quirk
+                let reg = if self.quirks.jump { nnn >> 8 } else { 0 };
+                self.pc = ((self.v_reg[reg as usize] as u16) + nnn) & ADDR_MASK;
+            }
+            Instruction::Random(x, nn) => {
+                // Generate random number and AND it with NN
+                let rand = self.random_byte();
+
+                self.v_reg[x as usize] = rand & nn;
+            }
+            Instruction::Draw(x, y, n) => {
+                // DISPLAY/DRAW
+                let x_coord = self.v_reg[x as usize] as usize;
+                let y_coord = self.v_reg[y as usize] as usize;
+
+                // The sprite always starts on screen, the rest wraps or is clipped
+                let x_coord = x_coord % SCREEN_WIDTH;
+                let y_coord = y_coord % SCREEN_HEIGHT;
+
+                let mut flipped = false;
+                for i in 0..n as u16 {
+                    let sprite_byte = self.read_byte(self.i_reg.wrapping_add(i));
+
+                    for j in 0..8 {
+                        if sprite_byte & (0b10000000 >> j) != 0 {
+                            let x = x_coord + j;
+                            let y = y_coord + i as usize;
+                            if !self.quirks.wrap && (x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT) {
+                                continue;
+                            }
+                            let x = x % SCREEN_WIDTH;
+                            let y = y % SCREEN_HEIGHT;
+
+                            let idx = x + SCREEN_WIDTH * y;
+                            flipped |= self.screen[idx];
+                            self.screen[idx] ^= true;
+                        }
+                    }
+                }
+
+                if flipped {
+                    self.v_reg[15] = 1;
+                } else {
+                    self.v_reg[15] = 0;
+                }
+            }
+            Instruction::SkipKey(x) => {
+                // Skip if key in VX is pressed
+                let key = self.v_reg[x as usize] & 0xF;
+                if self.keys[key as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::SkipNotKey(x) => {
+                // Skip if key in VX is not pressed
+                let key = self.v_reg[x as usize] & 0xF;
+                if !self.keys[key as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::GetDelay(x) => {
+                // Sets VX to the current value of the delay timer
+                self.v_reg[x as usize] = self.delay_timer;
+            }
+            Instruction::SetDelay(x) => {
+                // Sets the delay timer to the value in VX
+                self.delay_timer = self.v_reg[x as usize];
+            }
+            Instruction::SetSound(x) => {
+                // Sets the sound timer to the value in VX
+                self.sound_timer = self.v_reg[x as usize];
+            }
+            Instruction::AddIndex(x) => {
+                // Add the value in VX to the index register I.
+                let vx = self.v_reg[x as usize] as u16;
+                self.i_reg = self.i_reg.wrapping_add(vx);
+            }
+            Instruction::WaitKey(x) => {
+                // Waits for key input
+                match self.keys.iter().position(|pressed| *pressed) {
+                    Some(key) => self.v_reg[x as usize] = key as u8,
+                    // Redo opcode
+                    None => self.pc = self.instruction_addr(),
+                }
+            }
+            Instruction::Font(x) => {
+                self.i_reg = self.v_reg[x as usize] as u16 * 5;
+            }
+            Instruction::Bcd(x) => {
+                // Binary-coded decimal of VX into I, I+1 and I+2
+                let vx = self.v_reg[x as usize];
+
+                self.write_byte(self.i_reg, vx / 100);
+                self.write_byte(self.i_reg.wrapping_add(1), (vx / 10) % 10);
+                self.write_byte(self.i_reg.wrapping_add(2), vx % 10);
+            }
+            Instruction::Store(x) => {
+                // Stores the value in the registers from V0 to VX into memory from the address in I
+                // Eg. V0 holds 0x20; I holds 0x40; memory[0x40] = 0x20;
+                for j in 0..=x as u16 {
+                    self.write_byte(self.i_reg.wrapping_add(j), self.v_reg[j as usize]);
+                }
+                self.advance_index(x);
+            }
+            Instruction::Load(x) => {
+                // Takes values in memory addresses V0 to VX and loads them into the variable registers
+                for j in 0..=x as u16 {
+                    self.v_reg[j as usize] = self.read_byte(self.i_reg.wrapping_add(j))
+                }
+                self.advance_index(x);
+            }
+            Instruction::Invalid(op) => {
+                return Err(Error::InvalidOpcode {
+                    pc: self.instruction_addr(),
+                    op,
+                });
+            }
+        };
+
+        Ok(())
+    }
+
+    fn reset_flag_after_logic(&mut self) {
+        if self.quirks.logic {
+            self.v_reg[0xF] = 0;
+        }
+    }
+
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift {
+            self.v_reg[x as usize]
+        } else {
+            self.v_reg[y as usize]
+        }
+    }
+
+    // I after FX55/FX65
+    fn advance_index(&mut self, x: u8) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+
+        let step = if self.quirks.memory_increment_by_x {
+            x as u16
+        } else {
+            x as u16 + 1
+        };
+        self.i_reg = self.i_reg.wrapping_add(step);
+    }
+
+    pub fn key_press(&mut self, keycode: usize, pressed: bool) {
+        if let Some(key) = self.keys.get_mut(keycode) {
+            *key = pressed;
+        }
+    }
+
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            if self.sound_timer == 1 {
+                self.beep()
+            }
+
+            self.sound_timer -= 1;
+        }
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+
+    fn beep(&self) {
+        info!("BEEP")
+    }
+}
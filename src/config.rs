@@ -0,0 +1,284 @@
+use crate::{MAX_SCALE, SCALE};
+use chip8_rs::palette::Palette;
+use chip8_rs::phosphor::MAX_DECAY;
+use chip8_rs::trace::{self, Trace, TraceFormat};
+use std::path::PathBuf;
+
+// Frames to record, end exclusive
+pub struct Recording {
+    pub path: PathBuf,
+    pub start: u64,
+    pub end: u64,
+}
+
+pub struct Config {
+    // None shows the launcher
+    pub rom_name: Option<String>,
+    pub rom_dir: PathBuf,
+    // None uses the ROM database palette, if any, or classic
+    pub palette: Option<Palette>,
+    pub decay: f32,
+    pub scale: u32,
+    pub integer_scale: bool,
+    pub fullscreen: bool,
+    pub screenshot: Option<u64>,
+    pub record: Option<Recording>,
+    pub trace: Option<Trace>,
+    pub trace_file: Option<PathBuf>,
+    pub diff_trace: Option<PathBuf>,
+    // Print the ROM's code, data and subroutines instead of running it, see
+    // analysis.rs
+    pub analyze: bool,
+    // With analyze, where to write the control-flow graph as Graphviz DOT
+    pub dot: Option<PathBuf>,
+    // Where the profile is saved when the game ends, JSON for .json
+    pub profile: Option<PathBuf>,
+    pub database: Option<PathBuf>,
+    // Where each ROM's frozen bytes are saved, defaults to cheats/ in the ROM
+    // directory
+    pub cheats: Option<PathBuf>,
+    pub use_database: bool,
+    pub platform: Option<String>,
+    pub tick_rate: Option<u16>,
+    pub watch: bool,
+    pub keep_state: bool,
+    // TCP address or unix:PATH to serve remote.rs's protocol on
+    pub remote: Option<String>,
+    // TCP address to wait for a debugger on, see gdb.rs
+    pub gdb: Option<String>,
+    // Rhai script with hooks into the game, see script.rs
+    pub script: Option<PathBuf>,
+}
+
+impl Config {
+    // Usage: chip8-rs [OPTIONS] [ROM]
+    //
+    //  --palette NAME|COLOURS  Builtin palette or 2-4 hex colours
+    //  --decay 0.0-0.99        Pixel persistence, 0 disables it
+    //  --scale 1-64            Initial window scale
+    //  --smooth-scale          Fill the window instead of scaling in whole steps
+    //  --fullscreen            Start in fullscreen
+    //  --screenshot FRAME      Save a PNG of the given frame
+    //  --record PATH           Record to a .gif, or to a directory of numbered PNGs
+    //  --frames START-END      Frames to record, defaults to everything
+    //  --trace                 Log every instruction, see trace.rs for the format
+    //  --trace-file PATH       Write the trace to a file instead of stderr
+    //  --trace-range START-END Only trace instructions in this hex address range
+    //  --trace-format FORMAT   compact (changed registers) or full (all registers)
+    //  --diff-trace PATH       Replay a trace without a window and report where it diverges
+    //  --analyze               Print the ROM's subroutines, code and data without running it
+    //  --dot PATH              With --analyze, write the control-flow graph as Graphviz DOT
+    //  --profile PATH          Count what runs and save a report when the game ends, as JSON
+    //                          for .json, otherwise text with an annotated disassembly
+    //  --rom-dir PATH          Where ROMs are looked up and listed, defaults to ./rom
+    //  --database PATH         Extra programs.json style ROM database, defaults to database.json
+    //                          in the ROM directory
+    //  --no-database           Don't look the ROM up, use the default quirks and keys
+    //  --cheats PATH           Where cheats are kept, one file per ROM, defaults to cheats/
+    //                          in the ROM directory
+    //  --platform ID           Quirks and tick rate of a platform, e.g. originalChip8 or superchip
+    //  --tick-rate N           Instructions per frame
+    //  --watch                 Restart the ROM whenever its file changes
+    //  --keep-state            With --watch, patch changes that leave the code that ran alone
+    //                          into the running program instead of restarting it
+    //  --remote ADDR           Take commands over TCP, e.g. 127.0.0.1:6502, or a Unix socket
+    //                          with unix:PATH, see remote.rs for the protocol
+    //  --gdb ADDR              Wait for a GDB remote protocol debugger on a TCP address
+    //  --script PATH           Run a Rhai script with hooks on frames, addresses and
+    //                          memory writes
+    //
+    // The ROM can be given as a plain name or as --NAME, both are looked up in the
+    // ROM directory. Without one the launcher lists the ROMs to pick from.
+    // Options given here win over what the database has for the ROM.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Self {
+            rom_name: None,
+            rom_dir: PathBuf::from("./rom"),
+            palette: None,
+            decay: 0.0,
+            scale: SCALE,
+            integer_scale: true,
+            fullscreen: false,
+            screenshot: None,
+            record: None,
+            trace: None,
+            trace_file: None,
+            diff_trace: None,
+            analyze: false,
+            dot: None,
+            profile: None,
+            database: None,
+            cheats: None,
+            use_database: true,
+            platform: None,
+            tick_rate: None,
+            watch: false,
+            keep_state: false,
+            remote: None,
+            gdb: None,
+            script: None,
+        };
+        let mut frames = (0, u64::MAX);
+        let mut trace = Trace {
+            format: TraceFormat::Compact,
+            range: 0..=0xFFF,
+        };
+        let mut tracing = false;
+
+        let mut args = args.iter().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--palette" => {
+                    let value = args.next().ok_or("--palette needs a value")?;
+                    config.palette = Some(Palette::parse(value)?);
+                }
+                "--decay" => {
+                    let value = args.next().ok_or("--decay needs a value")?;
+                    config.decay = value
+                        .parse()
+                        .ok()
+                        .filter(|decay| (0.0..=MAX_DECAY).contains(decay))
+                        .ok_or_else(|| format!("Invalid decay: {}", value))?;
+                }
+                "--scale" => {
+                    let value = args.next().ok_or("--scale needs a value")?;
+                    config.scale = value
+                        .parse()
+                        .ok()
+                        .filter(|scale| (1..=MAX_SCALE).contains(scale))
+                        .ok_or(format!("Invalid scale: {}", value))?;
+                }
+                "--smooth-scale" => config.integer_scale = false,
+                "--fullscreen" => config.fullscreen = true,
+                "--screenshot" => {
+                    let value = args.next().ok_or("--screenshot needs a frame")?;
+                    config.screenshot = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid frame: {}", value))?,
+                    );
+                }
+                "--record" => {
+                    let value = args.next().ok_or("--record needs a path")?;
+                    config.record = Some(Recording {
+                        path: PathBuf::from(value),
+                        start: 0,
+                        end: u64::MAX,
+                    });
+                }
+                "--frames" => {
+                    let value = args.next().ok_or("--frames needs a range")?;
+                    frames = value
+                        .split_once('-')
+                        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+                        .filter(|(start, end)| start < end)
+                        .ok_or(format!("Invalid frame range: {}", value))?;
+                }
+                "--trace" => tracing = true,
+                "--trace-file" => {
+                    let value = args.next().ok_or("--trace-file needs a path")?;
+                    config.trace_file = Some(PathBuf::from(value));
+                    tracing = true;
+                }
+                "--trace-range" => {
+                    let value = args.next().ok_or("--trace-range needs a range")?;
+                    trace.range = trace::parse_range(value)?;
+                    tracing = true;
+                }
+                "--trace-format" => {
+                    let value = args.next().ok_or("--trace-format needs a format")?;
+                    trace.format = TraceFormat::parse(value)?;
+                    tracing = true;
+                }
+                "--diff-trace" => {
+                    let value = args.next().ok_or("--diff-trace needs a path")?;
+                    config.diff_trace = Some(PathBuf::from(value));
+                }
+                "--analyze" => config.analyze = true,
+                "--dot" => {
+                    let value = args.next().ok_or("--dot needs a path")?;
+                    config.dot = Some(PathBuf::from(value));
+                    config.analyze = true;
+                }
+                "--profile" => {
+                    let value = args.next().ok_or("--profile needs a path")?;
+                    config.profile = Some(PathBuf::from(value));
+                }
+                "--rom-dir" => {
+                    let value = args.next().ok_or("--rom-dir needs a path")?;
+                    config.rom_dir = PathBuf::from(value);
+                }
+                "--database" => {
+                    let value = args.next().ok_or("--database needs a path")?;
+                    config.database = Some(PathBuf::from(value));
+                }
+                "--no-database" => config.use_database = false,
+                "--cheats" => {
+                    let value = args.next().ok_or("--cheats needs a path")?;
+                    config.cheats = Some(PathBuf::from(value));
+                }
+                "--watch" => config.watch = true,
+                "--keep-state" => {
+                    config.watch = true;
+                    config.keep_state = true;
+                }
+                "--remote" => {
+                    let value = args.next().ok_or("--remote needs an address")?;
+                    config.remote = Some(value.to_string());
+                }
+                "--gdb" => {
+                    let value = args.next().ok_or("--gdb needs an address")?;
+                    config.gdb = Some(value.to_string());
+                }
+                "--script" => {
+                    let value = args.next().ok_or("--script needs a path")?;
+                    config.script = Some(PathBuf::from(value));
+                }
+                "--platform" => {
+                    let value = args.next().ok_or("--platform needs a platform")?;
+                    config.platform = Some(value.to_string());
+                }
+                "--tick-rate" => {
+                    let value = args.next().ok_or("--tick-rate needs a value")?;
+                    config.tick_rate = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|rate| *rate > 0)
+                            .ok_or(format!("Invalid tick rate: {}", value))?,
+                    );
+                }
+                other => {
+                    config.rom_name = Some(other.strip_prefix("--").unwrap_or(other).to_string())
+                }
+            }
+        }
+
+        if let Some(record) = &mut config.record {
+            (record.start, record.end) = frames;
+        }
+
+        if config.diff_trace.is_some() && config.rom_name.is_none() {
+            return Err("--diff-trace needs a ROM".to_string());
+        }
+        if config.analyze && config.rom_name.is_none() {
+            return Err("--analyze needs a ROM".to_string());
+        }
+
+        // Each runs the program its own way
+        let drivers = [&config.gdb, &config.remote]
+            .iter()
+            .filter(|addr| addr.is_some())
+            .count()
+            + config.script.is_some() as usize;
+        if drivers > 1 {
+            return Err("Only one of --gdb, --remote and --script can be used".to_string());
+        }
+
+        if tracing {
+            config.trace = Some(trace);
+        }
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,188 @@
+// ROM database in the format of the community chip-8-database
+// (https://github.com/chip-8/chip-8-database): programs.json lists programs
+// with their ROMs keyed by SHA-1, platforms.json the quirks and tick rate of
+// each platform. Only the fields used here are read, so the full files work too.
+//
+// The embedded copy in data/ covers the ROMs bundled in rom/. More programs, or
+// changes to the embedded ones, can be added from a programs.json style file.
+
+use crate::palette::Palette;
+use crate::quirks::Quirks;
+use crate::{Chip8, TICKS_PER_FRAME};
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+const PROGRAMS: &str = include_str!("../data/programs.json");
+const PLATFORMS: &str = include_str!("../data/platforms.json");
+
+// Parsed once, on first use
+static BUILTIN: LazyLock<Database> = LazyLock::new(|| {
+    let mut database = Database {
+        programs: Vec::new(),
+        platforms: serde_json::from_str(PLATFORMS).expect("Invalid data/platforms.json"),
+        hashes: HashMap::new(),
+    };
+    database
+        .add_programs(PROGRAMS)
+        .expect("Invalid data/programs.json");
+    database
+});
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Platform {
+    pub id: String,
+    pub name: String,
+    pub default_tickrate: u16,
+    pub quirks: Quirks,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    release: Option<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    tickrate: Option<u16>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<Colors>,
+    // In order of preference
+    #[serde(default)]
+    platforms: Vec<String>,
+    // Quirks that differ from the platform's, by platform
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// Everything known about one ROM, with the platform defaults filled in
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomInfo {
+    pub sha1: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    // Platform id, None when none of the ROM's platforms are known
+    pub platform: Option<String>,
+    pub quirks: Quirks,
+    pub tick_rate: u16,
+    // Keypad key for each logical button: up, down, left, right, a, b and the
+    // same with a player2 prefix
+    pub keys: HashMap<String, u8>,
+    pub palette: Option<Palette>,
+}
+
+impl RomInfo {
+    // Sets up the machine for this ROM
+    pub fn apply(&self, chip8: &mut Chip8) {
+        chip8.quirks = self.quirks;
+        chip8.tick_rate = self.tick_rate;
+    }
+}
+
+#[derive(Clone)]
+pub struct Database {
+    programs: Vec<Program>,
+    platforms: Vec<Platform>,
+    // SHA-1 to index in programs, later programs win
+    hashes: HashMap<String, usize>,
+}
+
+impl Database {
+    // The embedded database, clone it to add programs
+    pub fn builtin() -> &'static Self {
+        &BUILTIN
+    }
+
+    // Adds the programs from a programs.json style string, replacing any ROM
+    // that is already known
+    pub fn add_programs(&mut self, json: &str) -> Result<(), String> {
+        let programs: Vec<Program> = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        for program in programs {
+            for sha1 in program.roms.keys() {
+                self.hashes.insert(sha1.to_lowercase(), self.programs.len());
+            }
+            self.programs.push(program);
+        }
+        Ok(())
+    }
+
+    pub fn add_programs_from(&mut self, path: &Path) -> Result<(), String> {
+        let json =
+            fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        self.add_programs(&json)
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn platform(&self, id: &str) -> Option<&Platform> {
+        self.platforms.iter().find(|platform| platform.id == id)
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        let sha1 = sha1_hex(rom);
+        let program = &self.programs[*self.hashes.get(&sha1)?];
+        let (_, entry) = program
+            .roms
+            .iter()
+            .find(|(hash, _)| hash.to_lowercase() == sha1)?;
+
+        let platform = entry.platforms.iter().find_map(|id| self.platform(id));
+        let mut quirks = platform.map(|p| p.quirks).unwrap_or_default();
+        if let Some(platform) = platform
+            && let Some(overrides) = entry.quirky_platforms.get(&platform.id)
+        {
+            for (name, value) in overrides {
+                // Quirks this emulator doesn't have make no difference to it
+                quirks.set(name, *value).ok();
+            }
+        }
+
+        let palette = entry
+            .colors
+            .as_ref()
+            .filter(|colors| !colors.pixels.is_empty())
+            .and_then(|colors| Palette::parse(&colors.pixels.join(",")).ok())
+            .map(|palette| Palette {
+                name: program.title.clone(),
+                ..palette
+            });
+
+        Some(RomInfo {
+            sha1,
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            release: program.release.clone(),
+            platform: platform.map(|p| p.id.clone()),
+            quirks,
+            tick_rate: entry
+                .tickrate
+                .or(platform.map(|p| p.default_tickrate))
+                .unwrap_or(TICKS_PER_FRAME),
+            keys: entry.keys.clone(),
+            palette,
+        })
+    }
+}
+
+pub fn sha1_hex(rom: &[u8]) -> String {
+    Sha1::digest(rom)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,301 @@
+// Differential testing: compare the machine state step by step against another
+// implementation, or against a trace recorded by another emulator, and stop at
+// the first instruction where they disagree.
+
+use crate::disasm::disassemble;
+use crate::{Chip8, NUM_REGS, RAM_SIZE, SCREEN_WIDTH, STACK_SIZE};
+use std::fmt;
+use std::io::BufRead;
+
+// Everything another implementation is expected to agree on
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub pc: u16,
+    pub v_reg: [u8; NUM_REGS],
+    pub i_reg: u16,
+    pub sp: u16,
+    pub stack: [u16; STACK_SIZE],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub memory: Vec<u8>,
+    pub screen: Vec<bool>,
+}
+
+impl State {
+    pub fn of(chip8: &Chip8) -> Self {
+        Self {
+            pc: chip8.pc,
+            v_reg: chip8.v_reg,
+            i_reg: chip8.i_reg,
+            sp: chip8.sp,
+            stack: chip8.stack,
+            delay_timer: chip8.delay_timer,
+            sound_timer: chip8.sound_timer,
+            memory: chip8.memory().to_vec(),
+            screen: chip8.screen.to_vec(),
+        }
+    }
+
+    // First difference as (what, expected, actual), `self` is the expected state
+    pub fn diff(&self, actual: &State) -> Option<(String, String, String)> {
+        let field = |name: &str, expected: String, actual: String| {
+            Some((name.to_string(), expected, actual))
+        };
+
+        if self.pc != actual.pc {
+            return field("PC", hex(self.pc, 3), hex(actual.pc, 3));
+        }
+        for reg in 0..NUM_REGS {
+            if self.v_reg[reg] != actual.v_reg[reg] {
+                return field(
+                    &format!("V{:X}", reg),
+                    hex(self.v_reg[reg], 2),
+                    hex(actual.v_reg[reg], 2),
+                );
+            }
+        }
+        if self.i_reg != actual.i_reg {
+            return field("I", hex(self.i_reg, 3), hex(actual.i_reg, 3));
+        }
+        if self.sp != actual.sp {
+            return field("SP", hex(self.sp, 1), hex(actual.sp, 1));
+        }
+        if let Some(idx) = (0..STACK_SIZE).find(|idx| self.stack[*idx] != actual.stack[*idx]) {
+            return field(
+                &format!("stack[{}]", idx),
+                hex(self.stack[idx], 3),
+                hex(actual.stack[idx], 3),
+            );
+        }
+        if self.delay_timer != actual.delay_timer {
+            return field("DT", hex(self.delay_timer, 2), hex(actual.delay_timer, 2));
+        }
+        if self.sound_timer != actual.sound_timer {
+            return field("ST", hex(self.sound_timer, 2), hex(actual.sound_timer, 2));
+        }
+        if let Some(addr) = (0..RAM_SIZE).find(|addr| self.memory[*addr] != actual.memory[*addr]) {
+            return field(
+                &format!("memory[{:03X}]", addr),
+                hex(self.memory[addr], 2),
+                hex(actual.memory[addr], 2),
+            );
+        }
+        if let Some(idx) =
+            (0..self.screen.len()).find(|idx| self.screen[*idx] != actual.screen[*idx])
+        {
+            return field(
+                &format!("pixel ({}, {})", idx % SCREEN_WIDTH, idx / SCREEN_WIDTH),
+                self.screen[idx].to_string(),
+                actual.screen[idx].to_string(),
+            );
+        }
+
+        None
+    }
+}
+
+fn hex(value: impl Into<u16>, digits: usize) -> String {
+    format!("{:0digits$X}", value.into(), digits = digits)
+}
+
+// Where two runs first disagree
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub cycle: u64,
+    // Address and opcode of the instruction that was just executed
+    pub pc: u16,
+    pub op: u16,
+    pub what: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cycle {} at {:03X} ({:04X} {}): {} expected {}, got {}",
+            self.cycle,
+            self.pc,
+            self.op,
+            disassemble(self.op),
+            self.what,
+            self.expected,
+            self.actual
+        )
+    }
+}
+
+// One line of a trace in the format written by --trace, see trace.rs.
+// Registers that aren't on the line are not checked.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceLine {
+    pub cycle: u64,
+    pub pc: u16,
+    pub op: u16,
+    pub i_reg: Option<u16>,
+    pub sp: Option<u16>,
+    pub v_reg: [Option<u8>; NUM_REGS],
+    // What CXNN stored
+    pub random: Option<u8>,
+}
+
+impl TraceLine {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid trace line: {}", line);
+        let mut tokens = line.split_whitespace();
+
+        let cycle = tokens
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(invalid)?;
+        let pc = tokens
+            .next()
+            .and_then(|t| u16::from_str_radix(t, 16).ok())
+            .ok_or_else(invalid)?;
+        let op = tokens
+            .next()
+            .and_then(|t| u16::from_str_radix(t, 16).ok())
+            .ok_or_else(invalid)?;
+
+        let mut trace = Self {
+            cycle,
+            pc,
+            op,
+            i_reg: None,
+            sp: None,
+            v_reg: [None; NUM_REGS],
+            random: None,
+        };
+
+        // The rest is the mnemonic followed by NAME=HEX pairs
+        for (name, value) in tokens.filter_map(|t| t.split_once('=')) {
+            let value = u16::from_str_radix(value, 16).map_err(|_| invalid())?;
+            match name {
+                "I" => trace.i_reg = Some(value),
+                "SP" => trace.sp = Some(value),
+                "RND" => trace.random = Some(value as u8),
+                _ => {
+                    let reg = name
+                        .strip_prefix('V')
+                        .and_then(|reg| usize::from_str_radix(reg, 16).ok())
+                        .filter(|reg| *reg < NUM_REGS)
+                        .ok_or_else(invalid)?;
+                    trace.v_reg[reg] = Some(value as u8);
+                }
+            }
+        }
+
+        Ok(trace)
+    }
+}
+
+// Run the loaded ROM along a recorded trace. Returns the first divergence, or
+// None when the whole trace matched. Cycles missing from the trace (e.g. when it
+// was filtered by address) are executed without checking.
+//
+// The RNG is seeded from the trace's header, so CXNN gives the same numbers as
+// in the recorded run. Traces from elsewhere have no seed, then CXNN results
+// are copied from RND=, or from VX when that's all the line has.
+pub fn replay_trace(chip8: &mut Chip8, trace: impl BufRead) -> Result<Option<Divergence>, String> {
+    // The instruction executed last, blamed when the PC doesn't match
+    let mut last = (0, chip8.pc, 0);
+
+    for line in trace.lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(seed) = comment.trim().strip_prefix("seed=") {
+                let seed = u64::from_str_radix(seed, 16)
+                    .map_err(|_| format!("Invalid trace seed: {}", seed))?;
+                chip8.seed(seed);
+            }
+            continue;
+        }
+        let expected = TraceLine::parse(&line)?;
+
+        while chip8.cycles < expected.cycle {
+            last = (chip8.cycles, chip8.pc, 0);
+            step(chip8)?;
+            last.2 = chip8.opcode;
+        }
+
+        if chip8.pc != expected.pc {
+            let (cycle, pc, op) = last;
+            return Ok(Some(Divergence {
+                cycle,
+                pc,
+                op,
+                what: "PC".to_string(),
+                expected: hex(expected.pc, 3),
+                actual: hex(chip8.pc, 3),
+            }));
+        }
+
+        last = (chip8.cycles, chip8.pc, 0);
+        step(chip8)?;
+        last.2 = chip8.opcode;
+
+        let divergence = |what: String, expected: String, actual: String| {
+            Some(Divergence {
+                cycle: last.0,
+                pc: last.1,
+                op: last.2,
+                what,
+                expected,
+                actual,
+            })
+        };
+
+        if chip8.opcode != expected.op {
+            let (expected, actual) = (hex(expected.op, 4), hex(chip8.opcode, 4));
+            return Ok(divergence("opcode".to_string(), expected, actual));
+        }
+
+        if chip8.opcode >> 12 == 0xC {
+            let x = ((chip8.opcode >> 8) & 0xF) as usize;
+            if let Some(value) = expected.random.or(expected.v_reg[x]) {
+                chip8.v_reg[x] = value;
+            }
+        }
+
+        for reg in 0..NUM_REGS {
+            if let Some(value) = expected.v_reg[reg]
+                && value != chip8.v_reg[reg]
+            {
+                return Ok(divergence(
+                    format!("V{:X}", reg),
+                    hex(value, 2),
+                    hex(chip8.v_reg[reg], 2),
+                ));
+            }
+        }
+        if let Some(i_reg) = expected.i_reg
+            && i_reg != chip8.i_reg
+        {
+            return Ok(divergence(
+                "I".to_string(),
+                hex(i_reg, 3),
+                hex(chip8.i_reg, 3),
+            ));
+        }
+        if let Some(sp) = expected.sp
+            && sp != chip8.sp
+        {
+            return Ok(divergence("SP".to_string(), hex(sp, 1), hex(chip8.sp, 1)));
+        }
+    }
+
+    Ok(None)
+}
+
+// Step with the timers ticking at the same rate as in run_frame
+fn step(chip8: &mut Chip8) -> Result<(), String> {
+    chip8.step().map_err(|err| err.to_string())?;
+    if chip8.cycles.is_multiple_of(chip8.tick_rate as u64) {
+        chip8.tick_timers();
+    }
+    Ok(())
+}
@@ -0,0 +1,225 @@
+// Headless environment for reinforcement learning, in the shape of a gym env:
+// reset with a seed, then step with one of the game's actions and get back what
+// the agent sees, the reward and whether the episode is over. The same seed and
+// actions always play out the same.
+//
+// Rewards and game overs are read from the game's RAM and registers, so each
+// supported game needs to be worked out from its code, see Game.
+
+use crate::database::{self, Database};
+use crate::{Chip8, NUM_KEYS};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    // The program failed, as with Chip8::run_frame
+    Program(crate::Error),
+    // Not an index into the game's actions
+    InvalidAction { action: usize, actions: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Program(err) => write!(f, "{}", err),
+            Error::InvalidAction { action, actions } => {
+                write!(
+                    f,
+                    "Action {} out of range, the game has {}",
+                    action, actions
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<crate::Error> for Error {
+    fn from(err: crate::Error) -> Self {
+        Error::Program(err)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Game {
+    Brix,
+    Pong,
+    Tetris,
+}
+
+impl Game {
+    // The versions in rom/, by SHA-1
+    pub fn detect(rom: &[u8]) -> Option<Self> {
+        match database::sha1_hex(rom).as_str() {
+            "f13766c14aeb02ad8d4d103cb5eadd282d20cddc" => Some(Self::Brix),
+            "b232ef880bd6060fb45fa6effed7edf0ae95670e" => Some(Self::Pong),
+            "5f518084744bf3cb8733f6e5454dfd1634320563" => Some(Self::Tetris),
+            _ => None,
+        }
+    }
+
+    // Keys held for each action, one bit per key. The first does nothing.
+    pub fn actions(self) -> &'static [u16] {
+        match self {
+            // Left, right
+            Self::Brix => &[0, 1 << 0x4, 1 << 0x6],
+            // Left paddle up, down
+            Self::Pong => &[0, 1 << 0x1, 1 << 0x4],
+            // Rotate, left, right, drop
+            Self::Tetris => &[0, 1 << 0x4, 1 << 0x5, 1 << 0x6, 1 << 0x7],
+        }
+    }
+
+    // Rewards are the changes in this
+    pub fn score(self, chip8: &Chip8) -> i64 {
+        let memory = chip8.memory();
+        let digit = |addr: usize| memory[addr] as i64;
+        let bcd = |addr: usize| digit(addr) * 100 + digit(addr + 1) * 10 + digit(addr + 2);
+        match self {
+            // Bricks hit, stored as BCD at 314 to draw them
+            Self::Brix => bcd(0x314),
+            // Points of the left player minus the right. Both are in one number,
+            // the left in the tens and the right in the ones, stored as BCD at 2F2.
+            Self::Pong => digit(0x2F2) * 10 + digit(0x2F3) - digit(0x2F4),
+            // Lines cleared, stored as BCD at 804
+            Self::Tetris => bcd(0x804),
+        }
+    }
+
+    // Checked after every instruction, as some of these only last a moment
+    pub fn over(self, chip8: &Chip8) -> bool {
+        let memory = chip8.memory();
+        match self {
+            // Out of lives or bricks, the game spins on a jump to itself at 2DE
+            Self::Brix => chip8.pc == 0x2DE,
+            // First to 9, a 10th point for the right player would carry into the
+            // left player's digit
+            Self::Pong => {
+                memory[0x2F2] as i64 * 10 + memory[0x2F3] as i64 >= 9 || memory[0x2F4] >= 9
+            }
+            // A new piece hit the stack where it appears and is being locked
+            // there by the routine at 340. The game never stops by itself, every
+            // piece after that does the same.
+            Self::Tetris => chip8.pc == 0x340 && chip8.v_reg[1] == 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Observe {
+    // 0 or 1 per pixel, row by row
+    Screen,
+    Ram,
+    // The screen followed by RAM
+    ScreenAndRam,
+}
+
+pub struct Chip8Env {
+    chip8: Chip8,
+    rom: Vec<u8>,
+    game: Game,
+    // Frames run for each step, with the action's keys held
+    pub frame_skip: u32,
+    pub observe: Observe,
+    score: i64,
+    done: bool,
+}
+
+impl Chip8Env {
+    // Runs the ROM with the quirks and tick rate from the ROM database
+    pub fn new(rom: &[u8], game: Game) -> Result<Self, Error> {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(rom)?;
+        if let Some(info) = Database::builtin().lookup(rom) {
+            info.apply(&mut chip8);
+        }
+
+        let mut env = Self {
+            chip8,
+            rom: rom.to_vec(),
+            game,
+            frame_skip: 4,
+            observe: Observe::Screen,
+            score: 0,
+            done: false,
+        };
+        env.reset(0);
+        Ok(env)
+    }
+
+    pub fn game(&self) -> Game {
+        self.game
+    }
+
+    pub fn actions(&self) -> &'static [u16] {
+        self.game.actions()
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    // Starts a new episode, the seed decides what CXNN returns
+    pub fn reset(&mut self, seed: u64) -> Vec<u8> {
+        self.chip8.reset();
+        self.chip8.load_rom(&self.rom).expect("Loaded once already");
+        self.chip8.seed(seed);
+        self.score = self.game.score(&self.chip8);
+        self.done = false;
+        self.observation()
+    }
+
+    // Runs frame_skip frames with the keys of the action, an index into
+    // actions(), held down. Returns the observation, the reward and whether the
+    // game is over, after which nothing changes until the next reset.
+    pub fn step(&mut self, action: usize) -> Result<(Vec<u8>, f32, bool), Error> {
+        let actions = self.game.actions();
+        let keys = *actions.get(action).ok_or(Error::InvalidAction {
+            action,
+            actions: actions.len(),
+        })?;
+        for key in 0..NUM_KEYS {
+            self.chip8.key_press(key, keys & (1 << key) != 0);
+        }
+
+        for _ in 0..self.frame_skip {
+            if self.done {
+                break;
+            }
+            self.run_frame()?;
+        }
+
+        let score = self.game.score(&self.chip8);
+        let reward = (score - self.score) as f32;
+        self.score = score;
+        Ok((self.observation(), reward, self.done))
+    }
+
+    // Like Chip8::run_frame, but stops as soon as the game is over
+    fn run_frame(&mut self) -> Result<(), Error> {
+        for _ in 0..self.chip8.tick_rate {
+            self.chip8.step()?;
+            if self.game.over(&self.chip8) {
+                self.done = true;
+                return Ok(());
+            }
+            if self.chip8.waiting_for_vblank() {
+                break;
+            }
+        }
+
+        self.chip8.tick_timers();
+        Ok(())
+    }
+
+    pub fn observation(&self) -> Vec<u8> {
+        let screen = self.chip8.screen.iter().map(|pixel| *pixel as u8);
+        let ram = self.chip8.memory().iter().copied();
+        match self.observe {
+            Observe::Screen => screen.collect(),
+            Observe::Ram => ram.collect(),
+            Observe::ScreenAndRam => screen.chain(ram).collect(),
+        }
+    }
+}
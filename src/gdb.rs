@@ -0,0 +1,519 @@
+// GDB remote serial protocol stub, so programs can be debugged from gdb or any
+// debugger UI that speaks the protocol:
+//
+//     (gdb) target remote 127.0.0.1:1234
+//
+// GDB has no CHIP-8 architecture, so the registers are described by the
+// target.xml below, which clients fetch with qXfer. The registers are, in this
+// order: v0-vf, i, pc, sp, dt and st. Memory is the 4K of RAM.
+//
+// Supported: reading and writing registers and memory, software breakpoints
+// (Z0/z0), single steps, continue and interrupting with ctrl-C. Stops are
+// reported as SIGTRAP for breakpoints and steps, SIGINT when interrupted and
+// SIGILL or SIGSEGV when the program runs into an invalid opcode or a stack
+// error.
+//
+// One debugger at a time. The program waits for it at the start and runs on
+// its own again once it detaches or disconnects.
+
+use crate::{Chip8, Error, NUM_REGS, RAM_SIZE};
+use std::collections::BTreeSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// Registers after v0-vf, with their sizes in bytes
+const REGISTERS: [(&str, usize, &str); 5] = [
+    ("i", 2, "data_ptr"),
+    ("pc", 2, "code_ptr"),
+    ("sp", 1, "uint8"),
+    ("dt", 1, "uint8"),
+    ("st", 1, "uint8"),
+];
+
+enum Message {
+    Connected(u64, TcpStream),
+    Data(u64, Vec<u8>),
+    Closed(u64),
+}
+
+struct Client {
+    id: u64,
+    writer: TcpStream,
+    // Received bytes that don't make a whole packet yet
+    buffer: Vec<u8>,
+    // Resent when the debugger asks for it with -
+    last_reply: Vec<u8>,
+    ack: bool,
+}
+
+pub struct Gdb {
+    addr: String,
+    messages: Receiver<Message>,
+    client: Option<Client>,
+    // Nothing runs while stopped
+    pub stopped: bool,
+    breakpoints: BTreeSet<u16>,
+    // Continuing doesn't stop at a breakpoint it starts on
+    resuming: bool,
+}
+
+fn serve(client: u64, mut reader: TcpStream, messages: Sender<Message>) {
+    let mut buffer = [0; 4096];
+    while let Ok(read @ 1..) = reader.read(&mut buffer) {
+        if messages
+            .send(Message::Data(client, buffer[..read].to_vec()))
+            .is_err()
+        {
+            return;
+        }
+    }
+    let _ = messages.send(Message::Closed(client));
+}
+
+fn accept(listener: TcpListener, messages: Sender<Message>) {
+    for (client, stream) in (0..).zip(listener.incoming()) {
+        let Ok(stream) = stream else { continue };
+        let Ok(reader) = stream.try_clone() else {
+            continue;
+        };
+        if messages.send(Message::Connected(client, stream)).is_err() {
+            return;
+        }
+        let messages = messages.clone();
+        thread::spawn(move || serve(client, reader, messages));
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn number(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+// "addr,len" as used by memory packets
+fn range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((number(addr)?, number(len)?))
+}
+
+pub fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?>\n",
+        "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n",
+        "<target version=\"1.0\">\n",
+        "  <feature name=\"org.chip8-rs.core\">\n",
+    ));
+    for reg in 0..NUM_REGS {
+        xml += &format!(
+            "    <reg name=\"v{:x}\" bitsize=\"8\" type=\"uint8\"/>\n",
+            reg
+        );
+    }
+    for (name, size, kind) in REGISTERS {
+        xml += &format!(
+            "    <reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>\n",
+            name,
+            size * 8,
+            kind
+        );
+    }
+    xml + "  </feature>\n</target>\n"
+}
+
+fn read_register(chip8: &Chip8, reg: usize) -> Option<Vec<u8>> {
+    Some(match reg {
+        0..NUM_REGS => vec![chip8.v_reg[reg]],
+        _ => match REGISTERS.get(reg - NUM_REGS)?.0 {
+            "i" => chip8.i_reg.to_le_bytes().to_vec(),
+            "pc" => chip8.pc.to_le_bytes().to_vec(),
+            "sp" => vec![chip8.sp as u8],
+            "dt" => vec![chip8.delay_timer],
+            _ => vec![chip8.sound_timer],
+        },
+    })
+}
+
+fn write_register(chip8: &mut Chip8, reg: usize, value: &[u8]) -> Option<()> {
+    let size = match reg {
+        0..NUM_REGS => 1,
+        _ => REGISTERS.get(reg - NUM_REGS)?.1,
+    };
+    if value.len() != size {
+        return None;
+    }
+    let word = || u16::from_le_bytes([value[0], value[1]]);
+    match reg {
+        0..NUM_REGS => chip8.v_reg[reg] = value[0],
+        _ => match REGISTERS[reg - NUM_REGS].0 {
+            "i" => chip8.i_reg = word(),
+            "pc" => chip8.pc = word() % RAM_SIZE as u16,
+            // Past the stack it would panic on the next call or return
+            "sp" if value[0] as usize > chip8.stack.len() => return None,
+            "sp" => chip8.sp = value[0] as u16,
+            "dt" => chip8.delay_timer = value[0],
+            _ => chip8.sound_timer = value[0],
+        },
+    }
+    Some(())
+}
+
+// The signal for an error, leaving the PC on the instruction that failed so
+// the debugger shows it
+fn fault(chip8: &mut Chip8, err: &Error) -> u8 {
+    match *err {
+        Error::StackOverflow { pc } | Error::StackUnderflow { pc } => {
+            chip8.pc = pc;
+            SIGSEGV
+        }
+        Error::InvalidOpcode { pc, .. } => {
+            chip8.pc = pc;
+            SIGILL
+        }
+        Error::RomTooLarge { .. } => SIGILL,
+    }
+}
+
+impl Gdb {
+    pub fn listen(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?.to_string();
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || accept(listener, sender));
+        Ok(Self {
+            addr,
+            messages,
+            client: None,
+            stopped: true,
+            breakpoints: BTreeSet::new(),
+            resuming: false,
+        })
+    }
+
+    // With the port picked by the system when listening on port 0
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // Handles whatever the debugger sent since the last call
+    pub fn poll(&mut self, chip8: &mut Chip8) {
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                Message::Connected(id, writer) => {
+                    if self.client.is_some() {
+                        let _ = writer.shutdown(std::net::Shutdown::Both);
+                        continue;
+                    }
+                    self.client = Some(Client {
+                        id,
+                        writer,
+                        buffer: Vec::new(),
+                        last_reply: Vec::new(),
+                        ack: true,
+                    });
+                    self.stopped = true;
+                }
+                Message::Closed(id) => {
+                    if self.client.as_ref().is_some_and(|client| client.id == id) {
+                        self.detach();
+                    }
+                }
+                Message::Data(id, data) => {
+                    if let Some(client) = &mut self.client
+                        && client.id == id
+                    {
+                        client.buffer.extend(data);
+                        self.packets(chip8);
+                    }
+                }
+            }
+        }
+    }
+
+    fn detach(&mut self) {
+        self.client = None;
+        self.breakpoints.clear();
+        self.stopped = false;
+    }
+
+    // Handles every whole packet in the buffer
+    fn packets(&mut self, chip8: &mut Chip8) {
+        while let Some(client) = &mut self.client {
+            let buffer = &mut client.buffer;
+            match buffer.first() {
+                None => return,
+                Some(b'-') => {
+                    buffer.remove(0);
+                    let reply = client.last_reply.clone();
+                    let _ = client.writer.write_all(&reply);
+                }
+                // Ctrl-C
+                Some(0x03) => {
+                    buffer.remove(0);
+                    if !self.stopped {
+                        self.stop(SIGINT);
+                    }
+                }
+                Some(b'$') => {
+                    let Some(end) = buffer.iter().position(|byte| *byte == b'#') else {
+                        return;
+                    };
+                    if buffer.len() < end + 3 {
+                        return;
+                    }
+                    let packet: Vec<u8> = buffer.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let sum = std::str::from_utf8(&packet[end + 1..])
+                        .ok()
+                        .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+                    if client.ack {
+                        let valid = sum == Some(checksum(data));
+                        let _ = client.writer.write_all(if valid { b"+" } else { b"-" });
+                        if !valid {
+                            continue;
+                        }
+                    }
+                    let data = unescape(data);
+                    let reply = match std::str::from_utf8(&data) {
+                        Ok(data) => self.handle(data, chip8),
+                        // Binary writes, the debugger falls back to M
+                        Err(_) => Some(String::new()),
+                    };
+                    if let Some(reply) = reply {
+                        self.send(&reply);
+                    }
+                }
+                // Acks and anything between packets
+                Some(_) => {
+                    buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    // The reply to a packet, None when there's none yet
+    fn handle(&mut self, packet: &str, chip8: &mut Chip8) -> Option<String> {
+        let error = "E01".to_string();
+        let ok = "OK".to_string();
+        let (command, args) = packet.split_at(packet.len().min(1));
+        Some(match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => {
+                let mut registers = Vec::new();
+                for reg in 0..NUM_REGS + REGISTERS.len() {
+                    registers.extend(read_register(chip8, reg)?);
+                }
+                hex(&registers)
+            }
+            "G" => {
+                let Some(mut values) = from_hex(args) else {
+                    return Some(error);
+                };
+                for reg in 0..NUM_REGS + REGISTERS.len() {
+                    let size = read_register(chip8, reg)?.len();
+                    if values.len() < size {
+                        break;
+                    }
+                    let value: Vec<u8> = values.drain(..size).collect();
+                    if write_register(chip8, reg, &value).is_none() {
+                        return Some(error);
+                    }
+                }
+                ok
+            }
+            "p" => match number(args).and_then(|reg| read_register(chip8, reg)) {
+                Some(value) => hex(&value),
+                None => error,
+            },
+            "P" => {
+                let written = args.split_once('=').and_then(|(reg, value)| {
+                    write_register(chip8, number(reg)?, &from_hex(value)?)
+                });
+                if written.is_some() { ok } else { error }
+            }
+            "m" => match range(args) {
+                Some((addr, len)) if addr < RAM_SIZE => {
+                    hex(&chip8.memory()[addr..addr.saturating_add(len).min(RAM_SIZE)])
+                }
+                _ => error,
+            },
+            "M" => {
+                let write = args.split_once(':').and_then(|(range_text, data)| {
+                    let (addr, len) = range(range_text)?;
+                    let data = from_hex(data)?;
+                    (data.len() == len && addr < RAM_SIZE && len <= RAM_SIZE - addr)
+                        .then_some((addr, data))
+                });
+                match write {
+                    Some((addr, data)) => {
+                        chip8.write_memory(addr as u16, &data);
+                        ok
+                    }
+                    None => error,
+                }
+            }
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let kind = fields.next();
+                let addr = fields.next().and_then(number);
+                match (kind, addr) {
+                    // Software and hardware breakpoints are the same here
+                    (Some("0" | "1"), Some(addr)) if addr < RAM_SIZE => {
+                        if command == "Z" {
+                            self.breakpoints.insert(addr as u16);
+                        } else {
+                            self.breakpoints.remove(&(addr as u16));
+                        }
+                        ok
+                    }
+                    (Some("0" | "1"), _) => error,
+                    // Watchpoints aren't supported
+                    _ => String::new(),
+                }
+            }
+            "s" => {
+                if let Some(addr) = number(args) {
+                    chip8.pc = (addr % RAM_SIZE) as u16;
+                }
+                match chip8.step() {
+                    Ok(()) => format!("S{:02x}", SIGTRAP),
+                    Err(err) => format!("S{:02x}", fault(chip8, &err)),
+                }
+            }
+            "c" => {
+                if let Some(addr) = number(args) {
+                    chip8.pc = (addr % RAM_SIZE) as u16;
+                }
+                self.stopped = false;
+                self.resuming = true;
+                return None;
+            }
+            "D" => {
+                self.send(&ok);
+                self.detach();
+                return None;
+            }
+            "k" => {
+                self.detach();
+                return None;
+            }
+            "H" | "T" => ok,
+            _ => return self.query(packet),
+        })
+    }
+
+    // General queries, an empty reply for anything unknown
+    fn query(&mut self, packet: &str) -> Option<String> {
+        if let Some(features) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let xml = target_xml();
+            return Some(match range(features) {
+                Some((offset, _)) if offset >= xml.len() => "l".to_string(),
+                Some((offset, len)) if len >= xml.len() - offset => format!("l{}", &xml[offset..]),
+                Some((offset, len)) => format!("m{}", &xml[offset..offset + len]),
+                None => "E01".to_string(),
+            });
+        }
+        Some(match packet.split(':').next().unwrap_or_default() {
+            "qSupported" => "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+".to_string(),
+            "QStartNoAckMode" => {
+                self.send("OK");
+                if let Some(client) = &mut self.client {
+                    client.ack = false;
+                }
+                return None;
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        })
+    }
+
+    fn send(&mut self, data: &str) {
+        let Some(client) = &mut self.client else {
+            return;
+        };
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        client.last_reply = packet.clone().into_bytes();
+        let _ = client.writer.write_all(packet.as_bytes());
+    }
+
+    fn stop(&mut self, signal: u8) {
+        self.stopped = true;
+        self.send(&format!("S{:02x}", signal));
+    }
+
+    // Like Chip8::run_frame, but only while the debugger lets the program run.
+    // Errors stop the program and go to the debugger when one is attached.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Error> {
+        if self.stopped {
+            return Ok(());
+        }
+
+        for _ in 0..chip8.tick_rate {
+            let resuming = std::mem::take(&mut self.resuming);
+            if !resuming && self.breakpoints.contains(&chip8.pc) {
+                self.stop(SIGTRAP);
+                return Ok(());
+            }
+            if let Err(err) = chip8.step() {
+                if self.client.is_none() {
+                    return Err(err);
+                }
+                let signal = fault(chip8, &err);
+                self.stop(signal);
+                return Ok(());
+            }
+            if chip8.waiting_for_vblank() {
+                break;
+            }
+        }
+
+        chip8.tick_timers();
+        Ok(())
+    }
+}
+
+// Undoes the }-escaping of $, #, } and * in packets
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut escaped = false;
+    for byte in data {
+        match (escaped, byte) {
+            (false, b'}') => escaped = true,
+            (true, _) => {
+                bytes.push(byte ^ 0x20);
+                escaped = false;
+            }
+            _ => bytes.push(*byte),
+        }
+    }
+    bytes
+}
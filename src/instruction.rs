@@ -0,0 +1,171 @@
+// Decoded form of an opcode, so the nibbles only have to be picked apart once
+// per address instead of on every cycle. X and Y are register numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    // 0000
+    Nop,
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 1NNN
+    Jump(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipEqual(u8, u8),
+    // 4XNN
+    SkipNotEqual(u8, u8),
+    // 5XY0
+    SkipEqualReg(u8, u8),
+    // 6XNN
+    Set(u8, u8),
+    // 7XNN
+    AddValue(u8, u8),
+    // 8XY0
+    Copy(u8, u8),
+    // 8XY1
+    Or(u8, u8),
+    // 8XY2
+    And(u8, u8),
+    // 8XY3
+    Xor(u8, u8),
+    // 8XY4
+    Add(u8, u8),
+    // 8XY5
+    Sub(u8, u8),
+    // 8XY6
+    ShiftRight(u8, u8),
+    // 8XY7
+    SubReverse(u8, u8),
+    // 8XYE
+    ShiftLeft(u8, u8),
+    // 9XY0
+    SkipNotEqualReg(u8, u8),
+    // ANNN
+    SetIndex(u16),
+    // BNNN
+    JumpOffset(u16),
+    // CXNN
+    Random(u8, u8),
+    // DXYN
+    Draw(u8, u8, u8),
+    // EX9E
+    SkipKey(u8),
+    // EXA1
+    SkipNotKey(u8),
+    // FX07
+    GetDelay(u8),
+    // FX0A
+    WaitKey(u8),
+    // FX15
+    SetDelay(u8),
+    // FX18
+    SetSound(u8),
+    // FX1E
+    AddIndex(u8),
+    // FX29
+    Font(u8),
+    // FX33
+    Bcd(u8),
+    // FX55
+    Store(u8),
+    // FX65
+    Load(u8),
+    // Anything else, kept so the error can name the opcode
+    Invalid(u16),
+}
+
+impl Instruction {
+    pub fn decode(op: u16) -> Self {
+        let x = ((op >> 8) & 0xF) as u8;
+        let y = ((op >> 4) & 0xF) as u8;
+        let n = (op & 0xF) as u8;
+        let nn = (op & 0xFF) as u8;
+        let nnn = op & 0xFFF;
+
+        match (op >> 12, n) {
+            _ if op == 0x0000 => Self::Nop,
+            _ if op == 0x00E0 => Self::Clear,
+            _ if op == 0x00EE => Self::Return,
+            (1, _) => Self::Jump(nnn),
+            (2, _) => Self::Call(nnn),
+            (3, _) => Self::SkipEqual(x, nn),
+            (4, _) => Self::SkipNotEqual(x, nn),
+            (5, 0) => Self::SkipEqualReg(x, y),
+            (6, _) => Self::Set(x, nn),
+            (7, _) => Self::AddValue(x, nn),
+            (8, 0) => Self::Copy(x, y),
+            (8, 1) => Self::Or(x, y),
+            (8, 2) => Self::And(x, y),
+            (8, 3) => Self::Xor(x, y),
+            (8, 4) => Self::Add(x, y),
+            (8, 5) => Self::Sub(x, y),
+            (8, 6) => Self::ShiftRight(x, y),
+            (8, 7) => Self::SubReverse(x, y),
+            (8, 0xE) => Self::ShiftLeft(x, y),
+            (9, 0) => Self::SkipNotEqualReg(x, y),
+            (0xA, _) => Self::SetIndex(nnn),
+            (0xB, _) => Self::JumpOffset(nnn),
+            (0xC, _) => Self::Random(x, nn),
+            (0xD, _) => Self::Draw(x, y, n),
+            (0xE, _) if nn == 0x9E => Self::SkipKey(x),
+            (0xE, _) if nn == 0xA1 => Self::SkipNotKey(x),
+            (0xF, _) => match nn {
+                0x07 => Self::GetDelay(x),
+                0x0A => Self::WaitKey(x),
+                0x15 => Self::SetDelay(x),
+                0x18 => Self::SetSound(x),
+                0x1E => Self::AddIndex(x),
+                0x29 => Self::Font(x),
+                0x33 => Self::Bcd(x),
+                0x55 => Self::Store(x),
+                0x65 => Self::Load(x),
+                _ => Self::Invalid(op),
+            },
+            _ => Self::Invalid(op),
+        }
+    }
+
+    // The opcode's pattern, e.g. 8XY4 for Add, "invalid" for the rest
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Self::Nop => "0000",
+            Self::Clear => "00E0",
+            Self::Return => "00EE",
+            Self::Jump(..) => "1NNN",
+            Self::Call(..) => "2NNN",
+            Self::SkipEqual(..) => "3XNN",
+            Self::SkipNotEqual(..) => "4XNN",
+            Self::SkipEqualReg(..) => "5XY0",
+            Self::Set(..) => "6XNN",
+            Self::AddValue(..) => "7XNN",
+            Self::Copy(..) => "8XY0",
+            Self::Or(..) => "8XY1",
+            Self::And(..) => "8XY2",
+            Self::Xor(..) => "8XY3",
+            Self::Add(..) => "8XY4",
+            Self::Sub(..) => "8XY5",
+            Self::ShiftRight(..) => "8XY6",
+            Self::SubReverse(..) => "8XY7",
+            Self::ShiftLeft(..) => "8XYE",
+            Self::SkipNotEqualReg(..) => "9XY0",
+            Self::SetIndex(..) => "ANNN",
+            Self::JumpOffset(..) => "BNNN",
+            Self::Random(..) => "CXNN",
+            Self::Draw(..) => "DXYN",
+            Self::SkipKey(..) => "EX9E",
+            Self::SkipNotKey(..) => "EXA1",
+            Self::GetDelay(..) => "FX07",
+            Self::WaitKey(..) => "FX0A",
+            Self::SetDelay(..) => "FX15",
+            Self::SetSound(..) => "FX18",
+            Self::AddIndex(..) => "FX1E",
+            Self::Font(..) => "FX29",
+            Self::Bcd(..) => "FX33",
+            Self::Store(..) => "FX55",
+            Self::Load(..) => "FX65",
+            Self::Invalid(_) => "invalid",
+        }
+    }
+}
@@ -0,0 +1,297 @@
+// Basic block translator for headless runs. Straight-line code is turned into
+// a list of pre-decoded ops and run without fetching, decoding or looking
+// anything up per instruction. A block ends at the first instruction that can
+// change the PC (jumps, calls, returns, skips, FX0A), draws or writes memory.
+// Jumps and skips are done in place, the rest go through Chip8::execute.
+//
+// A block that is only an FX0A with no key down, or a jump to itself, can't
+// change anything until a key does, so the rest of the batch is skipped in one
+// go.
+//
+// Blocks are rechecked against memory after a write to any byte one was built
+// from, so self-modifying code and Chip8::write_memory work. Since a block ends
+// at the first write, it can never modify itself while running.
+//
+// Instructions are not traced.
+
+use crate::instruction::Instruction;
+use crate::{ADDR_MASK, Chip8, Error, RAM_SIZE};
+
+// Longest run of straight-line instructions in one block
+const MAX_BLOCK_LEN: usize = 32;
+
+// An instruction inside a block. The most common ones have their own case,
+// the rest go through Chip8::execute.
+#[derive(Clone, Copy)]
+enum Op {
+    Set(u8, u8),
+    AddValue(u8, u8),
+    Copy(u8, u8),
+    SetIndex(u16),
+    AddIndex(u8),
+    GetDelay(u8),
+    // Can't fail, jump or write memory
+    Execute(Instruction),
+}
+
+impl Op {
+    #[inline(always)]
+    fn run(self, chip8: &mut Chip8) {
+        match self {
+            Op::Set(x, nn) => chip8.v_reg[x as usize] = nn,
+            Op::AddValue(x, nn) => {
+                chip8.v_reg[x as usize] = chip8.v_reg[x as usize].wrapping_add(nn)
+            }
+            Op::Copy(x, y) => chip8.v_reg[x as usize] = chip8.v_reg[y as usize],
+            Op::SetIndex(nnn) => chip8.i_reg = nnn,
+            Op::AddIndex(x) => {
+                chip8.i_reg = chip8.i_reg.wrapping_add(chip8.v_reg[x as usize] as u16)
+            }
+            Op::GetDelay(x) => chip8.v_reg[x as usize] = chip8.delay_timer,
+            Op::Execute(instruction) => {
+                // These never fail
+                chip8.execute(instruction).ok();
+            }
+        }
+    }
+}
+
+struct Block {
+    // The code the block was built from
+    bytes: Vec<u8>,
+    // Chip8::code_version when the bytes were last known to match memory
+    checked: u64,
+    // Straight-line instructions with their opcodes
+    body: Vec<(u16, Op)>,
+    // The instruction ending the block, None when it hit MAX_BLOCK_LEN
+    end: Option<(u16, Instruction)>,
+}
+
+// Blocks by start address
+pub struct Jit {
+    blocks: Vec<Option<Block>>,
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        Self {
+            blocks: (0..RAM_SIZE).map(|_| None).collect(),
+        }
+    }
+
+    // Same as Chip8::run_frame
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Error> {
+        let mut left = chip8.tick_rate as u64;
+        while left > 0 {
+            left -= self.run_block(chip8, left)?;
+            if chip8.waiting_for_vblank() {
+                break;
+            }
+        }
+        chip8.tick_timers();
+        Ok(())
+    }
+
+    // Executes exactly this many instructions, blocks are cut short if needed
+    pub fn run(&mut self, chip8: &mut Chip8, instructions: u64) -> Result<(), Error> {
+        let mut left = instructions;
+        while left > 0 {
+            left -= self.run_block(chip8, left)?;
+        }
+        Ok(())
+    }
+
+    // Runs the block at the PC, returns the number of instructions executed
+    #[inline(always)]
+    fn run_block(&mut self, chip8: &mut Chip8, limit: u64) -> Result<u64, Error> {
+        let start = chip8.pc & ADDR_MASK;
+        let block = self.block(chip8, start);
+
+        let count = block.body.len().min(limit as usize);
+        for (_, op) in &block.body[..count] {
+            op.run(chip8);
+        }
+        if let Some((op, _)) = block.body[..count].last() {
+            chip8.opcode = *op;
+        }
+        chip8.cycles += count as u64;
+
+        let addr = start.wrapping_add(2 * count as u16) & ADDR_MASK;
+        let end = match block.end {
+            Some(end) if count == block.body.len() && (count as u64) < limit => end,
+            _ => {
+                chip8.pc = addr;
+                return Ok(count as u64);
+            }
+        };
+
+        let (op, instruction) = end;
+        let idle = match instruction {
+            Instruction::WaitKey(_) => !chip8.keys.contains(&true),
+            Instruction::Jump(nnn) => nnn == start,
+            _ => false,
+        };
+        if count == 0 && idle {
+            // Waiting for a key or spinning on a jump to itself, the same
+            // instruction over and over
+            chip8.pc = start;
+            chip8.opcode = op;
+            chip8.cycles += limit;
+            return Ok(limit);
+        }
+
+        // Execute the last instruction like step does
+        chip8.pc = addr.wrapping_add(2) & ADDR_MASK;
+        chip8.opcode = op;
+        end_block(chip8, instruction)?;
+        chip8.cycles += 1;
+        Ok(count as u64 + 1)
+    }
+
+    // The block starting at addr, translated again if memory changed under it
+    #[inline(always)]
+    fn block(&mut self, chip8: &mut Chip8, addr: u16) -> &Block {
+        let addr = addr & ADDR_MASK;
+        let slot = &mut self.blocks[addr as usize];
+
+        let valid = match slot {
+            Some(block) if block.checked == chip8.code_version => true,
+            Some(block) if matches(chip8, addr, &block.bytes) => {
+                block.checked = chip8.code_version;
+                mark(chip8, addr, block.bytes.len());
+                true
+            }
+            _ => false,
+        };
+
+        if !valid {
+            let block = translate(chip8, addr);
+            mark(chip8, addr, block.bytes.len());
+            *slot = Some(block);
+        }
+        slot.as_ref().unwrap()
+    }
+}
+
+// The instruction ending a block. Jumps and skips, which end most blocks, are
+// done here, the rest go through Chip8::execute.
+#[inline(always)]
+fn end_block(chip8: &mut Chip8, instruction: Instruction) -> Result<(), Error> {
+    let v = chip8.v_reg;
+    let skip = match instruction {
+        Instruction::Jump(nnn) => {
+            chip8.pc = nnn;
+            return Ok(());
+        }
+        Instruction::SkipEqual(x, nn) => v[x as usize] == nn,
+        Instruction::SkipNotEqual(x, nn) => v[x as usize] != nn,
+        Instruction::SkipEqualReg(x, y) => v[x as usize] == v[y as usize],
+        Instruction::SkipNotEqualReg(x, y) => v[x as usize] != v[y as usize],
+        Instruction::SkipKey(x) => chip8.keys[(v[x as usize] & 0xF) as usize],
+        Instruction::SkipNotKey(x) => !chip8.keys[(v[x as usize] & 0xF) as usize],
+        _ => return chip8.execute(instruction),
+    };
+    if skip {
+        chip8.pc = chip8.pc.wrapping_add(2) & ADDR_MASK;
+    }
+    Ok(())
+}
+
+// Writes to the bytes of a block in use have to change the code version
+fn mark(chip8: &mut Chip8, addr: u16, len: usize) {
+    for offset in 0..len {
+        chip8.translated[(addr as usize + offset) & ADDR_MASK as usize] = true;
+    }
+}
+
+fn matches(chip8: &Chip8, addr: u16, bytes: &[u8]) -> bool {
+    bytes.iter().enumerate().all(|(offset, byte)| {
+        chip8.memory()[(addr as usize + offset) & ADDR_MASK as usize] == *byte
+    })
+}
+
+#[cold]
+#[inline(never)]
+fn translate(chip8: &Chip8, start: u16) -> Block {
+    let mut block = Block {
+        bytes: Vec::new(),
+        checked: chip8.code_version,
+        body: Vec::new(),
+        end: None,
+    };
+
+    let mut addr = start;
+    while block.body.len() < MAX_BLOCK_LEN {
+        let high = chip8.memory()[addr as usize];
+        let low = chip8.memory()[(addr.wrapping_add(1) & ADDR_MASK) as usize];
+        block.bytes.extend([high, low]);
+
+        let op = (high as u16) << 8 | low as u16;
+        let instruction = Instruction::decode(op);
+        match compile(instruction) {
+            Some(compiled) => block.body.push((op, compiled)),
+            None => {
+                block.end = Some((op, instruction));
+                break;
+            }
+        }
+
+        addr = addr.wrapping_add(2) & ADDR_MASK;
+    }
+
+    block
+}
+
+// The op for an instruction that can sit inside a block, None for the ones
+// that end it
+fn compile(instruction: Instruction) -> Option<Op> {
+    let op = match instruction {
+        Instruction::Set(x, nn) => Op::Set(x, nn),
+        Instruction::AddValue(x, nn) => Op::AddValue(x, nn),
+        Instruction::Copy(x, y) => Op::Copy(x, y),
+        Instruction::SetIndex(nnn) => Op::SetIndex(nnn),
+        Instruction::AddIndex(x) => Op::AddIndex(x),
+        Instruction::GetDelay(x) => Op::GetDelay(x),
+
+        // Everything else that doesn't touch the PC or memory
+        Instruction::Nop
+        | Instruction::Clear
+        | Instruction::Or(..)
+        | Instruction::And(..)
+        | Instruction::Xor(..)
+        | Instruction::Add(..)
+        | Instruction::Sub(..)
+        | Instruction::ShiftRight(..)
+        | Instruction::SubReverse(..)
+        | Instruction::ShiftLeft(..)
+        | Instruction::Random(..)
+        | Instruction::SetDelay(..)
+        | Instruction::SetSound(..)
+        | Instruction::Font(..)
+        | Instruction::Load(..) => Op::Execute(instruction),
+
+        Instruction::Return
+        | Instruction::Jump(..)
+        | Instruction::Call(..)
+        | Instruction::SkipEqual(..)
+        | Instruction::SkipNotEqual(..)
+        | Instruction::SkipEqualReg(..)
+        | Instruction::SkipNotEqualReg(..)
+        | Instruction::JumpOffset(..)
+        | Instruction::Draw(..)
+        | Instruction::SkipKey(..)
+        | Instruction::SkipNotKey(..)
+        | Instruction::WaitKey(..)
+        | Instruction::Bcd(..)
+        | Instruction::Store(..)
+        | Instruction::Invalid(..) => return None,
+    };
+
+    Some(op)
+}
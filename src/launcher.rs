@@ -0,0 +1,313 @@
+// ROM picker shown in the window when no ROM is given. Like Renderer it draws
+// into RGB24 pixels, the frontend only uploads them and handles the input.
+
+use crate::database::{Database, RomInfo};
+use crate::palette::{Palette, Rgb};
+use crate::renderer::BYTES_PER_PIXEL;
+use crate::{RAM_SIZE, START_ADDR};
+use log::warn;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Same 2:1 shape as the CHIP-8 screen so both fill the window alike
+pub const MENU_WIDTH: usize = 320;
+pub const MENU_HEIGHT: usize = 160;
+
+// ROMs visible at once
+pub const LIST_ROWS: usize = 10;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 2;
+const MARGIN: usize = 4;
+const COLUMNS: usize = (MENU_WIDTH - 2 * MARGIN) / CELL_WIDTH;
+// Width of the file name column in the list
+const NAME_COLUMNS: usize = 16;
+
+// 5x7 glyphs for ASCII 0x20-0x5F, one byte per row with the leftmost pixel in
+// bit 4. Lower case is drawn as upper case, anything else as '?'.
+const FONT: [[u8; GLYPH_HEIGHT]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // '#'
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // '&'
+    [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // "'"
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // '0'
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // '1'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // '2'
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // '3'
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // '4'
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // '5'
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // '6'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // '8'
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // '@'
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'A'
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // 'B'
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // 'C'
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // 'D'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // 'E'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // 'F'
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // 'G'
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'H'
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // 'L'
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'O'
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // 'P'
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // 'Q'
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // 'R'
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // 'S'
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // 'W'
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // 'Y'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // 'Z'
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\\'
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ']'
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // '_'
+];
+
+pub struct Entry {
+    pub name: String,
+    pub path: PathBuf,
+    pub info: Option<RomInfo>,
+}
+
+pub struct Launcher {
+    entries: Vec<Entry>,
+    selected: usize,
+    pixels: Vec<u8>,
+}
+
+impl Launcher {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Self {
+            entries,
+            selected: 0,
+            pixels: vec![0; MENU_WIDTH * MENU_HEIGHT * BYTES_PER_PIXEL],
+        }
+    }
+
+    // Every file in dir that fits in memory, sorted by name. Without a
+    // database the entries have no metadata. Files that can't be read are left
+    // out with a warning.
+    pub fn scan(dir: &Path, database: Option<&Database>) -> io::Result<Self> {
+        let max_size = (RAM_SIZE - START_ADDR as usize) as u64;
+        let mut entries = Vec::new();
+        for file in fs::read_dir(dir)? {
+            let path = file?.path();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            if name.starts_with('.') || name.ends_with(".json") {
+                continue;
+            }
+
+            // Sized up before reading, so big files aren't read in full
+            let fits = fs::metadata(&path)
+                .is_ok_and(|meta| meta.is_file() && (1..=max_size).contains(&meta.len()));
+            if !fits {
+                continue;
+            }
+            let rom = match fs::read(&path) {
+                Ok(rom) => rom,
+                Err(err) => {
+                    warn!("{}: {}", path.display(), err);
+                    continue;
+                }
+            };
+            let info = database.and_then(|database| database.lookup(&rom));
+            entries.push(Entry { name, path, info });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Self::new(entries))
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    // Moves the selection, stopping at the first and last ROM
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    // Selects the ROM with the given file name, if it's listed
+    pub fn select(&mut self, name: &str) {
+        if let Some(idx) = self.entries.iter().position(|entry| entry.name == name) {
+            self.selected = idx;
+        }
+    }
+
+    pub fn render(&mut self, palette: &Palette) -> &[u8] {
+        let [background, foreground, dim, _] = palette.colors;
+        self.pixels
+            .chunks_exact_mut(BYTES_PER_PIXEL)
+            .for_each(|pixel| pixel.copy_from_slice(&background));
+
+        self.text(0, "CHIP-8", foreground, 0);
+        let count = format!("{} ROMS", self.entries.len());
+        self.text(0, &count, dim, COLUMNS - count.len());
+        self.line(1, dim);
+
+        if self.entries.is_empty() {
+            self.text(2, "NO ROMS FOUND", foreground, 0);
+        }
+
+        // Keeps the selection in the middle of the list where possible
+        let first = self
+            .selected
+            .saturating_sub(LIST_ROWS / 2)
+            .min(self.entries.len().saturating_sub(LIST_ROWS));
+        let last = (first + LIST_ROWS).min(self.entries.len());
+        for (row, idx) in (first..last).enumerate() {
+            let entry = &self.entries[idx];
+            let title = entry.info.as_ref().map_or("", |info| &info.title);
+            let text = format!("{:width$}{}", entry.name, title, width = NAME_COLUMNS);
+            if idx == self.selected {
+                self.fill(row + 2, foreground);
+                self.text(row + 2, &text, background, 0);
+            } else {
+                self.text(row + 2, &text, foreground, 0);
+            }
+        }
+
+        let details = self.selected().map(details).unwrap_or_default();
+        self.line(2 + LIST_ROWS, dim);
+        for (row, text) in details.iter().enumerate() {
+            self.text(3 + LIST_ROWS + row, text, foreground, 0);
+        }
+        self.text(
+            6 + LIST_ROWS,
+            "UP/DOWN CHOOSE   ENTER PLAY   ESC QUIT",
+            dim,
+            0,
+        );
+
+        &self.pixels
+    }
+
+    // Draws text at a character position, cut off at the right margin
+    fn text(&mut self, row: usize, text: &str, color: Rgb, column: usize) {
+        for (idx, ch) in text
+            .chars()
+            .take(COLUMNS.saturating_sub(column))
+            .enumerate()
+        {
+            let glyph = glyph(ch);
+            let left = MARGIN + (column + idx) * CELL_WIDTH;
+            let top = MARGIN + row * CELL_HEIGHT + 1;
+            for (y, bits) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> x) != 0 {
+                        self.set(left + x, top + y, color);
+                    }
+                }
+            }
+        }
+    }
+
+    // Background of a whole row, for the selection
+    fn fill(&mut self, row: usize, color: Rgb) {
+        let top = MARGIN + row * CELL_HEIGHT;
+        for y in top..top + CELL_HEIGHT {
+            for x in MARGIN - 1..MENU_WIDTH - MARGIN {
+                self.set(x, y, color);
+            }
+        }
+    }
+
+    // Horizontal rule through the middle of a row
+    fn line(&mut self, row: usize, color: Rgb) {
+        let y = MARGIN + row * CELL_HEIGHT + CELL_HEIGHT / 2;
+        for x in MARGIN..MENU_WIDTH - MARGIN {
+            self.set(x, y, color);
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        let offset = (x + y * MENU_WIDTH) * BYTES_PER_PIXEL;
+        self.pixels[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&color);
+    }
+}
+
+// The database metadata of a ROM in up to three lines
+fn details(entry: &Entry) -> Vec<String> {
+    let Some(info) = &entry.info else {
+        return vec![entry.name.clone(), "NOT IN THE ROM DATABASE".to_string()];
+    };
+
+    let mut byline = info.title.clone();
+    if !info.authors.is_empty() {
+        byline += &format!(" BY {}", info.authors.join(", "));
+    }
+    if let Some(release) = &info.release {
+        byline += &format!(" ({})", release);
+    }
+
+    let platform = format!(
+        "{}, {} PER FRAME",
+        info.platform.as_deref().unwrap_or("UNKNOWN PLATFORM"),
+        info.tick_rate
+    );
+
+    // Buttons in the same order as the frontend lists them
+    let mut keys: Vec<_> = info.keys.iter().collect();
+    keys.sort_by_key(|(name, _)| button_order(name));
+    let keys = keys
+        .iter()
+        .map(|(name, key)| format!("{}={:X}", name, key))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    vec![byline, platform, keys]
+}
+
+fn button_order(name: &str) -> (bool, usize) {
+    let player2 = name.starts_with("player2");
+    let button = name.trim_start_matches("player2").to_lowercase();
+    let order = ["up", "down", "left", "right", "a", "b"]
+        .iter()
+        .position(|b| *b == button)
+        .unwrap_or(usize::MAX);
+    (player2, order)
+}
+
+fn glyph(ch: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let code = ch.to_ascii_uppercase() as usize;
+    match code {
+        0x20..=0x5F => &FONT[code - 0x20],
+        _ => &FONT['?' as usize - 0x20],
+    }
+}
@@ -0,0 +1,24 @@
+pub mod analysis;
+pub mod cheat;
+mod chip8;
+pub mod database;
+pub mod diff;
+pub mod disasm;
+pub mod env;
+pub mod gdb;
+pub mod instruction;
+pub mod jit;
+pub mod launcher;
+pub mod palette;
+pub mod phosphor;
+pub mod profile;
+pub mod quirks;
+pub mod reload;
+pub mod remote;
+pub mod renderer;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod snapshot;
+pub mod trace;
+
+pub use chip8::*;
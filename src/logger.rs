@@ -0,0 +1,64 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+// Trace records are written as is so instruction traces stay diffable,
+// everything else goes to stderr with its level in front.
+pub struct Logger {
+    level: LevelFilter,
+    trace_out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    // Traces go to the given file, or stderr when there is none
+    pub fn init(trace: bool, trace_file: Option<&Path>) -> Result<(), String> {
+        let trace_out: Box<dyn Write + Send> = match trace_file {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(path).map_err(|err| format!("{}: {}", path.display(), err))?,
+            )),
+            None => Box::new(BufWriter::new(io::stderr())),
+        };
+
+        let level = if trace {
+            LevelFilter::Trace
+        } else {
+            LevelFilter::Info
+        };
+
+        // The logger lives for the rest of the program
+        let logger = Box::leak(Box::new(Self {
+            level,
+            trace_out: Mutex::new(trace_out),
+        }));
+        log::set_logger(logger).map_err(|err| err.to_string())?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if record.level() == Level::Trace {
+            let mut out = self.trace_out.lock().unwrap();
+            writeln!(out, "{}", record.args()).ok();
+        } else {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {
+        self.trace_out.lock().unwrap().flush().ok();
+    }
+}
@@ -1,622 +1,769 @@
-use log::{info, trace, warn};
-use rand::random;
+mod capture;
+mod config;
+mod logger;
+
+use capture::Recorder;
+use chip8_rs::{
+    Chip8, SCREEN_HEIGHT, SCREEN_WIDTH, START_ADDR, TICKS_PER_FRAME, analysis,
+    cheat::Cheats,
+    database::{Database, RomInfo},
+    diff,
+    gdb::Gdb,
+    launcher::{LIST_ROWS, Launcher, MENU_HEIGHT, MENU_WIDTH},
+    palette::{Palette, Rgb},
+    phosphor::DEFAULT_DECAY,
+    profile::Profile,
+    quirks::Quirks,
+    reload::{self, Watcher},
+    remote::{Action, Pending, Remote},
+    renderer::{self, BYTES_PER_PIXEL, Renderer},
+    script::{self, Script},
+    trace,
+};
+use config::Config;
+use log::warn;
+use logger::Logger;
 use sdl2::{
-    event::Event, keyboard::Keycode, pixels::Color, rect::Rect, render::Canvas, video::Window,
+    controller::Button,
+    event::Event,
+    keyboard::Keycode,
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::{FullscreenType, Window},
+};
+use std::{
+    collections::{HashMap, hash_map::RandomState},
+    env, fs,
+    hash::BuildHasher,
+    io,
+    path::{Path, PathBuf},
+    process,
 };
-use std::{env, fs, io::stdout};
-
-const RAM_SIZE: usize = 4096;
-const SCREEN_WIDTH: usize = 64;
-const SCREEN_HEIGHT: usize = 32;
 
-const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
+// Default window scale
+const SCALE: u32 = 15;
+// Largest --scale, the window is 4096x2048 then
+const MAX_SCALE: u32 = 64;
 
-const NUM_REGS: usize = 16;
-const STACK_SIZE: usize = 16;
+// What scripts draw is tinted with this
+const OVERLAY: Rgb = [255, 0, 0];
 
-const NUM_KEYS: usize = 16;
-const START_ADDR: u16 = 0x200;
+// How often --watch checks the ROM file, in frames
+const WATCH_FRAMES: u64 = 30;
 
-const TICKS_PER_FRAME: u16 = 10;
+// Without a ROM the launcher lists the ROM directory: Up/Down (or the d-pad)
+// to choose, Return/Space (or A/Start) to play. Escape (or Back) goes back to
+// the launcher, Escape in the launcher quits.
+//
+// GAME KEYS
+//
+// The keypad is on 1234/QWER/ASDF/ZXCV. For ROMs in the database the game's
+// buttons are also on
+//  Arrows - Up/Down/Left/Right
+//  Space - A
+//  Return - B
+//  I/K/J/L - Player 2 Up/Down/Left/Right
+//  U/O - Player 2 A/B
+// and on a gamepad's d-pad and A/B buttons, see data/programs.json for which keypad key each button is.
+//
+// EMULATOR KEYS
+//
+//  F2 - Next palette
+//  F3 - Toggle pixel persistence
+//  F9 - Start/stop recording a GIF
+//  F11 - Toggle fullscreen
+//  F12 - Screenshot
+
+fn parse_key(keycode: Keycode) -> Option<usize> {
+    match keycode {
+        Keycode::Num0 => Some(0x0),
+        Keycode::Num1 => Some(0x1),
+        Keycode::Num2 => Some(0x2),
+        Keycode::Num3 => Some(0x3),
+        Keycode::Num4 => Some(0xC),
+        Keycode::Q => Some(0x4),
+        Keycode::W => Some(0x5),
+        Keycode::E => Some(0x6),
+        Keycode::R => Some(0xD),
+        Keycode::A => Some(0x7),
+        Keycode::S => Some(0x8),
+        Keycode::D => Some(0x9),
+        Keycode::F => Some(0xE),
+        Keycode::Z => Some(0xA),
+        Keycode::X => Some(0x0),
+        Keycode::C => Some(0xB),
+        Keycode::V => Some(0xF),
+        _ => None,
+    }
+}
 
-const SCALE: u32 = 15;
+// Keyboard keys for the ROM database's button names
+const GAME_KEYS: [(&str, Keycode); 12] = [
+    ("up", Keycode::Up),
+    ("down", Keycode::Down),
+    ("left", Keycode::Left),
+    ("right", Keycode::Right),
+    ("a", Keycode::Space),
+    ("b", Keycode::Return),
+    ("player2Up", Keycode::I),
+    ("player2Down", Keycode::K),
+    ("player2Left", Keycode::J),
+    ("player2Right", Keycode::L),
+    ("player2A", Keycode::U),
+    ("player2B", Keycode::O),
+];
 
-const FONTSET_SIZE: usize = 80;
-const FONTSET: [u8; FONTSET_SIZE] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+// Gamepad buttons for the same names
+const GAME_BUTTONS: [(&str, Button); 6] = [
+    ("up", Button::DPadUp),
+    ("down", Button::DPadDown),
+    ("left", Button::DPadLeft),
+    ("right", Button::DPadRight),
+    ("a", Button::A),
+    ("b", Button::B),
 ];
 
-// GAME KEYS
-//
-// PONG:
-//  Left player
-//      Q - Down
-//      1 - Up
-//  Right player
-//      R - Down
-//      4 - Up
-// Tetris:
-//  W - Left
-//  E - Right
-//  Q - Flip
-//  A - Down
-
-struct Chip8 {
-    memory: [u8; RAM_SIZE],
-    v_reg: [u8; NUM_REGS],
-    i_reg: u16,
-    sp: u16,
-    pc: u16,
-    stack: [u16; STACK_SIZE],
-    delay_timer: u8,
-    sound_timer: u8,
-    keys: [bool; NUM_KEYS],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
-    opcode: u16,
+fn map_key(game_keys: &HashMap<String, u8>, keycode: Keycode) -> Option<usize> {
+    GAME_KEYS
+        .iter()
+        .find(|(_, game_key)| *game_key == keycode)
+        .and_then(|(name, _)| game_keys.get(*name))
+        .map(|key| *key as usize & 0xF)
+        .or_else(|| parse_key(keycode))
 }
 
-impl Chip8 {
-    fn new() -> Self {
-        let mut emu = Self {
-            memory: [0; RAM_SIZE],
-            sp: 0,
-            pc: START_ADDR,
-            v_reg: [0; NUM_REGS],
-            i_reg: 0,
-            stack: [0; STACK_SIZE],
-            delay_timer: 0,
-            keys: [false; NUM_KEYS],
-            sound_timer: 0,
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
-            opcode: 0,
-        };
+fn map_button(game_keys: &HashMap<String, u8>, button: Button) -> Option<usize> {
+    GAME_BUTTONS
+        .iter()
+        .find(|(_, game_button)| *game_button == button)
+        .and_then(|(name, _)| game_keys.get(*name))
+        .map(|key| *key as usize & 0xF)
+}
 
-        emu.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
-        emu
-    }
+fn draw(
+    canvas: &mut Canvas<Window>,
+    texture: &mut Texture,
+    pixels: &[u8],
+    (width, height): (usize, usize),
+    background: Rgb,
+    integer_scale: bool,
+) {
+    texture
+        .update(None, pixels, width * BYTES_PER_PIXEL)
+        .unwrap();
+
+    // Letterbox bars use the background colour
+    let [r, g, b] = background;
+    canvas.set_draw_color(Color::RGB(r, g, b));
+    canvas.clear();
+
+    let (x, y, width, height) = renderer::viewport(
+        canvas.output_size().unwrap(),
+        (width as u32, height as u32),
+        integer_scale,
+    );
+    canvas
+        .copy(texture, None, Rect::new(x, y, width, height))
+        .unwrap();
+}
 
-    fn load_rom(&mut self, rom: &[u8]) {
-        let size = rom.len() + START_ADDR as usize;
-        self.memory[START_ADDR as usize..size].copy_from_slice(rom);
+fn start_recording(path: &Path, config: &Config) -> Option<Recorder> {
+    match Recorder::new(path, SCREEN_WIDTH, SCREEN_HEIGHT, config.scale as usize) {
+        Ok(recorder) => {
+            println!("Recording: {}", path.display());
+            Some(recorder)
+        }
+        Err(err) => {
+            warn!("Recording failed: {}", err);
+            None
+        }
+    }
+}
 
-        for i in self.memory {
-            //print!("{:x}, ", i);
+// Position of a palette in the list, adding it when it's not a builtin one
+fn palette_index(palettes: &mut Vec<Palette>, palette: Palette) -> usize {
+    match palettes.iter().position(|p| *p == palette) {
+        Some(idx) => idx,
+        None => {
+            palettes.push(palette);
+            palettes.len() - 1
         }
     }
+}
+
+// The ROM being played, the launcher is shown when there is none
+struct Game {
+    name: String,
+    // From the database, the name otherwise
+    title: String,
+    path: PathBuf,
+    // As loaded, to see what changed when the file does
+    rom: Vec<u8>,
+    // With --watch
+    watcher: Option<Watcher>,
+    // Keypad keys of the game's buttons, from the database
+    keys: HashMap<String, u8>,
+    palette: Option<Palette>,
+    // Frozen bytes, written every frame
+    cheats: Cheats,
+}
 
-    fn push_to_stack(&mut self, value: u16) {
-        self.stack[self.sp as usize] = value;
-        self.sp += 1;
+// Loads a ROM into a fresh machine, set up from the database and then the
+// command line
+fn start(
+    chip8: &mut Chip8,
+    path: &Path,
+    database: &Database,
+    config: &Config,
+) -> Result<Game, String> {
+    println!("Loading ROM: {}", path.display());
+    let rom = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    chip8.reset();
+    chip8.load_rom(&rom).map_err(|err| err.to_string())?;
+    chip8.quirks = Quirks::default();
+    chip8.tick_rate = TICKS_PER_FRAME;
+    chip8.profile = config.profile.as_ref().map(|_| Profile::new());
+
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let mut game = Game {
+        title: name.clone(),
+        name,
+        path: path.to_path_buf(),
+        rom: Vec::new(),
+        watcher: config.watch.then(|| Watcher::new(path)),
+        keys: HashMap::new(),
+        palette: None,
+        cheats: Cheats::default(),
+    };
+    if config.use_database
+        && let Some(info) = database.lookup(&rom)
+    {
+        println!("{}", describe(&info));
+        info.apply(chip8);
+        game.title = info.title.clone();
+        game.keys = info.keys;
+        game.palette = info.palette;
     }
-    fn pop_from_stack(&mut self) -> u16 {
-        self.sp -= 1;
-        let value = self.stack[self.sp as usize];
-        self.stack[self.sp as usize] = 0;
-        value
+    if let Some(platform) = config
+        .platform
+        .as_deref()
+        .and_then(|id| database.platform(id))
+    {
+        chip8.quirks = platform.quirks;
+        chip8.tick_rate = platform.default_tickrate;
     }
-
-    fn reset(&mut self) {
-        self.pc = START_ADDR;
-        self.sp = 0;
-        self.i_reg = 0;
-        self.sound_timer = 0;
-        self.delay_timer = 0;
-        self.memory[..FONTSET_SIZE].copy_from_slice(&FONTSET);
-        self.v_reg = [0; NUM_REGS];
-        self.stack = [0; STACK_SIZE];
-        self.keys = [false; NUM_KEYS];
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-        self.opcode = 0;
+    if let Some(tick_rate) = config.tick_rate {
+        chip8.tick_rate = tick_rate;
     }
 
-    fn fetch_instruction(&mut self) -> u16 {
-        let first_byte: u16 = self.memory[self.pc as usize] as u16; // USIZE WILL OVERFLOW?
-        let second_byte: u16 = self.memory[(self.pc + 1) as usize] as u16;
-
-        //println!(
-        //    "First byte: {:x} {:b} {}",
-        //    self.memory[self.pc as usize],
-        //    self.memory[self.pc as usize],
-        //    self.memory[self.pc as usize]
-        //);
-        //println!(
-        //    "Second byte: {:x} {:b} {}",
-        //    self.memory[self.pc as usize + 1],
-        //    self.memory[self.pc as usize + 1],
-        //    self.memory[self.pc as usize + 1]
-        //);
-        //println!("Bytes: {}", first_byte + second_byte);
-
-        let op = (first_byte << 8) | second_byte;
-        //let combined = second_byte << 8;
-
-        //println!("Shift to left: {:x} {:b}", combined, combined);
-        //println!("OP: {:x} {:b} {}", op, op, op);
-        self.pc += 2;
-        op
+    let cheat_dir = config
+        .cheats
+        .clone()
+        .unwrap_or_else(|| config.rom_dir.join("cheats"));
+    game.cheats = Cheats::load(&cheat_dir, &rom).unwrap_or_else(|err| {
+        warn!("Failed to load cheats: {}", err);
+        Cheats::default()
+    });
+    if !game.cheats.list.is_empty() {
+        println!("{} cheats", game.cheats.list.len());
     }
 
-    fn execute_instruction(&mut self, op: u16) {
-        let digits: (u16, u16, u16, u16) = (op >> 12, (op >> 8) & 0xF, (op >> 4) & 0xF, op & 0xF);
-        match digits {
-            (0, 0, 0, 0) => return, // NOP
-            (0, 0, 0xE, 0) => {
-                // CLEAR SCREEN
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-            }
-            (0, 0, 0xE, 0xE) => {
-                // RET
-                self.pc = self.pop_from_stack();
-            }
-            (1, n1, n2, n3) => {
-                // JUMP
-                self.pc = (n1 << 8) | (n2 << 4) | n3;
-            }
-
-            (2, n1, n2, n3) => {
-                // CALL
+    game.rom = rom;
+    Ok(game)
+}
 
-                self.push_to_stack(self.pc);
-                //self.pc = (n1 << 8) | (n2 << 4) | n3;
-                self.pc = (n1 << 8) | (n2 << 4) | n3;
-            }
-            (3, x, n1, n2) => {
-                // Skip if VX = NN
-                let vx = self.v_reg[x as usize] as u16;
+fn run(chip8: &mut Chip8, config: &Config, database: &Database, mut game: Option<Game>) {
+    let mut palettes = Palette::builtin();
+    let palette = config
+        .palette
+        .clone()
+        .or_else(|| game.as_ref().and_then(|game| game.palette.clone()))
+        .unwrap_or_else(Palette::classic);
+    let mut palette_idx = palette_index(&mut palettes, palette);
+
+    let mut renderer = Renderer::new(config.decay, chip8.screen.len());
+    let persistence_decay = if config.decay > 0.0 {
+        config.decay
+    } else {
+        DEFAULT_DECAY
+    };
+
+    let mut launcher = Launcher::scan(&config.rom_dir, config.use_database.then_some(database))
+        .unwrap_or_else(|err| {
+            warn!("Failed to list {}: {}", config.rom_dir.display(), err);
+            Launcher::new(Vec::new())
+        });
+    if let Some(game) = &game {
+        launcher.select(&game.name);
+    }
 
-                let nn = (n1 << 4) | n2;
+    let mut remote = config.remote.as_deref().map(|addr| {
+        let remote = Remote::listen(addr)
+            .unwrap_or_else(|err| panic!("Failed to listen on {}: {}", addr, err));
+        println!("Remote control on {}", remote.addr());
+        remote
+    });
+    let mut gdb = config.gdb.as_deref().map(|addr| {
+        let gdb =
+            Gdb::listen(addr).unwrap_or_else(|err| panic!("Failed to listen on {}: {}", addr, err));
+        println!("Waiting for a debugger on {}", gdb.addr());
+        gdb
+    });
+    let mut script = config
+        .script
+        .as_deref()
+        .map(|path| Script::load(path, chip8).unwrap_or_else(|err| panic!("{}", err)));
+
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let controller_subsystem = sdl_context.game_controller().unwrap();
+    // Kept open for their events
+    let mut controllers = Vec::new();
+
+    let mut window = video_subsystem
+        .window(
+            "Chip8",
+            SCREEN_WIDTH as u32 * config.scale,
+            SCREEN_HEIGHT as u32 * config.scale,
+        )
+        .position_centered()
+        .resizable()
+        .opengl()
+        .build()
+        .unwrap();
+
+    if config.fullscreen {
+        window.set_fullscreen(FullscreenType::Desktop).unwrap();
+    }
 
-                if vx == nn {
-                    self.pc += 2;
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+        )
+        .unwrap();
+    let mut menu_texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            MENU_WIDTH as u32,
+            MENU_HEIGHT as u32,
+        )
+        .unwrap();
+
+    canvas.clear();
+    canvas.present();
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let mut last_cycle_time = std::time::Instant::now();
+
+    let mut frame: u64 = 0;
+    let mut take_screenshot = false;
+    let mut recorder: Option<Recorder> = None;
+    // Set when the program hits an error, the last screen stays up
+    let mut halted = false;
+
+    'running: loop {
+        // Switching between the launcher and a game, done after the events.
+        // Games started by a remote client are answered once they are.
+        let mut launch: Vec<(PathBuf, Option<Pending>)> = Vec::new();
+        let mut leave = false;
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => break 'running,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => match game {
+                    Some(_) => leave = true,
+                    None => break 'running,
+                },
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match controller_subsystem.open(which) {
+                        Ok(controller) => controllers.push(controller),
+                        Err(err) => warn!("Failed to open gamepad: {}", err),
+                    }
                 }
-            }
-            (4, x, n1, n2) => {
-                // Skip if VX != NN
-                let vx = self.v_reg[x as usize] as u16;
-
-                let nn = (n1 << 4) | n2;
-
-                if vx != nn {
-                    self.pc += 2;
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    ..
+                } => {
+                    palette_idx = (palette_idx + 1) % palettes.len();
+                    println!("Palette: {}", palettes[palette_idx].name);
                 }
-            }
-            (5, x, y, 0) => {
-                // Skip if VX = VY
-                let vx = self.v_reg[x as usize] as u16;
-                let vy = self.v_reg[y as usize] as u16;
-
-                if vx == vy {
-                    self.pc += 2;
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    ..
+                } => {
+                    if renderer.phosphor.decay() > 0.0 {
+                        renderer.phosphor.set_decay(0.0);
+                        println!("Persistence: off");
+                    } else {
+                        renderer.phosphor.set_decay(persistence_decay);
+                        println!("Persistence: {}", persistence_decay);
+                    }
                 }
-            }
-            (6, x, n1, n2) => {
-                // SET REGISTER TO VX
-
-                self.v_reg[x as usize] = ((n1 << 4) | n2) as u8;
-            }
-
-            (7, x, _, _) => {
-                // ADD VALUE REGISTER TO VX
-
-                self.v_reg[x as usize] = self.v_reg[x as usize].wrapping_add((op & 0xFF) as u8);
-            }
-            (8, x, y, 0) => {
-                // Set VX to VY
-                let vy = self.v_reg[y as usize];
-
-                self.v_reg[x as usize] = vy;
-            }
-            (8, x, y, 1) => {
-                // Set VX to bitwise OR of VX and VY
-                let vy = self.v_reg[y as usize];
-
-                self.v_reg[x as usize] |= vy;
-            }
-            (8, x, y, 2) => {
-                // Set VX to bitwise AND of VX and VY
-                let vy = self.v_reg[y as usize];
-
-                self.v_reg[x as usize] &= vy;
-            }
-            (8, x, y, 3) => {
-                // Set VX to bitwise XOR of VX and VY
-                let vy = self.v_reg[y as usize];
-
-                self.v_reg[x as usize] ^= vy;
-            }
-            (8, x, y, 4) => {
-                // Set VX to VX + VY
-                let vy = self.v_reg[y as usize];
-                let vx = self.v_reg[x as usize];
-
-                let (new_vx, carry) =
-                    self.v_reg[x as usize].overflowing_add(self.v_reg[y as usize]);
-
-                //println!("added new vx: {} {} {}", new_vx, vx, vy);
-                // Set carry bit
-                self.v_reg[0xF] = if carry { 1 } else { 0 };
-
-                self.v_reg[x as usize] = new_vx;
-            }
-            (8, x, y, 5) => {
-                // Set VX to VX - VY
-                let vy = self.v_reg[y as usize];
-                let vx = self.v_reg[x as usize];
-
-                let (new_vx, borrow) =
-                    self.v_reg[x as usize].overflowing_sub(self.v_reg[y as usize]);
-
-                //println!("sub new vx: {} {} {}", new_vx, vx, vy);
-                // Set carry bit
-                self.v_reg[0xF] = if borrow { 0 } else { 1 };
-
-                self.v_reg[x as usize] = new_vx;
-            }
-
-            (8, x, y, 6) => {
-                // Right shift
-                // Put VY into VX and shift the value in VX 1 bit to the right.
-                // Set flag register to the bit shiftet out
-                let vy = self.v_reg[y as usize];
-                let vx = self.v_reg[x as usize];
-
-                let lsb = self.v_reg[x as usize] & 1;
-                self.v_reg[x as usize] >>= 1;
-                self.v_reg[0xF] = lsb;
-            }
-            (8, x, y, 0xE) => {
-                // Left shift ?
-                let msb = (self.v_reg[x as usize] >> 7) & 1;
-                //println!("msb: {:x}", msb);
-                self.v_reg[x as usize] <<= 1;
-                //println!("vreg: {:x}", self.v_reg[x as usize]);
-                self.v_reg[0xF] = msb;
-            }
-            (8, x, y, 7) => {
-                // Set VX to VY - VX
-                let vy = self.v_reg[y as usize];
-                let vx = self.v_reg[x as usize];
-
-                let (new_vx, borrow) =
-                    self.v_reg[y as usize].overflowing_sub(self.v_reg[x as usize]);
-
-                //println!("sub new vx: {} {} {}", new_vx, vx, vy);
-                // Set carry bit
-                self.v_reg[0xF] = if borrow { 0 } else { 1 };
-
-                self.v_reg[x as usize] = new_vx;
-            }
-            (9, x, y, 0) => {
-                // Skip if VX != VY
-                let vx = self.v_reg[x as usize] as u16;
-                let vy = self.v_reg[y as usize] as u16;
-
-                if vx != vy {
-                    self.pc += 2;
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    ..
+                } => {
+                    let window = canvas.window_mut();
+                    let fullscreen = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    window.set_fullscreen(fullscreen).unwrap();
                 }
+                _ => {}
             }
 
-            (0xA, n1, n2, n3) => {
-                // SET INDEX REGISTER I
-
-                //let nnn = op & 0xFFF;
-                //self.i_reg = nnn;
-                self.i_reg = (n1 << 8) | (n2 << 4) | n3;
-            }
-            (0xB, n1, n2, n3) => {
-                //let v0 = self.v_reg[0] as u16;
-                //let nnn = (n1 << 8) | (n2 << 4) | n3;
-
-                //let added = nnn.wrapping_add(v0);
-                //let added2 = v0 + (op & 0xFFF);
-                //println!("nnn: {}", nnn);
-                //println!("opn: {}", op & 0xFFF);
-                //println!("added {} added 2: {}", added, added2);
-
-                //self.pc = added;
-                let nnn = op & 0xFFF;
-                self.pc = (self.v_reg[0] as u16) + nnn;
-            }
-            (0xC, x, n1, n2) => {
-                // Generate random number and AND it with NN
-                let rand: u8 = random();
-
-                let value = ((n1 << 4) | n2) as u8;
-
-                self.v_reg[x as usize] = rand & value;
-            }
-            (0xD, x, y, n) => {
-                // DISPLAY/DRAW
-
-                let x_coord = self.v_reg[x as usize] as u16;
-                let y_coord = self.v_reg[y as usize] as u16;
-
-                //println!("Coords x: {} y: {}", x_coord, y_coord);
-
-                let mut flipped = false;
-                for i in 0..n {
-                    let sprite_byte = self.memory[(self.i_reg + i) as usize];
-
-                    //println!("Spr btye: {:b} {:x}", sprite_byte, sprite_byte);
-                    for j in 0..8 {
-                        //let pixel_bit = sprite_byte[j];
-
-                        //println!(
-                        //    "S: {:b} {:x}",
-                        //    sprite_byte & (0b10000000 >> j),
-                        //    sprite_byte & (0b10000000 >> j)
-                        //);
-                        if sprite_byte & (0b10000000 >> j) != 0 {
-                            let x = (x_coord + j) as usize % SCREEN_WIDTH;
-                            let y = (y_coord + i) as usize % SCREEN_WIDTH;
-
-                            let idx = x + SCREEN_WIDTH * y;
-                            if idx < self.screen.len() {
-                                flipped |= self.screen[idx];
-                                self.screen[idx] ^= true;
-                            }
+            match &game {
+                // Launcher
+                None => match event {
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => match keycode {
+                        Keycode::Up => launcher.move_selection(-1),
+                        Keycode::Down => launcher.move_selection(1),
+                        Keycode::PageUp => launcher.move_selection(-(LIST_ROWS as isize)),
+                        Keycode::PageDown => launcher.move_selection(LIST_ROWS as isize),
+                        Keycode::Home => launcher.move_selection(isize::MIN),
+                        Keycode::End => launcher.move_selection(isize::MAX),
+                        Keycode::Return | Keycode::Space => launch
+                            .extend(launcher.selected().map(|entry| (entry.path.clone(), None))),
+                        _ => {}
+                    },
+                    Event::ControllerButtonDown { button, .. } => match button {
+                        Button::DPadUp => launcher.move_selection(-1),
+                        Button::DPadDown => launcher.move_selection(1),
+                        Button::A | Button::Start => launch
+                            .extend(launcher.selected().map(|entry| (entry.path.clone(), None))),
+                        _ => {}
+                    },
+                    _ => {}
+                },
+                Some(game) => match event {
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F12),
+                        ..
+                    } => take_screenshot = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::F9),
+                        ..
+                    } => match recorder.take() {
+                        Some(recording) => println!("Recorded {} frames", recording.frames()),
+                        None => {
+                            let path = capture_path(&game.name, frame, "gif");
+                            recorder = start_recording(&path, config);
+                        }
+                    },
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => {
+                        if let Some(key) = map_key(&game.keys, keycode) {
+                            chip8.key_press(key, true);
                         }
                     }
-                }
-
-                if flipped {
-                    self.v_reg[15] = 1;
-                } else {
-                    self.v_reg[15] = 0;
-                }
+                    Event::KeyUp {
+                        keycode: Some(keycode),
+                        ..
+                    } => {
+                        if let Some(key) = map_key(&game.keys, keycode) {
+                            chip8.key_press(key, false);
+                        }
+                    }
+                    Event::ControllerButtonDown {
+                        button: Button::Back | Button::Guide,
+                        ..
+                    } => leave = true,
+                    Event::ControllerButtonDown { button, .. } => {
+                        if let Some(key) = map_button(&game.keys, button) {
+                            chip8.key_press(key, true);
+                        }
+                    }
+                    Event::ControllerButtonUp { button, .. } => {
+                        if let Some(key) = map_button(&game.keys, button) {
+                            chip8.key_press(key, false);
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        }
 
-                //let start = self.screen[self.i_reg as usize];
+        if leave && let Some(game) = game.take() {
+            save_profile(chip8, &game, config);
+            if let Some(recording) = recorder.take() {
+                println!("Recorded {} frames", recording.frames());
             }
-            (0xE, x, 9, 0xE) => {
-                // Skip if key in VX is pressed
-                let key = self.v_reg[x as usize];
-                if self.keys[key as usize] {
-                    self.pc += 2;
+            launcher.select(&game.name);
+            canvas.window_mut().set_title("Chip8").unwrap();
+        }
+        if let Some(remote) = &mut remote {
+            let mut no_cheats = Cheats::default();
+            let cheats = game
+                .as_mut()
+                .map_or(&mut no_cheats, |game| &mut game.cheats);
+            for (action, pending) in remote.poll(chip8, cheats) {
+                match action {
+                    Action::Load(path) => launch.push((config.rom_dir.join(path), Some(pending))),
+                    Action::Reset => match &game {
+                        Some(game) => launch.push((game.path.clone(), Some(pending))),
+                        None => remote.finish(pending, Err("No ROM loaded".to_string())),
+                    },
                 }
             }
-            (0xE, x, 0xA, 1) => {
-                // Skip if key in VX is not pressed
-                let key = self.v_reg[x as usize];
-                if !self.keys[key as usize] {
-                    self.pc += 2;
+        }
+        if let Some(gdb) = &mut gdb {
+            gdb.poll(chip8);
+        }
+        for (path, pending) in launch {
+            if let Some(game) = &game {
+                save_profile(chip8, game, config);
+            }
+            let result = start(chip8, &path, database, config).map(|started| {
+                if config.palette.is_none()
+                    && let Some(palette) = &started.palette
+                {
+                    palette_idx = palette_index(&mut palettes, palette.clone());
+                }
+                canvas
+                    .window_mut()
+                    .set_title(&format!("Chip8 - {}", started.title))
+                    .unwrap();
+                renderer = Renderer::new(renderer.phosphor.decay(), chip8.screen.len());
+                frame = 0;
+                halted = false;
+                game = Some(started);
+            });
+            match (&mut remote, pending) {
+                (Some(remote), Some(pending)) => remote.finish(pending, result),
+                _ => {
+                    if let Err(err) = result {
+                        warn!("{}", err);
+                    }
                 }
             }
-            (0xF, x, 0, 7) => {
-                // Sets VX to the current value of the delay timer
+        }
 
-                self.v_reg[x as usize] = self.delay_timer;
+        if let Some(current) = &mut game
+            && frame.is_multiple_of(WATCH_FRAMES)
+            && let Some(rom) = current.watcher.as_mut().and_then(Watcher::poll)
+        {
+            if config.keep_state && reload::patch(chip8, &current.rom, &rom) {
+                println!("Reloaded {}, kept the running state", current.name);
+                current.rom = rom;
+            } else {
+                save_profile(chip8, current, config);
+                match start(chip8, &current.path, database, config) {
+                    Ok(restarted) => {
+                        *current = restarted;
+                        halted = false;
+                    }
+                    Err(err) => warn!("Reload failed: {}", err),
+                }
             }
-            (0xF, x, 1, 5) => {
-                // Sets the delay timer to the value in VX
+        }
 
-                self.delay_timer = self.v_reg[x as usize];
-            }
-            (0xF, x, 1, 8) => {
-                // Sets the sound timer to the value in VX
+        let now = std::time::Instant::now();
+
+        let palette = &palettes[palette_idx];
+        let Some(game) = &game else {
+            let pixels = launcher.render(palette);
+            draw(
+                &mut canvas,
+                &mut menu_texture,
+                pixels,
+                (MENU_WIDTH, MENU_HEIGHT),
+                palette.colors[0],
+                config.integer_scale,
+            );
+            canvas.present();
+            continue;
+        };
 
-                self.sound_timer = self.v_reg[x as usize];
+        if !halted {
+            game.cheats.apply(chip8);
+            // The remote or the debugger holds the program while it's paused
+            let result = match (&mut remote, &mut gdb, &mut script) {
+                (Some(remote), ..) => remote.run_frame(chip8),
+                (_, Some(gdb), _) => gdb.run_frame(chip8),
+                (.., Some(running)) => match running.run_frame(chip8) {
+                    Err(script::Error::Program(err)) => Err(err),
+                    // The game goes on without it
+                    Err(err) => {
+                        warn!("{}", err);
+                        script = None;
+                        Ok(())
+                    }
+                    Ok(()) => Ok(()),
+                },
+                _ => chip8.run_frame(),
+            };
+            if let Err(err) = result {
+                warn!("{}", err);
+                halted = true;
             }
-            (0xF, x, 1, 0xE) => {
-                // Add the value in VX to the index register I.
-                // ADD OTPION
-
-                //self.i_reg += self.v_reg[x as usize] as u16;
-                let vx = self.v_reg[x as usize] as u16;
-                self.i_reg = self.i_reg.wrapping_add(vx);
+        }
+        renderer.render(&chip8.screen, palette);
+        if let Some(script) = &script {
+            renderer.highlight(&script.overlay(), OVERLAY);
+        }
+        let pixels = renderer.pixels();
+        draw(
+            &mut canvas,
+            &mut texture,
+            pixels,
+            (SCREEN_WIDTH, SCREEN_HEIGHT),
+            palette.colors[0],
+            config.integer_scale,
+        );
+
+        let pixels = renderer.pixels();
+        let mut screenshots = Vec::new();
+        if take_screenshot || config.screenshot == Some(frame) {
+            screenshots.push(capture_path(&game.name, frame, "png"));
+            take_screenshot = false;
+        }
+        if let Some(script) = &mut script {
+            screenshots.extend(script.take_screenshots().into_iter().map(|path| {
+                path.map_or_else(|| capture_path(&game.name, frame, "png"), PathBuf::from)
+            }));
+        }
+        for path in screenshots {
+            match capture::save_png(
+                &path,
+                pixels,
+                SCREEN_WIDTH,
+                SCREEN_HEIGHT,
+                config.scale as usize,
+            ) {
+                Ok(()) => println!("Screenshot: {}", path.display()),
+                Err(err) => warn!("Screenshot failed: {}", err),
             }
-            (0xF, x, 0, 0xA) => {
-                // Waits for key input
-
-                println!("WAIT");
-                //let key = self.keys.iter().enumerate().find(|(i, k)| **k);
-                //if let Some(key) = key {
-                //    self.v_reg[x as usize] = key.0 as u8;
-                //} else {
-                //    println!("No key");
-                //}
-                let mut pressed = false;
-                for i in 0..self.keys.len() {
-                    if self.keys[i] {
-                        self.v_reg[x as usize] = i as u8;
-                        pressed = true;
-                        break;
-                    }
-                }
+        }
 
-                if !pressed {
-                    // Redo opcode
-                    self.pc -= 2;
-                }
+        if let Some(record) = &config.record {
+            if frame == record.start {
+                recorder = start_recording(&record.path, config);
             }
-
-            (0xF, x, 2, 9) => {
-                self.i_reg = self.v_reg[x as usize] as u16 * 5;
+            if frame == record.end {
+                recorder = None;
+                println!("Recording finished");
             }
-            (0xF, x, 3, 3) => {
-                let vx = self.v_reg[x as usize] as f64;
-
-                let hundreds = (vx / 100.0).floor() as u8;
-                let tens = ((vx / 10.0) % 10.0).floor() as u8;
-                let ones = (vx % 10.0).floor() as u8;
-
-                self.memory[self.i_reg as usize] = hundreds;
-                self.memory[self.i_reg as usize + 1] = tens;
-                self.memory[self.i_reg as usize + 2] = ones;
-                //r.trunc()
-
-                //if vx / 10.0 < 1.0 {
-                //        let n = (vx / 10.0).rou
-                //} else if vx / 100.0 < 1.0 {
+        }
+        if let Some(recording) = &mut recorder
+            && let Err(err) = recording.add_frame(pixels)
+        {
+            warn!("Recording failed: {}", err);
+            recorder = None;
+        }
 
-                // } else if vx / 1000.0 < 1.0 {
+        frame += 1;
+        //if last_cycle_time + std::time::Duration::from_micros(1) <= now {
+        //    // Fetch
+        //    let op = chip8.fetch_instruction();
 
-                //}
-            }
-            (0xF, x, 5, 5) => {
-                // Stores the value in the registers from V0 to VX into memory from the address in I
-                // Eg. V0 holds 0x20; I holds 0x40; memory[0x40] = 0x20;
-                // Maybe option
+        //    // Decode and execute
+        //    let exec = chip8.execute_instruction(op);
 
-                for j in 0..=x {
-                    self.memory[self.i_reg as usize + j as usize] = self.v_reg[j as usize];
-                }
-            }
-            (0xF, x, 6, 5) => {
-                // Takes values in memory addresses V0 to VX and loads them into the variable registers
-                // Maybe option
+        //    chip8.draw(&mut canvas);
 
-                for j in 0..=x {
-                    self.v_reg[j as usize] = self.memory[self.i_reg as usize + j as usize]
-                }
-            }
-            _ => panic!("Invalid opcode: {:x}", op),
-        };
-    }
-
-    fn parse_key(&mut self, keycode: Keycode) -> Option<usize> {
-        match keycode {
-            Keycode::Num0 => Some(0x0),
-            Keycode::Num1 => Some(0x1),
-            Keycode::Num2 => Some(0x2),
-            Keycode::Num3 => Some(0x3),
-            Keycode::Num4 => Some(0xC),
-            Keycode::Q => Some(0x4),
-            Keycode::W => Some(0x5),
-            Keycode::E => Some(0x6),
-            Keycode::R => Some(0xD),
-            Keycode::A => Some(0x7),
-            Keycode::S => Some(0x8),
-            Keycode::D => Some(0x9),
-            Keycode::F => Some(0xE),
-            Keycode::Z => Some(0xA),
-            Keycode::X => Some(0x0),
-            Keycode::C => Some(0xB),
-            Keycode::V => Some(0xF),
-            _ => None,
-        }
+        //    last_cycle_time = std::time::Instant::now();
+        //}
+        canvas.present();
     }
 
-    fn key_press(&mut self, keycode: usize, pressed: bool) {
-        self.keys[keycode] = pressed;
+    if let Some(game) = &game {
+        save_profile(chip8, game, config);
     }
+}
 
-    fn draw(&self, canvas: &mut Canvas<Window>) {
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        for (i, pixel) in self.screen.iter().enumerate() {
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
-            if *pixel {
-                let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-                canvas.fill_rect(rect).unwrap();
-            }
+// Writes the game's profile to --profile's path, if it's set
+fn save_profile(chip8: &Chip8, game: &Game, config: &Config) {
+    let (Some(path), Some(profile)) = (&config.profile, &chip8.profile) else {
+        return;
+    };
+    let end = (START_ADDR as usize + game.rom.len()) as u16;
+    let rom = START_ADDR..end;
+    let report = match path.extension() {
+        Some(extension) if extension == "json" => {
+            serde_json::to_string_pretty(&profile.json(chip8.memory(), rom.clone())).unwrap()
         }
-        canvas.present();
+        _ => format!(
+            "{}\n{}",
+            profile.report(chip8.memory(), rom.clone()),
+            profile.listing(chip8.memory(), rom)
+        ),
+    };
+    match fs::write(path, report) {
+        Ok(()) => println!("Profile: {}", path.display()),
+        Err(err) => warn!("Failed to save the profile: {}", err),
     }
+}
 
-    fn tick_timers(&mut self) {
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-        }
-
-        if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                self.beep()
-            }
+// Captures are saved in the working directory, named after the ROM and frame
+fn capture_path(rom_name: &str, frame: u64, extension: &str) -> PathBuf {
+    PathBuf::from(format!("{}-{:06}.{}", rom_name, frame, extension))
+}
 
-            self.sound_timer -= 1;
+// Headless replay of a trace from another emulator
+fn diff_trace(chip8: &mut Chip8, path: &Path) {
+    let file = fs::File::open(path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    match diff::replay_trace(chip8, io::BufReader::new(file)) {
+        Ok(None) => println!("Trace matches ({} instructions)", chip8.cycles),
+        Ok(Some(divergence)) => {
+            println!("Diverged at {}", divergence);
+            process::exit(1);
+        }
+        Err(err) => {
+            println!("Replay stopped: {}", err);
+            process::exit(1);
         }
     }
+}
 
-    fn beep(&self) {
-        println!("BEEP")
+// Headless static analysis of a ROM
+fn analyze(rom: &[u8], dot: Option<&Path>) {
+    let cfg = analysis::analyze(rom).unwrap_or_else(|err| panic!("{}", err));
+    println!("{}\n{}", cfg.summary(), cfg.listing());
+    if let Some(path) = dot {
+        fs::write(path, cfg.dot()).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        println!("Graph: {}", path.display());
     }
+}
 
-    fn run(&mut self) {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-
-        let window = video_subsystem
-            .window("Chip8", WINDOW_WIDTH, WINDOW_HEIGHT)
-            .position_centered()
-            .opengl()
-            .build()
-            .unwrap();
-
-        let mut canvas = window.into_canvas().present_vsync().build().unwrap();
-
-        canvas.clear();
-        canvas.present();
-
-        let mut event_pump = sdl_context.event_pump().unwrap();
-        let mut last_cycle_time = std::time::Instant::now();
-
-        'running: loop {
-            for event in event_pump.poll_iter() {
-                match event {
-                    Event::Quit { .. }
-                    | Event::KeyDown {
-                        keycode: Some(Keycode::Escape),
-                        ..
-                    } => break 'running,
-                    Event::KeyDown { keycode, .. } => {
-                        let key = self.parse_key(keycode.expect("No key"));
-                        if let Some(key) = key {
-                            self.key_press(key, true);
-                        }
-                    }
-                    Event::KeyUp { keycode, .. } => {
-                        let key = self.parse_key(keycode.expect("No key"));
-                        if let Some(key) = key {
-                            self.key_press(key, false);
-                        }
-                    }
-                    _ => {}
-                };
-            }
-
-            let now = std::time::Instant::now();
-
-            for _ in 0..TICKS_PER_FRAME {
-                let op = self.fetch_instruction();
-
-                // Decode and execute
-                let exec = self.execute_instruction(op);
-            }
-
-            self.tick_timers();
-            self.draw(&mut canvas);
-            //if last_cycle_time + std::time::Duration::from_micros(1) <= now {
-            //    // Fetch
-            //    let op = self.fetch_instruction();
-
-            //    // Decode and execute
-            //    let exec = self.execute_instruction(op);
-
-            //    self.draw(&mut canvas);
+// The builtin database plus the user's, which wins for ROMs in both
+fn load_database(config: &Config) -> Database {
+    let mut database = Database::builtin().clone();
+    let path = match &config.database {
+        Some(path) => path.clone(),
+        None => config.rom_dir.join("database.json"),
+    };
+    if (config.database.is_some() || path.exists())
+        && let Err(err) = database.add_programs_from(&path)
+    {
+        panic!("Failed to load ROM database: {}", err);
+    }
+    database
+}
 
-            //    last_cycle_time = std::time::Instant::now();
-            //}
-            canvas.present();
-        }
+fn describe(info: &RomInfo) -> String {
+    let mut text = info.title.clone();
+    if !info.authors.is_empty() {
+        text += &format!(" by {}", info.authors.join(", "));
+    }
+    if let Some(release) = &info.release {
+        text += &format!(" ({})", release);
     }
+    text
 }
 
 fn main() {
@@ -627,20 +774,45 @@ fn main() {
     //let rom = fs::read("./rom/test_opcode.ch8").expect("Failed to read rom");
 
     let args: Vec<String> = env::args().collect();
-    let rom_name = args.get(1).map(|s| s.as_str()).unwrap_or("TETRIS"); // <-- just the name, no --
-
-    // If you still want to support --TETRIS as a hidden easter egg:
-    let rom_name = rom_name.strip_prefix("--").unwrap_or(rom_name);
-
-    let path = format!("./rom/{}", rom_name); // or .bin, whatever your extension is
-    println!("Loading ROM: {}", path);
+    let config = Config::from_args(&args).unwrap_or_else(|err| panic!("{}", err));
+
+    Logger::init(config.trace.is_some(), config.trace_file.as_deref())
+        .unwrap_or_else(|err| panic!("Failed to start logger: {}", err));
+    chip8.trace = config.trace.clone();
+    if config.trace.is_some() {
+        // So --diff-trace can replay CXNN with the same numbers
+        let seed = RandomState::new().hash_one(0);
+        chip8.seed(seed);
+        log::trace!("{}", trace::header(seed));
+    }
 
-    let rom = fs::read(&path).expect("Failed to read ROM");
+    let database = load_database(&config);
+    if let Some(id) = &config.platform
+        && database.platform(id).is_none()
+    {
+        panic!("Unknown platform: {}", id);
+    }
 
     //chip8.execute_instruction(0x2D21);
 
     //chip8.execute_instruction(0xD123);
 
-    chip8.load_rom(rom.as_slice());
-    chip8.run();
+    let game = config.rom_name.as_ref().map(|name| {
+        start(&mut chip8, &config.rom_dir.join(name), &database, &config)
+            .unwrap_or_else(|err| panic!("{}", err))
+    });
+
+    if let Some(path) = &config.diff_trace {
+        diff_trace(&mut chip8, path);
+        return;
+    }
+    if config.analyze
+        && let Some(game) = &game
+    {
+        analyze(&game.rom, config.dot.as_deref());
+        return;
+    }
+
+    run(&mut chip8, &config, &database, game);
+    log::logger().flush();
 }
@@ -0,0 +1,87 @@
+pub type Rgb = [u8; 3];
+
+// Entries map to the XO-CHIP plane combinations:
+//  0 - background
+//  1 - plane 1
+//  2 - plane 2
+//  3 - both planes
+// Plain CHIP-8 only uses the first two.
+pub const PALETTE_SIZE: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Rgb; PALETTE_SIZE],
+}
+
+impl Palette {
+    fn named(name: &str, colors: [u32; PALETTE_SIZE]) -> Self {
+        Self {
+            name: name.to_string(),
+            colors: colors.map(|c| [(c >> 16) as u8, (c >> 8) as u8, c as u8]),
+        }
+    }
+
+    pub fn classic() -> Self {
+        Self::named("classic", [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555])
+    }
+
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            Self::classic(),
+            Self::named("green", [0x0A1A0A, 0x33FF66, 0x1F9C3D, 0xB3FFC6]),
+            Self::named("amber", [0x1A0F00, 0xFFB000, 0x996A00, 0xFFE0A0]),
+            Self::named("gameboy", [0x9BBC0F, 0x0F380F, 0x8BAC0F, 0x306230]),
+            Self::named("contrast", [0x000000, 0xFFFF00, 0x00FFFF, 0xFFFFFF]),
+        ]
+    }
+
+    // Either a builtin name or 2 to 4 hex colours, e.g. "#000000,#33ff66".
+    // Missing plane colours fall back to the plane 1 colour.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Some(palette) = Self::builtin().into_iter().find(|p| p.name == value) {
+            return Ok(palette);
+        }
+
+        let colors = value
+            .split(',')
+            .map(parse_rgb)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if colors.len() < 2 || colors.len() > PALETTE_SIZE {
+            return Err(format!(
+                "Palette needs 2 to {} colours, got {}",
+                PALETTE_SIZE,
+                colors.len()
+            ));
+        }
+
+        let mut palette = [colors[1]; PALETTE_SIZE];
+        palette[..colors.len()].copy_from_slice(&colors);
+
+        Ok(Self {
+            name: "custom".to_string(),
+            colors: palette,
+        })
+    }
+}
+
+fn parse_rgb(value: &str) -> Result<Rgb, String> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("Invalid colour: {}", value));
+    }
+
+    let rgb = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid colour: {}", value))?;
+    Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+}
+
+// Mix two colours, amount 0.0 gives `from` and 1.0 gives `to`
+pub fn blend(from: Rgb, to: Rgb, amount: f32) -> Rgb {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    [
+        mix(from[0], to[0]),
+        mix(from[1], to[1]),
+        mix(from[2], to[2]),
+    ]
+}
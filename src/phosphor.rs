@@ -0,0 +1,62 @@
+// Pixel persistence for the renderer.
+//
+// Games erase and redraw sprites with XOR, so a sprite is often missing for a
+// whole frame and flickers. Instead of showing the raw screen, every pixel keeps
+// an intensity that is set to full when lit and fades by `decay` each frame after
+// it is cleared. The emulated screen itself is never touched.
+
+pub const DEFAULT_DECAY: f32 = 0.6;
+
+// Most brightness kept per frame. At 1.0 cleared pixels would never fade.
+pub const MAX_DECAY: f32 = 0.99;
+
+// Below this a pixel is treated as fully dark
+const MIN_INTENSITY: f32 = 0.05;
+
+pub struct Phosphor {
+    decay: f32,
+    intensity: Vec<f32>,
+}
+
+impl Phosphor {
+    // decay is the fraction of brightness kept per frame, 0.0 disables persistence
+    pub fn new(decay: f32, size: usize) -> Self {
+        Self {
+            decay: clamp_decay(decay),
+            intensity: vec![0.0; size],
+        }
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = clamp_decay(decay);
+    }
+
+    // Advance one frame and return the intensity of every pixel
+    pub fn update(&mut self, screen: &[bool]) -> &[f32] {
+        if self.intensity.len() != screen.len() {
+            self.intensity = vec![0.0; screen.len()];
+        }
+
+        for (level, lit) in self.intensity.iter_mut().zip(screen) {
+            *level = if *lit { 1.0 } else { *level * self.decay };
+
+            if *level < MIN_INTENSITY {
+                *level = 0.0;
+            }
+        }
+
+        &self.intensity
+    }
+}
+
+fn clamp_decay(decay: f32) -> f32 {
+    if decay.is_nan() {
+        0.0
+    } else {
+        decay.clamp(0.0, MAX_DECAY)
+    }
+}
@@ -0,0 +1,287 @@
+// Profiling: how often each instruction runs, by address and by opcode
+// pattern, and which bytes ran as code and which were read as data by DRW and
+// FX65. Shows which parts of a ROM a run got to and where it spent its time.
+//
+// Set Chip8::profile to start counting. The report has the totals, the
+// opcode patterns and the hottest addresses, as text or JSON. The listing
+// walks the ROM: instructions that ran with their counts, data bytes with
+// their pixels, and the ranges nothing touched.
+
+use crate::disasm::disassemble;
+use crate::instruction::Instruction;
+use crate::{ADDR_MASK, RAM_SIZE};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Addresses in the text report
+const HOTSPOTS: usize = 20;
+
+// Bits of Profile::usage
+const CODE: u8 = 1;
+const DATA: u8 = 2;
+
+#[derive(Clone, Debug)]
+pub struct Profile {
+    // Instructions run
+    pub total: u64,
+    // Runs of the instruction starting at each address
+    hits: Vec<u64>,
+    classes: HashMap<&'static str, u64>,
+    // CODE and DATA for each byte
+    usage: Vec<u8>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// How the bytes of a range were used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coverage {
+    pub bytes: usize,
+    // Ran, possibly read as well
+    pub code: usize,
+    // Read without running
+    pub data: usize,
+    pub unused: usize,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self {
+            total: 0,
+            hits: vec![0; RAM_SIZE],
+            classes: HashMap::new(),
+            usage: vec![0; RAM_SIZE],
+        }
+    }
+
+    // Counts an instruction that ran at pc, with I as it was before
+    pub(crate) fn record(&mut self, pc: u16, instruction: Instruction, i_reg: u16) {
+        self.total += 1;
+        self.hits[pc as usize] += 1;
+        *self.classes.entry(instruction.pattern()).or_default() += 1;
+        self.mark(pc, 2, CODE);
+        match instruction {
+            Instruction::Draw(_, _, n) => self.mark(i_reg, n as u16, DATA),
+            Instruction::Load(x) => self.mark(i_reg, x as u16 + 1, DATA),
+            _ => {}
+        }
+    }
+
+    fn mark(&mut self, addr: u16, len: u16, usage: u8) {
+        for offset in 0..len {
+            self.usage[(addr.wrapping_add(offset) & ADDR_MASK) as usize] |= usage;
+        }
+    }
+
+    // Runs of the instruction starting at addr
+    pub fn hits(&self, addr: u16) -> u64 {
+        self.hits[(addr & ADDR_MASK) as usize]
+    }
+
+    pub fn executed(&self, addr: u16) -> bool {
+        self.usage[(addr & ADDR_MASK) as usize] & CODE != 0
+    }
+
+    pub fn read(&self, addr: u16) -> bool {
+        self.usage[(addr & ADDR_MASK) as usize] & DATA != 0
+    }
+
+    // Opcode patterns with how often they ran, most first
+    pub fn classes(&self) -> Vec<(&'static str, u64)> {
+        let mut classes: Vec<_> = self.classes.iter().map(|(k, v)| (*k, *v)).collect();
+        classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        classes
+    }
+
+    // Addresses that ran with how often, most first
+    pub fn hotspots(&self) -> Vec<(u16, u64)> {
+        let mut hotspots: Vec<_> = (0..RAM_SIZE as u16)
+            .filter(|addr| self.hits(*addr) > 0)
+            .map(|addr| (addr, self.hits(addr)))
+            .collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hotspots
+    }
+
+    pub fn coverage(&self, range: Range<u16>) -> Coverage {
+        let mut coverage = Coverage {
+            bytes: range.len(),
+            code: 0,
+            data: 0,
+            unused: 0,
+        };
+        for addr in range {
+            if self.executed(addr) {
+                coverage.code += 1;
+            } else if self.read(addr) {
+                coverage.data += 1;
+            } else {
+                coverage.unused += 1;
+            }
+        }
+        coverage
+    }
+
+    // Consecutive addresses in range that pass, as first and last
+    fn ranges(&self, range: Range<u16>, pass: impl Fn(u16) -> bool) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for addr in range.filter(|addr| pass(*addr)) {
+            match ranges.last_mut() {
+                Some((_, last)) if *last + 1 == addr => *last = addr,
+                _ => ranges.push((addr, addr)),
+            }
+        }
+        ranges
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.total.max(1) as f64
+    }
+
+    // Totals, opcode patterns and the hottest addresses. rom is where the
+    // program was loaded, for the coverage.
+    pub fn report(&self, memory: &[u8; RAM_SIZE], rom: Range<u16>) -> String {
+        let coverage = self.coverage(rom.clone());
+        let share = |count: usize| count as f64 * 100.0 / coverage.bytes.max(1) as f64;
+        let mut text = format!("{} instructions\n", self.total);
+        text += &format!(
+            "{:03X}-{:03X}: {} bytes ran ({:.1}%), {} read as data ({:.1}%), {} unused ({:.1}%)\n",
+            rom.start,
+            rom.end.saturating_sub(1),
+            coverage.code,
+            share(coverage.code),
+            coverage.data,
+            share(coverage.data),
+            coverage.unused,
+            share(coverage.unused)
+        );
+
+        text += "\nOpcodes\n";
+        for (class, count) in self.classes() {
+            text += &format!(
+                "  {:<8} {:>12} {:>6.1}%\n",
+                class,
+                count,
+                self.percent(count)
+            );
+        }
+
+        text += "\nHotspots\n";
+        for (addr, count) in self.hotspots().into_iter().take(HOTSPOTS) {
+            let op = opcode(memory, addr);
+            text += &format!(
+                "  {:03X} {:04X} {:<16} {:>12} {:>6.1}%\n",
+                addr,
+                op,
+                disassemble(op),
+                count,
+                self.percent(count)
+            );
+        }
+        text
+    }
+
+    // The report with every address that ran, and the code and data ranges
+    pub fn json(&self, memory: &[u8; RAM_SIZE], rom: Range<u16>) -> Value {
+        let coverage = self.coverage(rom.clone());
+        let classes: Vec<Value> = self
+            .classes()
+            .into_iter()
+            .map(|(class, count)| json!({"class": class, "count": count}))
+            .collect();
+        let hotspots: Vec<Value> = self
+            .hotspots()
+            .into_iter()
+            .map(|(addr, count)| {
+                let op = opcode(memory, addr);
+                json!({"addr": addr, "op": op, "instruction": disassemble(op), "count": count})
+            })
+            .collect();
+        json!({
+            "instructions": self.total,
+            "rom": [rom.start, rom.end.saturating_sub(1)],
+            "coverage": {
+                "bytes": coverage.bytes,
+                "code": coverage.code,
+                "data": coverage.data,
+                "unused": coverage.unused,
+            },
+            "classes": classes,
+            "hotspots": hotspots,
+            "code": self.ranges(rom.clone(), |addr| self.executed(addr)),
+            "data": self.ranges(rom, |addr| self.read(addr) && !self.executed(addr)),
+        })
+    }
+
+    // The ROM annotated with what ran and what was read, e.g.
+    //
+    //  200 00E0 CLS                         1
+    //  22A   FF ########                 data
+    //  230-27F                   80 bytes unused
+    pub fn listing(&self, memory: &[u8; RAM_SIZE], rom: Range<u16>) -> String {
+        let mut text = String::new();
+        let mut addr = rom.start;
+        while addr < rom.end {
+            let hits = self.hits(addr);
+            if hits > 0 {
+                let op = opcode(memory, addr);
+                let both = if self.read(addr) || self.read(addr + 1) {
+                    "  (read as data too)"
+                } else {
+                    ""
+                };
+                text += &format!(
+                    "{:03X} {:04X} {:<16} {:>12}{}\n",
+                    addr,
+                    op,
+                    disassemble(op),
+                    hits,
+                    both
+                );
+                addr += 2;
+            } else if self.executed(addr) || self.read(addr) {
+                // Data, or the second half of an instruction that started
+                // on the previous byte
+                let byte = memory[addr as usize];
+                let pixels: String = (0..8)
+                    .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+                    .collect();
+                let usage = if self.read(addr) { "data" } else { "code" };
+                text += &format!("{:03X}   {:02X} {:<16} {:>12}\n", addr, byte, pixels, usage);
+                addr += 1;
+            } else {
+                let start = addr;
+                while addr < rom.end
+                    && self.hits(addr) == 0
+                    && !self.executed(addr)
+                    && !self.read(addr)
+                {
+                    addr += 1;
+                }
+                let span = match addr - start {
+                    1 => format!("{:03X}", start),
+                    _ => format!("{:03X}-{:03X}", start, addr - 1),
+                };
+                text += &format!("{:<25} {} unused\n", span, plural(addr - start, "byte"));
+            }
+        }
+        text
+    }
+}
+
+fn opcode(memory: &[u8; RAM_SIZE], addr: u16) -> u16 {
+    let next = (addr.wrapping_add(1) & ADDR_MASK) as usize;
+    (memory[addr as usize] as u16) << 8 | memory[next] as u16
+}
+
+fn plural(count: u16, word: &str) -> String {
+    match count {
+        1 => format!("1 {}", word),
+        _ => format!("{} {}s", count, word),
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+// Behaviour that differs between CHIP-8 interpreters, named as in the
+// chip-8-database platforms.json. The defaults are what this emulator did
+// before quirks could be chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VX in place instead of putting VY shifted into VX
+    pub shift: bool,
+    // FX55/FX65 increase I by X instead of X + 1
+    pub memory_increment_by_x: bool,
+    // FX55/FX65 leave I unchanged
+    pub memory_leave_i_unchanged: bool,
+    // DXYN wraps sprites around the edges instead of clipping them
+    pub wrap: bool,
+    // BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump: bool,
+    // DXYN waits for the next frame
+    pub vblank: bool,
+    // 8XY1/8XY2/8XY3 reset VF to 0
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: true,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
+
+impl Quirks {
+    // Sets a quirk by its database name
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), String> {
+        let quirk = match name {
+            "shift" => &mut self.shift,
+            "memoryIncrementByX" => &mut self.memory_increment_by_x,
+            "memoryLeaveIUnchanged" => &mut self.memory_leave_i_unchanged,
+            "wrap" => &mut self.wrap,
+            "jump" => &mut self.jump,
+            "vblank" => &mut self.vblank,
+            "logic" => &mut self.logic,
+            _ => return Err(format!("Unknown quirk: {}", name)),
+        };
+        *quirk = value;
+        Ok(())
+    }
+}
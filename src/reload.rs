@@ -0,0 +1,77 @@
+// Hot reloading of a ROM that's being worked on. The file is polled, which
+// works the same everywhere and is cheap at a few times a second.
+
+use crate::{Chip8, RAM_SIZE, START_ADDR};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Modification time and size
+type Stamp = (SystemTime, u64);
+
+pub struct Watcher {
+    path: PathBuf,
+    // Of the version that was loaded last
+    loaded: Option<Stamp>,
+    // Seen on the previous poll
+    seen: Option<Stamp>,
+}
+
+impl Watcher {
+    // Watches for changes after the current version of the file
+    pub fn new(path: &Path) -> Self {
+        let current = stamp(path);
+        Self {
+            path: path.to_path_buf(),
+            loaded: current,
+            seen: current,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The new contents once the file has changed and then stayed the same for
+    // a poll, so a file that is still being written isn't picked up half done
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let current = stamp(&self.path);
+        let previous = std::mem::replace(&mut self.seen, current);
+        if current.is_none() || current == self.loaded || current != previous {
+            return None;
+        }
+
+        let rom = fs::read(&self.path).ok().filter(|rom| !rom.is_empty())?;
+        self.loaded = current;
+        Some(rom)
+    }
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Writes the bytes that differ between two versions of a ROM into the running
+// machine, keeping everything else as it is. Only done when none of them are
+// part of an instruction that has run, otherwise the machine is left alone and
+// this returns false so it can be restarted instead.
+pub fn patch(chip8: &mut Chip8, old: &[u8], new: &[u8]) -> bool {
+    let len = old.len().max(new.len());
+    if START_ADDR as usize + len > RAM_SIZE {
+        return false;
+    }
+
+    let changed: Vec<(u16, u8)> = (0..len)
+        .filter(|idx| old.get(*idx) != new.get(*idx))
+        .map(|idx| (START_ADDR + idx as u16, new.get(idx).copied().unwrap_or(0)))
+        .collect();
+    if changed.iter().any(|(addr, _)| chip8.ran_at(*addr)) {
+        return false;
+    }
+
+    for (addr, value) in changed {
+        chip8.write_memory(addr, &[value]);
+    }
+    true
+}
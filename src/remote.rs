@@ -0,0 +1,585 @@
+// Remote control over a socket, for test scripts and editors. Clients send one
+// JSON object per line and get one back per request, in order:
+//
+//     {"id": 1, "cmd": "read_memory", "addr": 512, "len": 4}
+//     {"id": 1, "ok": true, "data": [18, 0, 0, 224]}
+//
+//     {"cmd": "step", "count": 100}
+//     {"ok": false, "error": "Not paused"}
+//
+// The id is optional and copied into the reply. Addresses and values are
+// numbers or hex strings like "0x200". Commands:
+//
+//     load {path}                    Start a ROM, relative paths are in the ROM directory
+//     reset                          Restart the ROM
+//     pause, resume                  Stop and continue running frames
+//     step {count}                   Run instructions while paused, 1 by default and at
+//                                    most MAX_STEPS, stopping at breakpoints
+//     status                         {paused, pc, cycles, breakpoints}
+//     registers                      {v, i, pc, sp, stack, delay_timer, sound_timer}
+//     set_register {name, value}     v0-vf, i, pc, delay_timer or sound_timer
+//     read_memory {addr, len}        {data}
+//     write_memory {addr, data}
+//     press {key}, release {key}     Keypad keys 0-F
+//     screen                         {width, height, rows}, rows of 0s and 1s
+//     break {addr}, clear {addr}     Add or remove a breakpoint
+//     subscribe {events}             Get events as they happen, all when events is left out
+//     unsubscribe
+//     search {filter, value}         {count}, starts a memory search without a filter,
+//                                    narrows it with equal, changed, increased,
+//                                    decreased or value, see cheat.rs
+//     search_results {limit}         {count, results}, [addr, value] pairs, 100 by default
+//     freeze {addr, value, name}     Keep a byte at a value, saved for the ROM
+//     unfreeze {addr}
+//     cheats                         {cheats}, the frozen bytes
+//
+// Events have no id: {"event": "breakpoint", "pc": 734}, {"event": "sound",
+// "on": true} and {"event": "error", "message": "..."} when the program stops.
+// Hitting a breakpoint pauses.
+//
+// Clients are served on their own threads, but everything they ask for is done
+// by poll() on the emulator's thread, between frames. Replies and events are
+// queued for a writer thread per client, a client that stops reading is dropped
+// once MAX_QUEUED lines are waiting instead of holding up the frames.
+
+use crate::cheat::{Cheat, Cheats, Filter, Search};
+use crate::{Chip8, Error, NUM_KEYS, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+use serde_json::{Map, Value, json};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+
+pub const EVENTS: [&str; 3] = ["breakpoint", "sound", "error"];
+
+// Instructions one step request can run, they run between two frames
+pub const MAX_STEPS: u64 = 100_000;
+
+// Lines waiting to be written to one client
+pub const MAX_QUEUED: usize = 1024;
+
+// Commands the frontend carries out, as it knows how to start a ROM
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Load(PathBuf),
+    Reset,
+}
+
+// A request waiting for the frontend, answered with Remote::finish
+#[derive(Debug)]
+pub struct Pending {
+    client: u64,
+    id: Option<Value>,
+}
+
+enum Message {
+    Connected(u64, SyncSender<String>),
+    Line(u64, String),
+    Closed(u64),
+}
+
+struct Client {
+    lines: SyncSender<String>,
+    events: Vec<String>,
+}
+
+pub struct Remote {
+    addr: String,
+    messages: Receiver<Message>,
+    clients: HashMap<u64, Client>,
+    pub paused: bool,
+    breakpoints: BTreeSet<u16>,
+    sounding: bool,
+    // Where the program was paused, so continuing doesn't stop at the same
+    // breakpoint again
+    paused_at: Option<u16>,
+    search: Option<Search>,
+}
+
+// Reads lines from a client until it disconnects
+fn serve(client: u64, reader: impl Read, messages: Sender<Message>) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        if messages.send(Message::Line(client, line)).is_err() {
+            return;
+        }
+    }
+    let _ = messages.send(Message::Closed(client));
+}
+
+// Writes queued lines to a client until it disconnects or is dropped
+fn write_lines(mut writer: impl Write, lines: Receiver<String>) {
+    for line in lines {
+        if writer.write_all(line.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+// Registers clients and starts serving them
+fn accept<S: Read + Write + Send + 'static>(
+    incoming: impl Iterator<Item = io::Result<S>>,
+    try_clone: fn(&S) -> io::Result<S>,
+    messages: Sender<Message>,
+) {
+    for (client, stream) in (0..).zip(incoming) {
+        let Ok(stream) = stream else { continue };
+        let Ok(writer) = try_clone(&stream) else {
+            continue;
+        };
+        let (lines, queue) = mpsc::sync_channel(MAX_QUEUED);
+        thread::spawn(move || write_lines(writer, queue));
+        if messages.send(Message::Connected(client, lines)).is_err() {
+            return;
+        }
+        let messages = messages.clone();
+        thread::spawn(move || serve(client, stream, messages));
+    }
+}
+
+fn number(value: Option<&Value>, name: &str) -> Result<u64, String> {
+    match value {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(Value::String(text)) => {
+            let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+            match digits {
+                Some(digits) => u64::from_str_radix(digits, 16).ok(),
+                None => text.parse().ok(),
+            }
+        }
+        _ => return Err(format!("Missing {}", name)),
+    }
+    .ok_or_else(|| format!("Invalid {}", name))
+}
+
+fn to_addr(addr: u64) -> Result<u16, String> {
+    if addr >= RAM_SIZE as u64 {
+        return Err(format!("Address {:X} out of range", addr));
+    }
+    Ok(addr as u16)
+}
+
+fn addr(request: &Map<String, Value>) -> Result<u16, String> {
+    to_addr(number(request.get("addr"), "addr")?)
+}
+
+fn key(request: &Map<String, Value>) -> Result<usize, String> {
+    let key = number(request.get("key"), "key")?;
+    if key >= NUM_KEYS as u64 {
+        return Err(format!("No key {:X}", key));
+    }
+    Ok(key as usize)
+}
+
+fn byte(value: u64) -> Result<u8, String> {
+    u8::try_from(value).map_err(|_| format!("{} doesn't fit in a byte", value))
+}
+
+fn bytes(value: Option<&Value>) -> Result<Vec<u8>, String> {
+    let Some(Value::Array(values)) = value else {
+        return Err("Missing data".to_string());
+    };
+    values
+        .iter()
+        .map(|value| match number(Some(value), "data")? {
+            byte @ 0..=0xFF => Ok(byte as u8),
+            _ => Err("Invalid data".to_string()),
+        })
+        .collect()
+}
+
+impl Remote {
+    // Listens on a TCP address like 127.0.0.1:6464, or on a Unix socket given
+    // as unix:PATH
+    pub fn listen(addr: &str) -> io::Result<Self> {
+        let (sender, messages) = mpsc::channel();
+        let addr = match addr.strip_prefix("unix:") {
+            Some(path) => Self::listen_unix(path, sender)?,
+            None => {
+                let listener = TcpListener::bind(addr)?;
+                let local_addr = listener.local_addr()?.to_string();
+                thread::spawn(move || accept(listener.incoming(), TcpStream::try_clone, sender));
+                local_addr
+            }
+        };
+
+        Ok(Self {
+            addr,
+            messages,
+            clients: HashMap::new(),
+            paused: false,
+            breakpoints: BTreeSet::new(),
+            sounding: false,
+            paused_at: None,
+            search: None,
+        })
+    }
+
+    #[cfg(unix)]
+    fn listen_unix(path: &str, sender: Sender<Message>) -> io::Result<String> {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        // Left behind by an earlier run
+        if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        thread::spawn(move || accept(listener.incoming(), UnixStream::try_clone, sender));
+        Ok(format!("unix:{}", path))
+    }
+
+    #[cfg(not(unix))]
+    fn listen_unix(_path: &str, _sender: Sender<Message>) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets aren't supported here",
+        ))
+    }
+
+    // The address clients connect to, with the port picked by the system when
+    // listening on port 0
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    // Handles everything the clients sent since the last call, with the
+    // running ROM's cheats. Loads and resets are left to the frontend, which
+    // answers them with finish.
+    pub fn poll(&mut self, chip8: &mut Chip8, cheats: &mut Cheats) -> Vec<(Action, Pending)> {
+        let mut actions = Vec::new();
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                Message::Connected(client, lines) => {
+                    let client_state = Client {
+                        lines,
+                        events: Vec::new(),
+                    };
+                    self.clients.insert(client, client_state);
+                }
+                Message::Closed(client) => {
+                    self.clients.remove(&client);
+                }
+                Message::Line(client, line) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let request = match serde_json::from_str::<Value>(&line) {
+                        Ok(Value::Object(request)) => request,
+                        Ok(_) => {
+                            self.reply(client, None, Err("Expected an object".to_string()));
+                            continue;
+                        }
+                        Err(err) => {
+                            self.reply(client, None, Err(format!("Invalid JSON: {}", err)));
+                            continue;
+                        }
+                    };
+
+                    let id = request.get("id").cloned();
+                    let pending = || Pending {
+                        client,
+                        id: id.clone(),
+                    };
+                    match request.get("cmd").and_then(Value::as_str) {
+                        Some("load") => match request.get("path").and_then(Value::as_str) {
+                            Some(path) => actions.push((Action::Load(path.into()), pending())),
+                            None => self.reply(client, id, Err("Missing path".to_string())),
+                        },
+                        Some("reset") => actions.push((Action::Reset, pending())),
+                        _ => {
+                            let result = self.handle(client, &request, chip8, cheats);
+                            self.reply(client, id, result);
+                        }
+                    }
+                }
+            }
+        }
+        actions
+    }
+
+    // Answers a request returned by poll
+    pub fn finish(&mut self, pending: Pending, result: Result<(), String>) {
+        if result.is_ok() {
+            self.paused_at = None;
+            self.sounding = false;
+            self.search = None;
+        }
+        self.reply(pending.client, pending.id, result.map(|()| Map::new()));
+    }
+
+    fn handle(
+        &mut self,
+        client: u64,
+        request: &Map<String, Value>,
+        chip8: &mut Chip8,
+        cheats: &mut Cheats,
+    ) -> Result<Map<String, Value>, String> {
+        let mut reply = Map::new();
+        let cmd = request.get("cmd").and_then(Value::as_str);
+        match cmd.ok_or("Missing cmd")? {
+            "pause" => {
+                self.paused = true;
+                self.paused_at = Some(chip8.pc);
+            }
+            "resume" => self.paused = false,
+            "step" => {
+                if !self.paused {
+                    return Err("Not paused".to_string());
+                }
+                let count = match request.get("count") {
+                    Some(count) => number(Some(count), "count")?,
+                    None => 1,
+                };
+                if count > MAX_STEPS {
+                    return Err(format!("count is at most {}", MAX_STEPS));
+                }
+                // The instruction at the PC runs even if there's a breakpoint on
+                // it, as when resuming
+                for ran in 0..count {
+                    if ran > 0 && self.breakpoints.contains(&chip8.pc) {
+                        self.emit("breakpoint", json!({ "pc": chip8.pc }));
+                        break;
+                    }
+                    if let Err(err) = chip8.step() {
+                        self.error(&err);
+                        return Err(err.to_string());
+                    }
+                }
+                self.paused_at = Some(chip8.pc);
+                self.sound_events(chip8);
+                reply.insert("pc".to_string(), json!(chip8.pc));
+            }
+            "status" => {
+                reply.insert("paused".to_string(), json!(self.paused));
+                reply.insert("pc".to_string(), json!(chip8.pc));
+                reply.insert("cycles".to_string(), json!(chip8.cycles));
+                reply.insert("breakpoints".to_string(), json!(self.breakpoints));
+            }
+            "registers" => {
+                reply.insert("v".to_string(), json!(chip8.v_reg));
+                reply.insert("i".to_string(), json!(chip8.i_reg));
+                reply.insert("pc".to_string(), json!(chip8.pc));
+                reply.insert("sp".to_string(), json!(chip8.sp));
+                reply.insert("stack".to_string(), json!(chip8.stack[..chip8.sp as usize]));
+                reply.insert("delay_timer".to_string(), json!(chip8.delay_timer));
+                reply.insert("sound_timer".to_string(), json!(chip8.sound_timer));
+            }
+            "set_register" => {
+                let name = request.get("name").and_then(Value::as_str);
+                let name = name.ok_or("Missing name")?.to_lowercase();
+                let value = number(request.get("value"), "value")?;
+                let byte = || u8::try_from(value).map_err(|_| format!("{} is 8 bits", name));
+                match name.as_str() {
+                    "i" => chip8.i_reg = u16::try_from(value).map_err(|_| "i is 16 bits")?,
+                    "pc" => chip8.pc = to_addr(value)?,
+                    "delay_timer" => chip8.delay_timer = byte()?,
+                    "sound_timer" => chip8.sound_timer = byte()?,
+                    _ => {
+                        let reg = name
+                            .strip_prefix('v')
+                            .and_then(|reg| usize::from_str_radix(reg, 16).ok())
+                            .filter(|reg| *reg < NUM_REGS)
+                            .ok_or_else(|| format!("No register {}", name))?;
+                        chip8.v_reg[reg] = byte()?;
+                    }
+                }
+            }
+            "read_memory" => {
+                let start = addr(request)? as usize;
+                let len = number(request.get("len"), "len")? as usize;
+                let memory = chip8.memory();
+                let data: Vec<u8> = (start..start + len.min(RAM_SIZE))
+                    .map(|addr| memory[addr % RAM_SIZE])
+                    .collect();
+                reply.insert("data".to_string(), json!(data));
+            }
+            "write_memory" => {
+                let start = addr(request)?;
+                chip8.write_memory(start, &bytes(request.get("data"))?);
+            }
+            "press" => chip8.key_press(key(request)?, true),
+            "release" => chip8.key_press(key(request)?, false),
+            "screen" => {
+                let rows: Vec<String> = chip8
+                    .screen
+                    .chunks(SCREEN_WIDTH)
+                    .map(|row| row.iter().map(|lit| if *lit { '1' } else { '0' }).collect())
+                    .collect();
+                reply.insert("width".to_string(), json!(SCREEN_WIDTH));
+                reply.insert("height".to_string(), json!(SCREEN_HEIGHT));
+                reply.insert("rows".to_string(), json!(rows));
+            }
+            "break" => {
+                self.breakpoints.insert(addr(request)?);
+            }
+            "clear" => {
+                self.breakpoints.remove(&addr(request)?);
+            }
+            "subscribe" => {
+                let events = match request.get("events") {
+                    None => EVENTS.iter().map(|event| event.to_string()).collect(),
+                    Some(Value::Array(events)) => events
+                        .iter()
+                        .map(|event| match event.as_str() {
+                            Some(event) if EVENTS.contains(&event) => Ok(event.to_string()),
+                            _ => Err(format!("Unknown event {}", event)),
+                        })
+                        .collect::<Result<_, _>>()?,
+                    Some(_) => return Err("events must be a list".to_string()),
+                };
+                if let Some(client) = self.clients.get_mut(&client) {
+                    client.events = events;
+                }
+            }
+            "unsubscribe" => {
+                if let Some(client) = self.clients.get_mut(&client) {
+                    client.events.clear();
+                }
+            }
+            "search" => {
+                let count = match request.get("filter").and_then(Value::as_str) {
+                    None => {
+                        let search = Search::new(chip8);
+                        let count = search.len();
+                        self.search = Some(search);
+                        count
+                    }
+                    Some(filter) => {
+                        let value = match request.get("value") {
+                            Some(value) => Some(byte(number(Some(value), "value")?)?),
+                            None => None,
+                        };
+                        let filter = Filter::parse(filter, value)?;
+                        let search = self.search.as_mut().ok_or("No search started")?;
+                        search.narrow(chip8, filter)
+                    }
+                };
+                reply.insert("count".to_string(), json!(count));
+            }
+            "search_results" => {
+                let search = self.search.as_ref().ok_or("No search started")?;
+                let limit = match request.get("limit") {
+                    Some(limit) => number(Some(limit), "limit")? as usize,
+                    None => 100,
+                };
+                let results: Vec<(u16, u8)> = search.results().take(limit).collect();
+                reply.insert("count".to_string(), json!(search.len()));
+                reply.insert("results".to_string(), json!(results));
+            }
+            "freeze" => {
+                let value = byte(number(request.get("value"), "value")?)?;
+                let name = request.get("name").and_then(Value::as_str);
+                cheats.freeze(Cheat {
+                    name: name.unwrap_or_default().to_string(),
+                    addr: addr(request)?,
+                    value,
+                })?;
+            }
+            "unfreeze" => {
+                if !cheats.unfreeze(addr(request)?)? {
+                    return Err("Not frozen".to_string());
+                }
+            }
+            "cheats" => {
+                reply.insert("cheats".to_string(), json!(cheats.list));
+            }
+            cmd => return Err(format!("Unknown command {}", cmd)),
+        }
+        Ok(reply)
+    }
+
+    fn reply(
+        &mut self,
+        client: u64,
+        id: Option<Value>,
+        result: Result<Map<String, Value>, String>,
+    ) {
+        let mut reply = Map::new();
+        if let Some(id) = id {
+            reply.insert("id".to_string(), id);
+        }
+        match result {
+            Ok(fields) => {
+                reply.insert("ok".to_string(), json!(true));
+                reply.extend(fields);
+            }
+            Err(err) => {
+                reply.insert("ok".to_string(), json!(false));
+                reply.insert("error".to_string(), json!(err));
+            }
+        }
+        self.send(client, &Value::Object(reply));
+    }
+
+    fn send(&mut self, client: u64, message: &Value) {
+        let Some(state) = self.clients.get_mut(&client) else {
+            return;
+        };
+        // A client that's gone, or too far behind, is dropped. Its reader
+        // thread reports it closed too.
+        let line = format!("{}\n", message);
+        if state.lines.try_send(line).is_err() {
+            self.clients.remove(&client);
+        }
+    }
+
+    fn emit(&mut self, event: &str, fields: Value) {
+        let mut message = json!({ "event": event });
+        if let (Value::Object(message), Value::Object(fields)) = (&mut message, fields) {
+            message.extend(fields);
+        }
+        let clients: Vec<u64> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.events.iter().any(|name| name == event))
+            .map(|(id, _)| *id)
+            .collect();
+        for client in clients {
+            self.send(client, &message);
+        }
+    }
+
+    fn error(&mut self, err: &Error) {
+        self.emit("error", json!({ "message": err.to_string() }));
+    }
+
+    fn sound_events(&mut self, chip8: &Chip8) {
+        let sounding = chip8.sound_timer > 0;
+        if sounding != self.sounding {
+            self.sounding = sounding;
+            self.emit("sound", json!({ "on": sounding }));
+        }
+    }
+
+    // Like Chip8::run_frame, but nothing runs while paused and breakpoints
+    // pause before the instruction at them runs
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Error> {
+        if self.paused {
+            return Ok(());
+        }
+
+        for _ in 0..chip8.tick_rate {
+            let resuming = self.paused_at.take() == Some(chip8.pc);
+            if !resuming && self.breakpoints.contains(&chip8.pc) {
+                self.paused = true;
+                self.paused_at = Some(chip8.pc);
+                self.emit("breakpoint", json!({ "pc": chip8.pc }));
+                return Ok(());
+            }
+            if let Err(err) = chip8.step() {
+                self.error(&err);
+                return Err(err);
+            }
+            if chip8.waiting_for_vblank() {
+                break;
+            }
+        }
+
+        chip8.tick_timers();
+        self.sound_events(chip8);
+        Ok(())
+    }
+}
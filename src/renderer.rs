@@ -0,0 +1,77 @@
+use crate::palette::{self, Palette, Rgb};
+use crate::phosphor::Phosphor;
+
+pub const BYTES_PER_PIXEL: usize = 3;
+
+// Turns the screen into RGB24 pixels, ready to be uploaded to a texture
+pub struct Renderer {
+    pub phosphor: Phosphor,
+    pixels: Vec<u8>,
+}
+
+impl Renderer {
+    pub fn new(decay: f32, size: usize) -> Self {
+        Self {
+            phosphor: Phosphor::new(decay, size),
+            pixels: vec![0; size * BYTES_PER_PIXEL],
+        }
+    }
+
+    pub fn render(&mut self, screen: &[bool], palette: &Palette) -> &[u8] {
+        let [background, foreground, ..] = palette.colors;
+        let intensity = self.phosphor.update(screen);
+
+        self.pixels.resize(screen.len() * BYTES_PER_PIXEL, 0);
+        for (pixel, level) in self.pixels.chunks_exact_mut(BYTES_PER_PIXEL).zip(intensity) {
+            pixel.copy_from_slice(&palette::blend(background, foreground, *level));
+        }
+
+        &self.pixels
+    }
+
+    // Tints pixels of the last rendered frame, for overlays drawn over the
+    // screen
+    pub fn highlight(&mut self, mask: &[bool], colour: Rgb) {
+        for (pixel, _) in self
+            .pixels
+            .chunks_exact_mut(BYTES_PER_PIXEL)
+            .zip(mask)
+            .filter(|(_, highlighted)| **highlighted)
+        {
+            let tinted = palette::blend([pixel[0], pixel[1], pixel[2]], colour, 0.5);
+            pixel.copy_from_slice(&tinted);
+        }
+    }
+
+    // Last rendered frame
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
+
+// Where the screen goes inside a window of the given size, as (x, y, width, height).
+// Keeps the aspect ratio and centres the picture, with integer scaling the picture
+// only grows in whole multiples of the screen size.
+pub fn viewport(
+    window: (u32, u32),
+    screen: (u32, u32),
+    integer_scale: bool,
+) -> (i32, i32, u32, u32) {
+    let (window_w, window_h) = window;
+    let (screen_w, screen_h) = screen;
+
+    let (width, height) = if integer_scale {
+        let scale = (window_w / screen_w).min(window_h / screen_h).max(1);
+        (screen_w * scale, screen_h * scale)
+    } else {
+        let scale = (window_w as f32 / screen_w as f32).min(window_h as f32 / screen_h as f32);
+        (
+            (screen_w as f32 * scale) as u32,
+            (screen_h as f32 * scale) as u32,
+        )
+    };
+
+    let x = (window_w as i32 - width as i32) / 2;
+    let y = (window_h as i32 - height as i32) / 2;
+    (x, y, width, height)
+}
@@ -0,0 +1,347 @@
+// Rhai scripts that run alongside a program, for bots, logging and cheats. The
+// top level of a script runs once when it's loaded and sets up hooks:
+//
+//     on_frame(|| { if peek(0x2F1) > 10 { press(6) } });
+//     on_pc(0x2CA, |pc| print(`missed at frame ${frame()}`));
+//     on_write(0x2F1, |addr, value| print(`score ${value}`));
+//
+// on_frame runs after every frame, on_pc before the instruction at an address
+// runs and on_write after an instruction stores to an address, also when the
+// value stays the same.
+//
+// Scripts can use:
+//
+//     v(x), set_v(x, value)           V registers
+//     i(), set_i(value)               and pc(), set_pc, sp(), delay_timer(),
+//                                     set_delay_timer, sound_timer(), set_sound_timer
+//     peek(addr), poke(addr, value)   Memory
+//     press(key), release(key),       Keypad keys 0-F, held until released
+//     pressed(key)
+//     pixel(x, y)                     Whether a screen pixel is on
+//     frame(), cycles()               Frames run by the script and instructions
+//                                     since the last reset
+//     draw(x, y, width, height)       Highlights a rectangle of screen pixels
+//                                     until the next frame
+//     screenshot(), screenshot(path)  Saved by the frontend after the frame
+//
+// The top level and each hook call run at most MAX_OPERATIONS, a script stuck
+// in a loop fails with Error::Script instead of hanging the frontend.
+//
+// Everything goes through the public Chip8 API. Scripts see the machine the
+// frontend passes to run_frame, it's only theirs while a hook runs.
+
+use crate::{Chip8, NUM_KEYS, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rhai::{AST, Dynamic, Engine, EvalAltResult, FnPtr, INT};
+use std::cell::{Ref, RefCell};
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+// Rhai operations one run of the top level or a hook can take
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Debug)]
+pub enum Error {
+    // The program failed, as with Chip8::run_frame
+    Program(crate::Error),
+    Script(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Program(err) => write!(f, "{}", err),
+            Error::Script(err) => write!(f, "Script: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Box<EvalAltResult>> for Error {
+    fn from(err: Box<EvalAltResult>) -> Self {
+        Error::Script(err.to_string())
+    }
+}
+
+// What the script's functions work on
+struct Context {
+    // The frontend's machine while a hook runs, a spare one otherwise
+    chip8: Chip8,
+    frame: u64,
+    overlay: Vec<bool>,
+    // None for the frontend's usual name
+    screenshots: Vec<Option<String>>,
+    frame_hooks: Vec<FnPtr>,
+    pc_hooks: Vec<(u16, FnPtr)>,
+    write_hooks: Vec<(u16, FnPtr)>,
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    context: Rc<RefCell<Context>>,
+}
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+fn check(value: INT, limit: usize, what: &str) -> Result<usize> {
+    usize::try_from(value)
+        .ok()
+        .filter(|value| *value < limit)
+        .ok_or_else(|| format!("{} {} out of range", what, value).into())
+}
+
+fn byte(value: INT) -> Result<u8> {
+    u8::try_from(value).map_err(|_| format!("{} doesn't fit in a byte", value).into())
+}
+
+// Addresses written by the instruction at the PC, going by the opcode and I
+// before it runs: BCD and register stores
+fn written(chip8: &Chip8) -> std::ops::Range<usize> {
+    let memory = chip8.memory();
+    let pc = chip8.pc as usize;
+    let op = u16::from_be_bytes([memory[pc], memory[(pc + 1) % RAM_SIZE]]);
+    let start = chip8.i_reg as usize;
+    match op & 0xF0FF {
+        0xF033 => start..start + 3,
+        0xF055 => start..start + ((op >> 8) & 0xF) as usize + 1,
+        _ => 0..0,
+    }
+}
+
+impl Script {
+    pub fn load(path: &Path, chip8: &mut Chip8) -> std::result::Result<Self, Error> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| Error::Script(format!("{}: {}", path.display(), err)))?;
+        Self::new(&source, chip8)
+    }
+
+    // Compiles the script and runs its top level with the machine
+    pub fn new(source: &str, chip8: &mut Chip8) -> std::result::Result<Self, Error> {
+        let context = Rc::new(RefCell::new(Context {
+            chip8: Chip8::new(),
+            frame: 0,
+            overlay: vec![false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screenshots: Vec::new(),
+            frame_hooks: Vec::new(),
+            pc_hooks: Vec::new(),
+            write_hooks: Vec::new(),
+        }));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        register(&mut engine, &context);
+        let ast = engine
+            .compile(source)
+            .map_err(|err| Error::Script(err.to_string()))?;
+
+        let script = Self {
+            engine,
+            ast,
+            context,
+        };
+        script.with_chip8(chip8, |script| script.engine.run_ast(&script.ast))?;
+        Ok(script)
+    }
+
+    // Lends the machine to the script for the length of f
+    fn with_chip8<T>(&self, chip8: &mut Chip8, f: impl FnOnce(&Self) -> T) -> T {
+        std::mem::swap(chip8, &mut self.context.borrow_mut().chip8);
+        let result = f(self);
+        std::mem::swap(chip8, &mut self.context.borrow_mut().chip8);
+        result
+    }
+
+    fn call(
+        &self,
+        chip8: &mut Chip8,
+        hooks: Vec<FnPtr>,
+        args: impl Fn() -> Vec<Dynamic>,
+    ) -> Result<()> {
+        if hooks.is_empty() {
+            return Ok(());
+        }
+        self.with_chip8(chip8, |script| {
+            for hook in hooks {
+                // What hooks return is ignored
+                let _ = hook.call::<Dynamic>(&script.engine, &script.ast, args())?;
+            }
+            Ok(())
+        })
+    }
+
+    // Like Chip8::run_frame, with the hooks run along the way
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> std::result::Result<(), Error> {
+        self.context.borrow_mut().overlay.fill(false);
+        for _ in 0..chip8.tick_rate {
+            let pc = chip8.pc;
+            let hooks = self.hooks(|context| &context.pc_hooks, pc);
+            self.call(chip8, hooks, || vec![Dynamic::from(pc as INT)])?;
+
+            let range = written(chip8);
+            chip8.step().map_err(Error::Program)?;
+            for addr in range {
+                let addr = (addr % RAM_SIZE) as u16;
+                let hooks = self.hooks(|context| &context.write_hooks, addr);
+                let value = chip8.memory()[addr as usize];
+                self.call(chip8, hooks, || {
+                    vec![Dynamic::from(addr as INT), Dynamic::from(value as INT)]
+                })?;
+            }
+
+            if chip8.waiting_for_vblank() {
+                break;
+            }
+        }
+        chip8.tick_timers();
+
+        self.context.borrow_mut().frame += 1;
+        let hooks = self.context.borrow().frame_hooks.clone();
+        self.call(chip8, hooks, Vec::new)?;
+        Ok(())
+    }
+
+    fn hooks(&self, list: impl Fn(&Context) -> &Vec<(u16, FnPtr)>, addr: u16) -> Vec<FnPtr> {
+        let context = self.context.borrow();
+        list(&context)
+            .iter()
+            .filter(|(hook_addr, _)| *hook_addr == addr)
+            .map(|(_, hook)| hook.clone())
+            .collect()
+    }
+
+    // Pixels the script highlighted this frame
+    pub fn overlay(&self) -> Ref<'_, [bool]> {
+        Ref::map(self.context.borrow(), |context| context.overlay.as_slice())
+    }
+
+    // Screenshots asked for since the last call, None for the usual name
+    pub fn take_screenshots(&mut self) -> Vec<Option<String>> {
+        std::mem::take(&mut self.context.borrow_mut().screenshots)
+    }
+}
+
+// The functions scripts can call
+fn register(engine: &mut Engine, context: &Rc<RefCell<Context>>) {
+    let shared = || Rc::clone(context);
+
+    let c = shared();
+    engine.register_fn("v", move |reg: INT| -> Result<INT> {
+        Ok(c.borrow().chip8.v_reg[check(reg, NUM_REGS, "Register")?] as INT)
+    });
+    let c = shared();
+    engine.register_fn("set_v", move |reg: INT, value: INT| -> Result<()> {
+        c.borrow_mut().chip8.v_reg[check(reg, NUM_REGS, "Register")?] = byte(value)?;
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("i", move || c.borrow().chip8.i_reg as INT);
+    let c = shared();
+    engine.register_fn("set_i", move |value: INT| -> Result<()> {
+        c.borrow_mut().chip8.i_reg =
+            u16::try_from(value).map_err(|_| format!("I can't be {}", value))?;
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("pc", move || c.borrow().chip8.pc as INT);
+    let c = shared();
+    engine.register_fn("set_pc", move |value: INT| -> Result<()> {
+        c.borrow_mut().chip8.pc = check(value, RAM_SIZE, "Address")? as u16;
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("sp", move || c.borrow().chip8.sp as INT);
+    let c = shared();
+    engine.register_fn("delay_timer", move || c.borrow().chip8.delay_timer as INT);
+    let c = shared();
+    engine.register_fn("set_delay_timer", move |value: INT| -> Result<()> {
+        c.borrow_mut().chip8.delay_timer = byte(value)?;
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("sound_timer", move || c.borrow().chip8.sound_timer as INT);
+    let c = shared();
+    engine.register_fn("set_sound_timer", move |value: INT| -> Result<()> {
+        c.borrow_mut().chip8.sound_timer = byte(value)?;
+        Ok(())
+    });
+
+    let c = shared();
+    engine.register_fn("peek", move |addr: INT| -> Result<INT> {
+        Ok(c.borrow().chip8.memory()[check(addr, RAM_SIZE, "Address")?] as INT)
+    });
+    let c = shared();
+    engine.register_fn("poke", move |addr: INT, value: INT| -> Result<()> {
+        let addr = check(addr, RAM_SIZE, "Address")? as u16;
+        c.borrow_mut().chip8.write_memory(addr, &[byte(value)?]);
+        Ok(())
+    });
+
+    let c = shared();
+    engine.register_fn("press", move |key: INT| -> Result<()> {
+        c.borrow_mut()
+            .chip8
+            .key_press(check(key, NUM_KEYS, "Key")?, true);
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("release", move |key: INT| -> Result<()> {
+        c.borrow_mut()
+            .chip8
+            .key_press(check(key, NUM_KEYS, "Key")?, false);
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("pressed", move |key: INT| -> Result<bool> {
+        Ok(c.borrow().chip8.keys[check(key, NUM_KEYS, "Key")?])
+    });
+
+    let c = shared();
+    engine.register_fn("pixel", move |x: INT, y: INT| -> Result<bool> {
+        let x = check(x, SCREEN_WIDTH, "x")?;
+        let y = check(y, SCREEN_HEIGHT, "y")?;
+        Ok(c.borrow().chip8.screen[y * SCREEN_WIDTH + x])
+    });
+    let c = shared();
+    engine.register_fn("frame", move || c.borrow().frame as INT);
+    let c = shared();
+    engine.register_fn("cycles", move || c.borrow().chip8.cycles as INT);
+
+    // Clipped to the screen
+    let c = shared();
+    engine.register_fn("draw", move |x: INT, y: INT, width: INT, height: INT| {
+        let clip = |start: INT, size: INT, limit: usize| {
+            start.clamp(0, limit as INT) as usize
+                ..start.saturating_add(size).clamp(0, limit as INT) as usize
+        };
+        let overlay = &mut c.borrow_mut().overlay;
+        for row in clip(y, height, SCREEN_HEIGHT) {
+            for column in clip(x, width, SCREEN_WIDTH) {
+                overlay[row * SCREEN_WIDTH + column] = true;
+            }
+        }
+    });
+    let c = shared();
+    engine.register_fn("screenshot", move || c.borrow_mut().screenshots.push(None));
+    let c = shared();
+    engine.register_fn("screenshot", move |path: &str| {
+        c.borrow_mut().screenshots.push(Some(path.to_string()))
+    });
+
+    let c = shared();
+    engine.register_fn("on_frame", move |hook: FnPtr| {
+        c.borrow_mut().frame_hooks.push(hook)
+    });
+    let c = shared();
+    engine.register_fn("on_pc", move |addr: INT, hook: FnPtr| -> Result<()> {
+        let addr = check(addr, RAM_SIZE, "Address")? as u16;
+        c.borrow_mut().pc_hooks.push((addr, hook));
+        Ok(())
+    });
+    let c = shared();
+    engine.register_fn("on_write", move |addr: INT, hook: FnPtr| -> Result<()> {
+        let addr = check(addr, RAM_SIZE, "Address")? as u16;
+        c.borrow_mut().write_hooks.push((addr, hook));
+        Ok(())
+    });
+}
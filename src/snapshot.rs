@@ -0,0 +1,165 @@
+// Save states: a copy of everything a program can see or change, to go back to
+// later. Chip8::snapshot takes one and Chip8::restore goes back to it. The
+// trace isn't part of it.
+//
+// to_bytes always gives SIZE bytes, starting with a version so states from
+// another layout are refused instead of misread. Numbers are little-endian.
+
+use crate::quirks::Quirks;
+use crate::{NUM_KEYS, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE};
+
+const MAGIC: &[u8; 4] = b"C8S1";
+
+const SCREEN_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+pub const SIZE: usize = MAGIC.len()
+    + RAM_SIZE
+    + NUM_REGS
+    + 2 * 3 // I, SP, PC
+    + 2 * STACK_SIZE
+    + 2 // timers
+    + NUM_KEYS
+    + SCREEN_SIZE
+    + 2 // opcode
+    + 8 // cycles
+    + 1 // quirks
+    + 2 // tick rate
+    + 8; // random numbers
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub(crate) memory: [u8; RAM_SIZE],
+    pub(crate) v_reg: [u8; NUM_REGS],
+    pub(crate) i_reg: u16,
+    pub(crate) sp: u16,
+    pub(crate) pc: u16,
+    pub(crate) stack: [u16; STACK_SIZE],
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) keys: [bool; NUM_KEYS],
+    pub(crate) screen: [bool; SCREEN_SIZE],
+    pub(crate) opcode: u16,
+    pub(crate) cycles: u64,
+    pub(crate) quirks: Quirks,
+    pub(crate) tick_rate: u16,
+    pub(crate) rng: u64,
+}
+
+// Quirks packed into one byte, in this order
+fn quirk_flags(quirks: &mut Quirks) -> [&mut bool; 7] {
+    [
+        &mut quirks.shift,
+        &mut quirks.memory_increment_by_x,
+        &mut quirks.memory_leave_i_unchanged,
+        &mut quirks.wrap,
+        &mut quirks.jump,
+        &mut quirks.vblank,
+        &mut quirks.logic,
+    ]
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.memory);
+        bytes.extend_from_slice(&self.v_reg);
+        for value in [self.i_reg, self.sp, self.pc] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.stack {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[self.delay_timer, self.sound_timer]);
+        bytes.extend(self.keys.iter().map(|key| *key as u8));
+        bytes.extend(self.screen.iter().map(|pixel| *pixel as u8));
+        bytes.extend_from_slice(&self.opcode.to_le_bytes());
+        bytes.extend_from_slice(&self.cycles.to_le_bytes());
+
+        let mut quirks = self.quirks;
+        let flags = quirk_flags(&mut quirks)
+            .iter()
+            .enumerate()
+            .fold(0, |flags, (bit, quirk)| flags | (**quirk as u8) << bit);
+        bytes.push(flags);
+
+        bytes.extend_from_slice(&self.tick_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.rng.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() != SIZE {
+            return Err(format!(
+                "Save state is {} bytes, expected {}",
+                bytes.len(),
+                SIZE
+            ));
+        }
+        if &bytes[..MAGIC.len()] != MAGIC {
+            return Err("Not a save state of this version".to_string());
+        }
+
+        let mut rest = &bytes[MAGIC.len()..];
+        let mut take = |count: usize| {
+            let (taken, left) = rest.split_at(count);
+            rest = left;
+            taken
+        };
+        let mut snapshot = Self {
+            memory: take(RAM_SIZE).try_into().unwrap(),
+            v_reg: take(NUM_REGS).try_into().unwrap(),
+            i_reg: u16::from_le_bytes(take(2).try_into().unwrap()),
+            sp: u16::from_le_bytes(take(2).try_into().unwrap()),
+            pc: u16::from_le_bytes(take(2).try_into().unwrap()),
+            stack: [0; STACK_SIZE],
+            delay_timer: 0,
+            sound_timer: 0,
+            keys: [false; NUM_KEYS],
+            screen: [false; SCREEN_SIZE],
+            opcode: 0,
+            cycles: 0,
+            quirks: Quirks::default(),
+            tick_rate: 0,
+            rng: 0,
+        };
+        for value in snapshot.stack.iter_mut() {
+            *value = u16::from_le_bytes(take(2).try_into().unwrap());
+        }
+        snapshot.delay_timer = take(1)[0];
+        snapshot.sound_timer = take(1)[0];
+        for (key, byte) in snapshot.keys.iter_mut().zip(take(NUM_KEYS)) {
+            *key = *byte != 0;
+        }
+        for (pixel, byte) in snapshot.screen.iter_mut().zip(take(SCREEN_SIZE)) {
+            *pixel = *byte != 0;
+        }
+        snapshot.opcode = u16::from_le_bytes(take(2).try_into().unwrap());
+        snapshot.cycles = u64::from_le_bytes(take(8).try_into().unwrap());
+
+        let flags = take(1)[0];
+        for (bit, quirk) in quirk_flags(&mut snapshot.quirks).into_iter().enumerate() {
+            *quirk = flags & (1 << bit) != 0;
+        }
+
+        snapshot.tick_rate = u16::from_le_bytes(take(2).try_into().unwrap());
+        snapshot.rng = u64::from_le_bytes(take(8).try_into().unwrap());
+
+        // None of these can come from a running machine
+        if snapshot.sp as usize > STACK_SIZE {
+            return Err(format!("Stack pointer {} out of range", snapshot.sp));
+        }
+        if snapshot.pc as usize >= RAM_SIZE {
+            return Err(format!("PC {:X} out of range", snapshot.pc));
+        }
+        // RET jumps to these
+        let sp = snapshot.sp as usize;
+        if let Some(addr) = snapshot.stack[..sp]
+            .iter()
+            .find(|addr| **addr as usize >= RAM_SIZE)
+        {
+            return Err(format!("Return address {:X} out of range", addr));
+        }
+        Ok(snapshot)
+    }
+}
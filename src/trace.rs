@@ -0,0 +1,80 @@
+use crate::Chip8;
+use crate::disasm::disassemble;
+use std::ops::RangeInclusive;
+
+// One line per instruction, all numbers in hex except the cycle:
+//
+//  compact: CYCLE PC OPCODE MNEMONIC I=... SP=... <changed registers>
+//  full:    CYCLE PC OPCODE MNEMONIC I=... SP=... V0=.. V1=.. ... VF=..
+//
+// Registers are shown after the instruction executed. Compact lists only the
+// V registers the instruction changed, full dumps all of them which is the
+// easiest to line up with traces from other emulators. CXNN lines end with
+// RND=.. in both, the number it stored, so a replay can use the same one.
+//
+// A trace starts with the seed of the run, see header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    Compact,
+    Full,
+}
+
+impl TraceFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "compact" => Ok(Self::Compact),
+            "full" => Ok(Self::Full),
+            _ => Err(format!("Unknown trace format: {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    pub format: TraceFormat,
+    // Only instructions at these addresses are traced
+    pub range: RangeInclusive<u16>,
+}
+
+impl Trace {
+    // `before` holds the V registers from before the instruction ran
+    pub fn line(&self, chip8: &Chip8, pc: u16, op: u16, before: &[u8]) -> String {
+        let mut line = format!(
+            "{:08} {:03X} {:04X} {:<16} I={:03X} SP={:X}",
+            chip8.cycles,
+            pc,
+            op,
+            disassemble(op),
+            chip8.i_reg,
+            chip8.sp
+        );
+
+        for (reg, (old, new)) in before.iter().zip(&chip8.v_reg).enumerate() {
+            if self.format == TraceFormat::Full || old != new {
+                line.push_str(&format!(" V{:X}={:02X}", reg, new));
+            }
+        }
+
+        if op >> 12 == 0xC {
+            let x = ((op >> 8) & 0xF) as usize;
+            line.push_str(&format!(" RND={:02X}", chip8.v_reg[x]));
+        }
+
+        line
+    }
+}
+
+// First line of a trace, the seed CXNN numbers come from. Replaying with it
+// gives the same numbers even for instructions outside the traced range.
+pub fn header(seed: u64) -> String {
+    format!("# seed={:016X}", seed)
+}
+
+// Parses a hex address range like 200-2FF
+pub fn parse_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+    let invalid = || format!("Invalid address range: {}", value);
+    let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+    let start = u16::from_str_radix(start.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+    let end = u16::from_str_radix(end.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+    Ok(start..=end)
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use chip8_rs::Chip8;
+use chip8_rs::analysis::{self, Edge, EdgeKind, Kind, Region};
+use chip8_rs::database::Database;
+use chip8_rs::profile::Profile;
+use common::rom;
+
+// CALL 208; LD I, 210; DRW V0, V0, 1; JP 206
+// 208: SE V0, 00; JP V0, 300; RET
+// 20E: JP 200, which nothing reaches, then a sprite
+const PROGRAM: [u8; 18] = [
+    0x22, 0x08, 0xA2, 0x10, 0xD0, 0x01, 0x12, 0x06, 0x30, 0x00, 0xB3, 0x00, 0x00, 0xEE, 0x12, 0x00,
+    0x3C, 0x3C,
+];
+
+#[test]
+fn graph() {
+    let cfg = analysis::analyze(&PROGRAM).unwrap();
+    let edge = |kind, to| Edge { kind, to };
+    let blocks: Vec<(u16, u16, Vec<Edge>)> = cfg
+        .blocks
+        .values()
+        .map(|block| (block.start, block.end, block.edges.clone()))
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (
+                0x200,
+                0x202,
+                vec![edge(EdgeKind::Call, 0x208), edge(EdgeKind::Next, 0x202)]
+            ),
+            (0x202, 0x206, vec![edge(EdgeKind::Next, 0x206)]),
+            (0x206, 0x208, vec![edge(EdgeKind::Jump, 0x206)]),
+            (
+                0x208,
+                0x20A,
+                vec![edge(EdgeKind::Next, 0x20A), edge(EdgeKind::Skip, 0x20C)]
+            ),
+            // Where V0 sends it isn't known
+            (0x20A, 0x20C, vec![]),
+            (0x20C, 0x20E, vec![]),
+        ]
+    );
+    assert_eq!(cfg.block(0x204).unwrap().start, 0x202);
+
+    assert_eq!(cfg.subroutines.len(), 1);
+    assert_eq!(cfg.subroutines[0].entry, 0x208);
+    assert_eq!(cfg.subroutines[0].blocks, [0x208, 0x20A, 0x20C]);
+    assert!(cfg.subroutines[0].returns);
+    assert_eq!(cfg.unresolved, [0x20A]);
+    assert!(cfg.invalid.is_empty());
+
+    assert_eq!(
+        cfg.regions,
+        [
+            Region {
+                kind: Kind::Code,
+                range: 0x200..0x20E
+            },
+            Region {
+                kind: Kind::Unreachable,
+                range: 0x20E..0x210
+            },
+            Region {
+                kind: Kind::Data,
+                range: 0x210..0x212
+            },
+        ]
+    );
+    assert_eq!(cfg.kind(0x211), Some(Kind::Data));
+    assert_eq!(cfg.kind(0x212), None);
+}
+
+#[test]
+fn output() {
+    let cfg = analysis::analyze(&PROGRAM).unwrap();
+    let summary = cfg.summary();
+    assert!(summary.contains("Subroutine 208: 3 blocks\n"));
+    assert!(summary.contains("Unresolved 20A: JP V0, 300\n"));
+    assert!(summary.ends_with("200-20D code\n20E-20F unreachable\n210-211 data\n"));
+
+    let listing = cfg.listing();
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(
+        lines[4..],
+        [
+            "sub 208:",
+            "208 3000 SE V0, 00",
+            "20A B300 JP V0, 300       unresolved",
+            "20C 00EE RET",
+            "20E 1200 JP 200           unreachable",
+            "210   3C ..####..         data",
+            "211   3C ..####..         data",
+        ]
+    );
+
+    let dot = cfg.dot();
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("    b208 [label=\"sub 208\\l208 SE V0, 00\\l\", style=bold];\n"));
+    assert!(dot.contains("    b20A [label=\"20A JP V0, 300\\l\", color=red];\n"));
+    assert!(dot.contains("    b200 -> b208 [label=\"call\", style=dashed];\n"));
+    assert!(dot.contains("    b208 -> b20C [label=\"skip\"];\n"));
+    assert!(dot.contains("    b206 -> b206 [label=\"jump\"];\n"));
+}
+
+#[test]
+fn errors() {
+    // Runs into 0xFFFF
+    let cfg = analysis::analyze(&[0x60, 0x01, 0xFF, 0xFF]).unwrap();
+    assert_eq!(cfg.invalid, [0x202]);
+    assert!(cfg.summary().contains("Invalid opcode 202: FFFF\n"));
+
+    assert!(analysis::analyze(&[0; 4096]).is_err());
+}
+
+// Everything BRIX runs is code and everything it draws is data
+#[test]
+fn matches_a_run() {
+    let program = rom("BRIX");
+    let cfg = analysis::analyze(&program).unwrap();
+    assert_eq!(cfg.subroutines.len(), 1);
+    assert!(cfg.unresolved.is_empty());
+
+    let mut chip8 = Chip8::new();
+    chip8.seed(1);
+    chip8.load_rom(&program).unwrap();
+    Database::builtin()
+        .lookup(&program)
+        .unwrap()
+        .apply(&mut chip8);
+    chip8.profile = Some(Profile::new());
+    for frame in 0..3000 {
+        chip8.key_press(if frame % 200 < 100 { 4 } else { 6 }, frame % 7 != 0);
+        chip8.run_frame().unwrap();
+    }
+    let profile = chip8.profile.unwrap();
+    for addr in 0x200..0x200 + program.len() as u16 {
+        let kind = cfg.kind(addr).unwrap();
+        assert!(
+            !profile.executed(addr) || kind == Kind::Code,
+            "{:03X}",
+            addr
+        );
+        assert!(!profile.read(addr) || kind == Kind::Data, "{:03X}", addr);
+    }
+}
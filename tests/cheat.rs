@@ -0,0 +1,106 @@
+use chip8_rs::Chip8;
+use chip8_rs::cheat::{Cheat, Cheats, Filter, Search};
+use chip8_rs::database::sha1_hex;
+use std::fs;
+
+// Counts loops at 300 and takes a life at 301 while 5 is held:
+// LD I, 300; LD V1, [I]; ADD V0, 1; LD V2, 5; SKNP V2; ADD V1, FF;
+// LD [I], V1; JP 200
+const GAME: [u8; 16] = [
+    0xA3, 0x00, 0xF1, 0x65, 0x70, 0x01, 0x62, 0x05, 0xE2, 0xA1, 0x71, 0xFF, 0xF1, 0x55, 0x12, 0x00,
+];
+
+fn start() -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&GAME).unwrap();
+    chip8.write_memory(0x301, &[3]);
+    chip8
+}
+
+// Once through the loop, back at 200
+fn play(chip8: &mut Chip8) {
+    chip8.step().unwrap();
+    while chip8.pc != 0x200 {
+        chip8.step().unwrap();
+    }
+}
+
+#[test]
+fn search() {
+    let mut chip8 = start();
+    let mut search = Search::new(&chip8);
+    assert_eq!(search.len(), 4096);
+    play(&mut chip8);
+    assert_eq!(search.narrow(&chip8, Filter::Changed), 1);
+    assert_eq!(search.results().collect::<Vec<_>>(), [(0x300, 1)]);
+
+    // Looking for the lives instead
+    let mut search = Search::new(&chip8);
+    play(&mut chip8);
+    assert_eq!(search.narrow(&chip8, Filter::Equal), 4095);
+    chip8.key_press(5, true);
+    play(&mut chip8);
+    chip8.key_press(5, false);
+    assert_eq!(search.narrow(&chip8, Filter::Decreased), 1);
+    play(&mut chip8);
+    assert_eq!(search.narrow(&chip8, Filter::Value(2)), 1);
+    assert_eq!(search.results().collect::<Vec<_>>(), [(0x301, 2)]);
+    assert_eq!(search.narrow(&chip8, Filter::Increased), 0);
+    assert!(search.is_empty());
+
+    assert_eq!(Filter::parse("value", Some(7)), Ok(Filter::Value(7)));
+    assert!(Filter::parse("value", None).is_err());
+    assert!(Filter::parse("bigger", None).is_err());
+}
+
+#[test]
+fn freeze_and_save() {
+    let dir = std::env::temp_dir().join(format!("chip8-rs-{}-cheats", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let mut cheats = Cheats::load(&dir, &GAME).unwrap();
+    assert!(cheats.list.is_empty());
+
+    let lives = |value| Cheat {
+        name: "lives".to_string(),
+        addr: 0x301,
+        value,
+    };
+    cheats.freeze(lives(9)).unwrap();
+    cheats.freeze(lives(5)).unwrap();
+    assert_eq!(cheats.list, [lives(5)]);
+    assert!(
+        cheats
+            .freeze(Cheat {
+                addr: 0x1000,
+                ..lives(1)
+            })
+            .is_err()
+    );
+
+    // The lives stay put however long 5 is held
+    let mut chip8 = start();
+    chip8.key_press(5, true);
+    for _ in 0..3 {
+        cheats.apply(&mut chip8);
+        play(&mut chip8);
+        assert_eq!(chip8.memory()[0x301], 4);
+    }
+
+    // Saved under the ROM's hash, names are optional
+    let path = Cheats::path(&dir, &GAME);
+    assert_eq!(path, dir.join(format!("{}.json", sha1_hex(&GAME))));
+    assert_eq!(Cheats::load(&dir, &GAME).unwrap(), cheats);
+    fs::write(&path, r#"[{"addr": 768, "value": 1}]"#).unwrap();
+    let loaded = Cheats::load(&dir, &GAME).unwrap();
+    assert_eq!(loaded.list[0].name, "");
+
+    assert!(cheats.unfreeze(0x301).unwrap());
+    assert!(!cheats.unfreeze(0x301).unwrap());
+    assert!(Cheats::load(&dir, &GAME).unwrap().list.is_empty());
+
+    for bad in ["{", r#"[{"addr": 4096, "value": 1}]"#] {
+        fs::write(&path, bad).unwrap();
+        assert!(Cheats::load(&dir, &GAME).is_err(), "{}", bad);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
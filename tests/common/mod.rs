@@ -0,0 +1,51 @@
+// Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+pub mod reference;
+
+use std::{fs, path::PathBuf};
+
+pub fn rom(name: &str) -> Vec<u8> {
+    fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("rom")
+            .join(name),
+    )
+    .unwrap()
+}
+
+// Every ROM in rom/, sorted by name
+pub fn bundled_roms() -> Vec<String> {
+    let mut names: Vec<String> =
+        fs::read_dir(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("rom"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+    names.sort();
+    names
+}
+
+// The same 256 byte ROMs on every run, from a small xorshift
+pub fn random_roms(count: usize) -> Vec<Vec<u8>> {
+    let mut seed = 0x2545_F491_u32;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+
+    (0..count)
+        .map(|_| (0..256).map(|_| next() as u8).collect())
+        .collect()
+}
+
+// Scripted input: every key in turn, held for a few frames
+pub fn keys_at(frame: u32) -> [bool; 16] {
+    let mut keys = [false; 16];
+    if frame % 16 < 8 {
+        keys[(frame / 16 % 16) as usize] = true;
+    }
+    keys
+}
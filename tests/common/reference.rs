@@ -0,0 +1,214 @@
+// A deliberately plain CHIP-8 interpreter to check the real one against. It
+// follows the same behaviour as the core (shifts work on VX in place, FX55 and
+// FX65 leave I alone, addresses wrap at 4 KiB) but shares no code with it, so a
+// bug has to be made twice to go unnoticed.
+
+use chip8_rs::diff::State;
+
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+pub struct Reference {
+    pub ram: Vec<u8>,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: [u16; 16],
+    pub sp: u16,
+    pub dt: u8,
+    pub st: u8,
+    pub keys: [bool; 16],
+    pub display: Vec<bool>,
+    pub cycles: u64,
+}
+
+impl Reference {
+    pub fn new(rom: &[u8]) -> Self {
+        let mut ram = vec![0; 4096];
+        ram[..FONT.len()].copy_from_slice(&FONT);
+        ram[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+        Self {
+            ram,
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: [0; 16],
+            sp: 0,
+            dt: 0,
+            st: 0,
+            keys: [false; 16],
+            display: vec![false; 64 * 32],
+            cycles: 0,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        State {
+            pc: self.pc,
+            v_reg: self.v,
+            i_reg: self.i,
+            sp: self.sp,
+            stack: self.stack,
+            delay_timer: self.dt,
+            sound_timer: self.st,
+            memory: self.ram.clone(),
+            screen: self.display.clone(),
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.ram[(addr % 4096) as usize]
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        self.ram[(addr % 4096) as usize] = value;
+    }
+
+    // Runs one instruction, false when it can't be executed. Timers tick every
+    // ten instructions, like run_frame.
+    pub fn step(&mut self) -> bool {
+        let here = self.pc;
+        let op = (self.peek(here) as u16) << 8 | self.peek(here + 1) as u16;
+        self.pc = (here + 2) % 4096;
+
+        let x = ((op >> 8) & 0xF) as usize;
+        let y = ((op >> 4) & 0xF) as usize;
+        let n = op & 0xF;
+        let nn = (op & 0xFF) as u8;
+        let nnn = op & 0xFFF;
+
+        match op >> 12 {
+            0x0 if op == 0x00E0 => self.display.fill(false),
+            0x0 if op == 0x00EE => {
+                if self.sp == 0 {
+                    return false;
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+                self.stack[self.sp as usize] = 0;
+            }
+            0x0 if op == 0x0000 => {}
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.sp == 16 {
+                    return false;
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+            0x3 if self.v[x] == nn => self.pc = (self.pc + 2) % 4096,
+            0x3 => {}
+            0x4 if self.v[x] != nn => self.pc = (self.pc + 2) % 4096,
+            0x4 => {}
+            0x5 if n == 0 => {
+                if self.v[x] == self.v[y] {
+                    self.pc = (self.pc + 2) % 4096;
+                }
+            }
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => {
+                let (vx, vy) = (self.v[x], self.v[y]);
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, None),
+                    0x2 => (vx & vy, None),
+                    0x3 => (vx ^ vy, None),
+                    0x4 => (
+                        vx.wrapping_add(vy),
+                        Some((vx as u16 + vy as u16 > 255) as u8),
+                    ),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x6 => (vx >> 1, Some(vx & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0xE => (vx << 1, Some(vx >> 7)),
+                    _ => return false,
+                };
+                self.v[x] = result;
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+            }
+            0x9 if n == 0 => {
+                if self.v[x] != self.v[y] {
+                    self.pc = (self.pc + 2) % 4096;
+                }
+            }
+            0xA => self.i = nnn,
+            0xB => self.pc = (nnn + self.v[0] as u16) % 4096,
+            // The random byte is copied over from the emulator under test
+            0xC => {}
+            0xD => {
+                self.v[0xF] = 0;
+                for row in 0..n {
+                    let bits = self.peek(self.i.wrapping_add(row));
+                    for col in 0..8 {
+                        if bits & (0x80 >> col) == 0 {
+                            continue;
+                        }
+                        let px = (self.v[x] as usize + col) % 64;
+                        let py = (self.v[y] as usize + row as usize) % 32;
+                        let pixel = &mut self.display[py * 64 + px];
+                        if *pixel {
+                            self.v[0xF] = 1;
+                        }
+                        *pixel = !*pixel;
+                    }
+                }
+            }
+            0xE if nn == 0x9E => {
+                if self.keys[(self.v[x] & 0xF) as usize] {
+                    self.pc = (self.pc + 2) % 4096;
+                }
+            }
+            0xE if nn == 0xA1 => {
+                if !self.keys[(self.v[x] & 0xF) as usize] {
+                    self.pc = (self.pc + 2) % 4096;
+                }
+            }
+            0xF => match nn {
+                0x07 => self.v[x] = self.dt,
+                0x0A => match self.keys.iter().position(|pressed| *pressed) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => self.pc = here,
+                },
+                0x15 => self.dt = self.v[x],
+                0x18 => self.st = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = self.v[x] as u16 * 5,
+                0x33 => {
+                    let value = self.v[x];
+                    self.poke(self.i, value / 100);
+                    self.poke(self.i.wrapping_add(1), value / 10 % 10);
+                    self.poke(self.i.wrapping_add(2), value % 10);
+                }
+                0x55 => {
+                    for reg in 0..=x {
+                        self.poke(self.i.wrapping_add(reg as u16), self.v[reg]);
+                    }
+                }
+                0x65 => {
+                    for reg in 0..=x {
+                        self.v[reg] = self.peek(self.i.wrapping_add(reg as u16));
+                    }
+                }
+                _ => return false,
+            },
+            _ => return false,
+        }
+
+        self.cycles += 1;
+        if self.cycles.is_multiple_of(10) {
+            self.dt = self.dt.saturating_sub(1);
+            self.st = self.st.saturating_sub(1);
+        }
+        true
+    }
+}
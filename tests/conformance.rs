@@ -0,0 +1,130 @@
+// Runs test ROMs headlessly and compares the final screen to a golden bitmap
+// in tests/golden, drawn with '#' for lit pixels and '.' for dark ones.
+//
+// Run with UPDATE_GOLDEN=1 to write the goldens from the current output.
+//
+// ROMs are looked up in rom/ and tests/roms/. The Timendus test suite ROMs
+// (flags, quirks, keypad) are not bundled, their cases are ignored until they
+// are put in tests/roms/, see tests/roms/README.md.
+
+use chip8_rs::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::{env, fs, path::PathBuf};
+
+// Scripted keypad input: (frame, key, pressed)
+type Input = (u32, usize, bool);
+
+fn find_rom(name: &str) -> PathBuf {
+    ["rom", "tests/roms"]
+        .iter()
+        .map(|dir| {
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join(dir)
+                .join(name)
+        })
+        .find(|path| path.exists())
+        .unwrap_or_else(|| panic!("ROM not found: {}", name))
+}
+
+fn run_rom(name: &str, frames: u32, input: &[Input]) -> Chip8 {
+    let rom = fs::read(find_rom(name)).unwrap();
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&rom).unwrap();
+
+    for frame in 0..frames {
+        for (_, key, pressed) in input.iter().filter(|(at, _, _)| *at == frame) {
+            chip8.key_press(*key, *pressed);
+        }
+        chip8.run_frame().unwrap();
+    }
+
+    chip8
+}
+
+fn bitmap(chip8: &Chip8) -> String {
+    let mut bitmap = String::new();
+    for row in chip8.screen.chunks(SCREEN_WIDTH).take(SCREEN_HEIGHT) {
+        bitmap.extend(row.iter().map(|lit| if *lit { '#' } else { '.' }));
+        bitmap.push('\n');
+    }
+    bitmap
+}
+
+fn check(golden: &str, rom: &str, frames: u32, input: &[Input]) {
+    let actual = bitmap(&run_rom(rom, frames, input));
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", golden));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("{}: {} (run with UPDATE_GOLDEN=1)", path.display(), err));
+    assert!(
+        actual == expected,
+        "{} after {} frames does not match {}\n\nexpected:\n{}\nactual:\n{}",
+        rom,
+        frames,
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn ibm_logo() {
+    check("ibm_logo", "ibm.ch8", 60, &[]);
+}
+
+#[test]
+fn corax_opcode_test() {
+    // Shows OK or ERR next to each opcode group
+    check("corax_opcode_test", "test_opcode.ch8", 100, &[]);
+}
+
+#[test]
+fn keypad_kaleidoscope() {
+    // Not the Timendus keypad test, only checks scripted input reaches a
+    // ROM. KALEID mirrors whatever is drawn with 2/4/6/8 into all four
+    // quadrants
+    let input = [
+        (20, 6, true),
+        (40, 6, false),
+        (45, 8, true),
+        (60, 8, false),
+        (65, 4, true),
+        (75, 4, false),
+        (80, 2, true),
+        (90, 2, false),
+    ];
+    check("keypad_kaleidoscope", "KALEID", 150, &input);
+}
+
+// The Timendus cases, run with --ignored once the ROMs are in tests/roms/.
+// Check each golden against the suite's documented result screen before
+// committing it, every test on it should show a checkmark.
+
+#[test]
+#[ignore = "needs tests/roms/4-flags.ch8 from the Timendus test suite"]
+fn timendus_flags() {
+    check("timendus_flags", "4-flags.ch8", 120, &[]);
+}
+
+#[test]
+#[ignore = "needs tests/roms/5-quirks.ch8 from the Timendus test suite"]
+fn timendus_quirks() {
+    // 1 picks CHIP-8 from the platform menu
+    let input = [(10, 1, true), (15, 1, false)];
+    check("timendus_quirks", "5-quirks.ch8", 600, &input);
+}
+
+#[test]
+#[ignore = "needs tests/roms/6-keypad.ch8 from the Timendus test suite"]
+fn timendus_keypad() {
+    // 3 picks the FX0A test, which then waits for a key to be pressed and
+    // released
+    let input = [(10, 3, true), (15, 3, false), (30, 5, true), (35, 5, false)];
+    check("timendus_keypad", "6-keypad.ch8", 60, &input);
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use chip8_rs::database::{self, Database};
+use chip8_rs::quirks::Quirks;
+use chip8_rs::{Chip8, TICKS_PER_FRAME};
+use common::rom;
+
+const PONG_SHA1: &str = "b232ef880bd6060fb45fa6effed7edf0ae95670e";
+
+#[test]
+fn sha1() {
+    assert_eq!(
+        database::sha1_hex(b"abc"),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(database::sha1_hex(&rom("PONG")), PONG_SHA1);
+}
+
+#[test]
+fn bundled_roms_are_known() {
+    let database = Database::builtin();
+    for name in common::bundled_roms() {
+        // Not identified
+        if name == "chip8.ch8" {
+            continue;
+        }
+        let info = database
+            .lookup(&rom(&name))
+            .unwrap_or_else(|| panic!("{} is not in the database", name));
+        assert!(info.platform.is_some(), "{} has no known platform", name);
+    }
+}
+
+#[test]
+fn lookup() {
+    let database = Database::builtin();
+    let info = database.lookup(&rom("PONG")).unwrap();
+    let chip48 = database.platform("chip48").unwrap();
+
+    assert_eq!(info.sha1, PONG_SHA1);
+    assert_eq!(info.title, "Pong");
+    assert_eq!(info.authors, ["Paul Vervalin"]);
+    assert_eq!(info.platform.as_deref(), Some("chip48"));
+    assert_eq!(info.quirks, chip48.quirks);
+    assert_eq!(info.tick_rate, chip48.default_tickrate);
+    assert_eq!(info.keys["up"], 0x1);
+    assert_eq!(info.keys["player2Down"], 0xD);
+    assert_eq!(info.palette, None);
+
+    let mut chip8 = Chip8::new();
+    info.apply(&mut chip8);
+    assert_eq!(chip8.quirks, chip48.quirks);
+    assert_eq!(chip8.tick_rate, 30);
+
+    assert_eq!(database.lookup(&[0x12, 0x00]), None);
+}
+
+#[test]
+fn later_programs_win() {
+    let mut database = Database::builtin().clone();
+    database
+        .add_programs(&format!(
+            r##"[{{
+                "title": "My Pong",
+                "roms": {{
+                    "{}": {{
+                        "tickrate": 20,
+                        "platforms": ["unknownPlatform", "originalChip8"],
+                        "quirkyPlatforms": {{
+                            "originalChip8": {{ "shift": true, "fontStyle": true }}
+                        }},
+                        "colors": {{ "pixels": ["#000000", "#33ff66"] }}
+                    }}
+                }}
+            }}]"##,
+            PONG_SHA1.to_uppercase()
+        ))
+        .unwrap();
+
+    let info = database.lookup(&rom("PONG")).unwrap();
+    let mut quirks = database.platform("originalChip8").unwrap().quirks;
+    quirks.shift = true;
+
+    assert_eq!(info.title, "My Pong");
+    assert!(info.authors.is_empty());
+    assert_eq!(info.platform.as_deref(), Some("originalChip8"));
+    assert_eq!(info.quirks, quirks);
+    assert_eq!(info.tick_rate, 20);
+    assert!(info.keys.is_empty());
+    assert_eq!(
+        info.palette.unwrap().colors,
+        [
+            [0, 0, 0],
+            [0x33, 0xFF, 0x66],
+            [0x33, 0xFF, 0x66],
+            [0x33, 0xFF, 0x66]
+        ]
+    );
+}
+
+#[test]
+fn unknown_platform() {
+    let mut database = Database::builtin().clone();
+    database
+        .add_programs(&format!(
+            r#"[{{ "title": "Pong", "roms": {{ "{}": {{ "platforms": ["megachip8"] }} }} }}]"#,
+            PONG_SHA1
+        ))
+        .unwrap();
+
+    let info = database.lookup(&rom("PONG")).unwrap();
+    assert_eq!(info.platform, None);
+    assert_eq!(info.quirks, Quirks::default());
+    assert_eq!(info.tick_rate, TICKS_PER_FRAME);
+}
+
+#[test]
+fn invalid_json() {
+    let mut database = Database::builtin().clone();
+    assert!(database.add_programs("{").is_err());
+    assert!(database.add_programs(r#"[{ "roms": {} }]"#).is_err());
+}
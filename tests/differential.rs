@@ -0,0 +1,187 @@
+// Runs ROMs in lock step with the reference interpreter in common/reference.rs
+// and stops at the first instruction after which the two machines differ.
+
+mod common;
+
+use chip8_rs::diff::{self, Divergence, State};
+use chip8_rs::trace::{self, Trace, TraceFormat};
+use chip8_rs::{Chip8, TICKS_PER_FRAME};
+use common::reference::Reference;
+use common::{bundled_roms, keys_at, random_roms, rom};
+
+fn lock_step(rom: &[u8], frames: u32) -> Option<Divergence> {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom).unwrap();
+    let mut reference = Reference::new(rom);
+
+    for frame in 0..frames {
+        let keys = keys_at(frame);
+        for (key, pressed) in keys.iter().enumerate() {
+            chip8.key_press(key, *pressed);
+        }
+        reference.keys = keys;
+
+        for _ in 0..TICKS_PER_FRAME {
+            let (cycle, pc) = (chip8.cycles, chip8.pc);
+            let result = chip8.step();
+            let stepped = reference.step();
+            let op = chip8.opcode;
+
+            if result.is_err() || !stepped {
+                assert_eq!(
+                    result.is_ok(),
+                    stepped,
+                    "cycle {} at {:03X}: only one side stopped ({:?})",
+                    cycle,
+                    pc,
+                    result
+                );
+                return None;
+            }
+
+            if op >> 12 == 0xC {
+                let x = ((op >> 8) & 0xF) as usize;
+                reference.v[x] = chip8.v_reg[x];
+            }
+
+            // run_frame ticks the timers after the last instruction of a frame
+            if chip8.cycles.is_multiple_of(TICKS_PER_FRAME as u64) {
+                chip8.tick_timers();
+            }
+
+            if let Some((what, expected, actual)) = reference.state().diff(&State::of(&chip8)) {
+                return Some(Divergence {
+                    cycle,
+                    pc,
+                    op,
+                    what,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+#[test]
+fn bundled_roms_match_reference() {
+    for name in bundled_roms() {
+        if let Some(divergence) = lock_step(&rom(&name), 300) {
+            panic!("{} diverged at {}", name, divergence);
+        }
+    }
+}
+
+#[test]
+fn random_roms_match_reference() {
+    for (idx, rom) in random_roms(500).iter().enumerate() {
+        if let Some(divergence) = lock_step(rom, 30) {
+            panic!("random ROM {} diverged at {}", idx, divergence);
+        }
+    }
+}
+
+// BRIX's first CXNN, at cycle 1163, stores the 00 V6 already holds
+const SEED: u64 = 11;
+
+// Records a full trace of a run, the way --trace would write it
+fn record_trace(rom: &[u8], frames: u32) -> Vec<String> {
+    let trace = Trace {
+        format: TraceFormat::Full,
+        range: 0..=0xFFF,
+    };
+    record(rom, frames, &trace)
+}
+
+fn record(rom: &[u8], frames: u32, trace: &Trace) -> Vec<String> {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom).unwrap();
+    chip8.seed(SEED);
+
+    let mut lines = vec![trace::header(SEED)];
+    for _ in 0..frames * TICKS_PER_FRAME as u32 {
+        let (pc, before) = (chip8.pc, chip8.v_reg);
+        let op = chip8.fetch_instruction();
+        chip8.opcode = op;
+        chip8.execute_instruction(op).unwrap();
+        if trace.range.contains(&pc) {
+            lines.push(trace.line(&chip8, pc, op, &before));
+        }
+        chip8.cycles += 1;
+        if chip8.cycles.is_multiple_of(TICKS_PER_FRAME as u64) {
+            chip8.tick_timers();
+        }
+    }
+    lines
+}
+
+fn replay(rom: &[u8], lines: &[String]) -> Option<Divergence> {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(rom).unwrap();
+    diff::replay_trace(&mut chip8, lines.join("\n").as_bytes()).unwrap()
+}
+
+#[test]
+fn replayed_trace_matches() {
+    // BRIX uses CXNN, the random values come from the trace
+    for name in ["ibm.ch8", "test_opcode.ch8", "BRIX"] {
+        let rom = rom(name);
+        let lines = record_trace(&rom, 300);
+        assert_eq!(replay(&rom, &lines), None, "{}", name);
+    }
+}
+
+#[test]
+fn replayed_compact_trace_matches() {
+    let rom = rom("BRIX");
+    let compact = Trace {
+        format: TraceFormat::Compact,
+        range: 0..=0xFFF,
+    };
+    let lines = record(&rom, 300, &compact);
+    assert_eq!(replay(&rom, &lines), None);
+
+    // Without the seed CXNN results come from RND=, even when the number is
+    // what VX already held and VX isn't on the line
+    assert_eq!(replay(&rom, &lines[1..]), None);
+
+    // BRIX's only CXNN is at 23A, outside the range it runs from the seed
+    let ranged = Trace {
+        format: TraceFormat::Compact,
+        range: 0x240..=0xFFF,
+    };
+    let lines = record(&rom, 300, &ranged);
+    assert!(!lines.iter().any(|line| line.contains(" 23A C60F ")));
+    assert_eq!(replay(&rom, &lines), None);
+}
+
+#[test]
+fn replayed_trace_reports_first_divergence() {
+    let rom = rom("ibm.ch8");
+    let mut lines = record_trace(&rom, 10);
+
+    // After the header, 00000002 204 600C LD V0, 0C ... V0=0C
+    lines[3] = lines[3].replace("V0=0C", "V0=0D");
+    lines[6] = lines[6].replace("I=", "I=F");
+
+    let divergence = replay(&rom, &lines).unwrap();
+    assert_eq!(
+        divergence.to_string(),
+        "cycle 2 at 204 (600C LD V0, 0C): V0 expected 0D, got 0C"
+    );
+
+    // Compact lines only check the registers they list
+    let compact: Vec<String> = lines[..3]
+        .iter()
+        .map(|line| line.split(" V0=").next().unwrap().to_string())
+        .collect();
+    assert_eq!(replay(&rom, &compact), None);
+
+    // A wrong jump is blamed on the instruction before the mismatch
+    let mut jumped = record_trace(&rom, 1);
+    jumped[2] = jumped[2].replacen(" 202 ", " 206 ", 1);
+    let divergence = replay(&rom, &jumped).unwrap();
+    assert_eq!((divergence.cycle, divergence.what.as_str()), (0, "PC"));
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use chip8_rs::env::{Chip8Env, Error, Game, Observe};
+use chip8_rs::{Chip8, SCREEN_WIDTH};
+use common::rom;
+
+fn game_env(name: &str) -> Chip8Env {
+    let rom = rom(name);
+    Chip8Env::new(&rom, Game::detect(&rom).unwrap()).unwrap()
+}
+
+// Steps with actions from a small xorshift until the game is over, returns
+// every step's result
+fn play(env: &mut Chip8Env, seed: u64, max_steps: usize) -> Vec<(Vec<u8>, f32, bool)> {
+    let mut x = seed.wrapping_mul(0x9E3779B97F4A7C15) | 1;
+    let mut steps = Vec::new();
+    for _ in 0..max_steps {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        let step = env.step(x as usize % env.actions().len()).unwrap();
+        let done = step.2;
+        steps.push(step);
+        if done {
+            break;
+        }
+    }
+    steps
+}
+
+#[test]
+fn detect() {
+    assert_eq!(Game::detect(&rom("BRIX")), Some(Game::Brix));
+    assert_eq!(Game::detect(&rom("PONG")), Some(Game::Pong));
+    assert_eq!(Game::detect(&rom("TETRIS")), Some(Game::Tetris));
+    assert_eq!(Game::detect(&rom("PONG2")), None);
+}
+
+#[test]
+fn deterministic() {
+    for name in ["BRIX", "PONG", "TETRIS"] {
+        let mut env = game_env(name);
+        let first_obs = env.reset(7);
+        let first = play(&mut env, 1, 300);
+        assert_eq!(env.reset(7), first_obs, "{}", name);
+        assert_eq!(play(&mut env, 1, 300), first, "{}", name);
+    }
+
+    // BRIX serves the ball from one of 16 random columns
+    let mut env = game_env("BRIX");
+    let mut episodes: Vec<_> = (0..8)
+        .map(|seed| {
+            env.reset(seed);
+            play(&mut env, 0, 40)
+        })
+        .collect();
+    episodes.dedup();
+    assert!(episodes.len() > 1);
+}
+
+#[test]
+fn episodes_end() {
+    // (ROM, actions until game over at most)
+    for (name, max_steps) in [("BRIX", 2000), ("PONG", 2000), ("TETRIS", 2000)] {
+        let mut env = game_env(name);
+        env.reset(3);
+        let steps = play(&mut env, 3, max_steps);
+        let (obs, _, done) = steps.last().unwrap().clone();
+        assert!(done, "{} still running after {} steps", name, max_steps);
+
+        // Nothing happens after that
+        assert_eq!(env.step(0).unwrap(), (obs, 0.0, true), "{}", name);
+        assert!(!env.reset(3).is_empty());
+        assert!(!env.step(0).unwrap().2, "{}", name);
+    }
+}
+
+#[test]
+fn rewards() {
+    // A brick for each point
+    let mut env = game_env("BRIX");
+    env.reset(0);
+    let steps = play(&mut env, 0, 2000);
+    let total: f32 = steps.iter().map(|(_, reward, _)| reward).sum();
+    assert!(steps.iter().all(|(_, reward, _)| *reward >= 0.0));
+    assert_eq!(total as i64, Game::Brix.score(env.chip8()));
+    assert!(total > 0.0);
+
+    // +1 for the agent's points, -1 for the other paddle's
+    let mut env = game_env("PONG");
+    env.reset(0);
+    let steps = play(&mut env, 0, 2000);
+    assert!(steps.iter().any(|(_, reward, _)| *reward == 1.0));
+    assert!(steps.iter().any(|(_, reward, _)| *reward == -1.0));
+}
+
+#[test]
+fn observations() {
+    let mut env = game_env("TETRIS");
+    env.frame_skip = 1;
+    let screen = env.reset(0);
+    assert_eq!(screen.len(), 64 * 32);
+    assert!(screen.iter().all(|pixel| *pixel <= 1));
+
+    // The well is drawn within the first frames
+    let (screen, _, _) = env.step(0).unwrap();
+    assert_eq!(screen[0x1A + 31 * SCREEN_WIDTH], 1);
+
+    env.observe = Observe::Ram;
+    let ram = env.observation();
+    assert_eq!(ram, env.chip8().memory().to_vec());
+
+    env.observe = Observe::ScreenAndRam;
+    assert_eq!(env.observation(), [screen, ram].concat());
+
+    // Only the game's actions
+    assert_eq!(
+        env.step(5),
+        Err(Error::InvalidAction {
+            action: 5,
+            actions: 5
+        })
+    );
+}
+
+#[test]
+fn digits_past_9() {
+    // Digits past 9 in RAM the game didn't write, e.g. poked by a cheat
+    let mut chip8 = Chip8::new();
+    chip8.write_memory(0x2F2, &[26, 0, 0]);
+    assert!(Game::Pong.over(&chip8));
+    assert_eq!(Game::Pong.score(&chip8), 260);
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use chip8_rs::Chip8;
+use chip8_rs::gdb::Gdb;
+use common::rom;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// A scripted debugger, with the emulator running frames in between
+struct Session {
+    gdb: Gdb,
+    chip8: Chip8,
+    stream: TcpStream,
+    received: Vec<u8>,
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, byte| sum.wrapping_add(byte))
+}
+
+impl Session {
+    fn new(program: &[u8]) -> Self {
+        let gdb = Gdb::listen("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(gdb.addr()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(2)))
+            .unwrap();
+        let mut chip8 = Chip8::new();
+        chip8.load_rom(program).unwrap();
+        Self {
+            gdb,
+            chip8,
+            stream,
+            received: Vec::new(),
+        }
+    }
+
+    fn frame(&mut self) {
+        self.gdb.poll(&mut self.chip8);
+        self.gdb.run_frame(&mut self.chip8).unwrap();
+    }
+
+    fn send(&mut self, data: &str) {
+        write!(self.stream, "${}#{:02x}", data, checksum(data)).unwrap();
+    }
+
+    // Waits for a whole packet, skipping acks, and checks its checksum
+    fn receive(&mut self) -> String {
+        for _ in 0..1000 {
+            self.frame();
+            let mut buffer = [0; 4096];
+            match self.stream.read(&mut buffer) {
+                Ok(read) => self.received.extend_from_slice(&buffer[..read]),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(err) => panic!("{}", err),
+            }
+
+            let text = String::from_utf8(self.received.clone()).unwrap();
+            let text = text.trim_start_matches('+');
+            if let Some(start) = text.find('$')
+                && let Some(end) = text[start..].find('#').map(|end| start + end)
+                && text.len() >= end + 3
+            {
+                let data = text[start + 1..end].to_string();
+                assert_eq!(
+                    u8::from_str_radix(&text[end + 1..end + 3], 16).unwrap(),
+                    checksum(&data)
+                );
+                self.received = text.as_bytes()[end + 3..].to_vec();
+                return data;
+            }
+        }
+        panic!("No reply");
+    }
+
+    // Runs frames until the stub has caught up
+    fn wait_until(&mut self, done: impl Fn(&Self) -> bool) {
+        for _ in 0..1000 {
+            self.frame();
+            if done(self) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("Timed out");
+    }
+
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.receive()
+    }
+}
+
+fn le16(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+#[test]
+fn handshake() {
+    let mut session = Session::new(&rom("ibm.ch8"));
+    let features = session.request("qSupported:multiprocess+;swbreak+;xmlRegisters=i386");
+    assert!(features.contains("qXfer:features:read+"));
+    assert_eq!(session.request("?"), "S05");
+
+    // The target description comes in parts
+    let mut xml = String::new();
+    loop {
+        let part = session.request(&format!(
+            "qXfer:features:read:target.xml:{:x},40",
+            xml.len()
+        ));
+        xml += &part[1..];
+        if part.starts_with('l') {
+            break;
+        }
+        assert!(part.starts_with('m'));
+    }
+    assert_eq!(xml, chip8_rs::gdb::target_xml());
+    assert_eq!(
+        session.request("qXfer:features:read:target.xml:10,ffffffffffffffff"),
+        format!("l{}", &xml[0x10..])
+    );
+    assert!(xml.contains(r#"<reg name="vf" bitsize="8""#));
+    assert!(xml.contains(r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#));
+
+    assert_eq!(session.request("qfThreadInfo"), "m1");
+    assert_eq!(session.request("Hg0"), "OK");
+    assert_eq!(session.request("vMustReplyEmpty"), "");
+
+    // Nothing ran while the debugger looked around
+    assert_eq!(session.chip8.cycles, 0);
+}
+
+#[test]
+fn registers_and_memory() {
+    let mut session = Session::new(&rom("ibm.ch8"));
+    let registers = session.request("g");
+    // v0-vf, then I, PC, SP, DT and ST
+    assert_eq!(registers.len(), 2 * (16 + 2 + 2 + 1 + 1 + 1));
+    assert_eq!(&registers[32..40], format!("{}{}", le16(0), le16(0x200)));
+
+    assert_eq!(session.request("P3=2a"), "OK");
+    assert_eq!(session.request("p3"), "2a");
+    assert_eq!(session.request(&format!("P10={}", le16(0x345))), "OK");
+    assert_eq!(session.chip8.i_reg, 0x345);
+    assert_eq!((session.chip8.v_reg[3]), 0x2A);
+    // Wrong size, unknown register, a stack pointer past the stack
+    assert_eq!(session.request("P3=2a00"), "E01");
+    assert_eq!(session.request("p20"), "E01");
+    assert_eq!(session.request("P12=11"), "E01");
+
+    let mut all = registers.clone();
+    all.replace_range(0..2, "ff");
+    assert_eq!(session.request(&format!("G{}", all)), "OK");
+    assert_eq!(session.chip8.v_reg[0], 0xFF);
+
+    assert_eq!(session.request("m200,4"), "00e0a22a");
+    assert_eq!(session.request("M300,3:0102ff"), "OK");
+    assert_eq!(session.chip8.memory()[0x300..0x303], [1, 2, 0xFF]);
+    // Reads stop at the end of RAM
+    assert_eq!(session.request("mffe,10").len(), 4);
+    assert_eq!(session.request("m200,ffffffffffffffff").len(), 2 * 0xE00);
+    assert_eq!(session.request("m1000,1"), "E01");
+    assert_eq!(session.request("Mfff,2:0102"), "E01");
+    assert_eq!(session.request("Mffffffffffffffff,1:00"), "E01");
+}
+
+#[test]
+fn breakpoints_and_stepping() {
+    let mut session = Session::new(&rom("ibm.ch8"));
+    assert_eq!(session.request("s"), "S05");
+    assert_eq!(session.chip8.pc, 0x202);
+
+    // LD I, 239 at 20C
+    assert_eq!(session.request("Z0,20c,2"), "OK");
+    assert_eq!(session.request("c"), "S05");
+    assert_eq!(session.chip8.pc, 0x20C);
+    assert!(session.gdb.stopped);
+
+    // Continuing leaves the breakpoint it's on, the next one is hit
+    assert_eq!(session.request("Z0,214,2"), "OK");
+    assert_eq!(session.request("c"), "S05");
+    assert_eq!(session.chip8.pc, 0x214);
+    assert_eq!(session.request("z0,20c,2"), "OK");
+    assert_eq!(session.request("z0,214,2"), "OK");
+    // Watchpoints aren't supported
+    assert_eq!(session.request("Z2,300,1"), "");
+
+    // The program loops forever at 228 until interrupted
+    session.send("c");
+    session.wait_until(|session| session.chip8.pc == 0x228);
+    assert!(!session.gdb.stopped);
+    session.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(session.receive(), "S02");
+    assert_eq!(session.chip8.pc, 0x228);
+}
+
+#[test]
+fn program_errors() {
+    // LD V0, 1; RET with an empty stack
+    let mut session = Session::new(&[0x60, 0x01, 0x00, 0xEE]);
+    assert_eq!(session.request("c"), "S0b");
+    // Stopped on the instruction that failed
+    assert_eq!(session.chip8.pc, 0x202);
+
+    // An invalid opcode
+    assert_eq!(session.request("M202,2:ffff"), "OK");
+    assert_eq!(session.request("s"), "S04");
+    assert_eq!(session.chip8.pc, 0x202);
+}
+
+#[test]
+fn packets() {
+    let mut session = Session::new(&rom("ibm.ch8"));
+
+    // A corrupted packet is refused and sent again
+    session.stream.write_all(b"$m200,2#00").unwrap();
+    session.frame();
+    session.send("m200,2");
+    assert_eq!(session.receive(), "00e0");
+    assert!(String::from_utf8_lossy(&session.received).is_empty());
+
+    // A reply that didn't come through is resent
+    session.stream.write_all(b"-").unwrap();
+    assert_eq!(session.receive(), "00e0");
+
+    assert_eq!(session.request("QStartNoAckMode"), "OK");
+    assert_eq!(session.request("p11"), le16(0x200));
+
+    // Detaching lets the program run on its own
+    assert_eq!(session.request("D"), "OK");
+    session.wait_until(|session| session.chip8.cycles > 0);
+    assert!(!session.gdb.attached());
+}
+
+#[test]
+fn one_debugger() {
+    let mut session = Session::new(&rom("ibm.ch8"));
+    assert_eq!(session.request("?"), "S05");
+
+    // A second one is turned away
+    let other = TcpStream::connect(session.gdb.addr()).unwrap();
+    other
+        .set_read_timeout(Some(Duration::from_millis(1)))
+        .unwrap();
+    session.wait_until(|_| matches!((&other).read(&mut [0; 16]), Ok(0)));
+
+    // After the first disconnects the program runs
+    session.stream.shutdown(std::net::Shutdown::Both).unwrap();
+    session.wait_until(|session| !session.gdb.attached());
+    assert!(!session.gdb.stopped);
+}
@@ -0,0 +1,175 @@
+// The block translator has to leave the machine in exactly the state the
+// interpreter would, checked after every frame or batch of instructions.
+
+mod common;
+
+use chip8_rs::diff::State;
+use chip8_rs::jit::Jit;
+use chip8_rs::{Chip8, START_ADDR, TICKS_PER_FRAME};
+use common::{bundled_roms, keys_at, random_roms, rom};
+
+const SEED: u64 = 0xC8;
+
+// Runs a ROM for some batches of instructions on both, returns the first
+// difference. Both are seeded the same so CXNN matches too.
+fn compare(rom: &[u8], batches: u32, batch: u64) -> Option<String> {
+    let mut interpreter = Chip8::new();
+    interpreter.load_rom(rom).unwrap();
+    interpreter.seed(SEED);
+    let mut translated = Chip8::new();
+    translated.load_rom(rom).unwrap();
+    translated.seed(SEED);
+    let mut jit = Jit::new();
+
+    for frame in 0..batches {
+        for (key, pressed) in keys_at(frame).iter().enumerate() {
+            interpreter.key_press(key, *pressed);
+            translated.key_press(key, *pressed);
+        }
+
+        let mut result = Ok(());
+        for _ in 0..batch {
+            result = interpreter.step();
+            if result.is_err() {
+                break;
+            }
+        }
+
+        let translated_result = jit.run(&mut translated, batch);
+        if result != translated_result {
+            return Some(format!(
+                "batch {}: interpreter {:?}, jit {:?}",
+                frame, result, translated_result
+            ));
+        }
+
+        let expected = State::of(&interpreter);
+        if let Some((what, expected, actual)) = expected.diff(&State::of(&translated)) {
+            return Some(format!(
+                "batch {}: {} expected {}, got {}",
+                frame, what, expected, actual
+            ));
+        }
+        if (interpreter.cycles, interpreter.opcode) != (translated.cycles, translated.opcode) {
+            return Some(format!("batch {}: cycles or opcode differ", frame));
+        }
+
+        if result.is_err() {
+            return None;
+        }
+
+        if interpreter.cycles.is_multiple_of(TICKS_PER_FRAME as u64) {
+            interpreter.tick_timers();
+            translated.tick_timers();
+        }
+    }
+
+    None
+}
+
+#[test]
+fn bundled_roms_match_interpreter() {
+    for name in bundled_roms() {
+        if let Some(divergence) = compare(&rom(&name), 300, TICKS_PER_FRAME as u64) {
+            panic!("{} diverged at {}", name, divergence);
+        }
+    }
+}
+
+#[test]
+fn blocks_cut_short_match_interpreter() {
+    // Batches that end in the middle of blocks
+    for batch in [1, 3, 7, 64] {
+        for name in ["ibm.ch8", "test_opcode.ch8", "INVADERS", "SYZYGY"] {
+            if let Some(divergence) = compare(&rom(name), 100, batch) {
+                panic!(
+                    "{} in batches of {} diverged at {}",
+                    name, batch, divergence
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn random_roms_match_interpreter() {
+    for (idx, rom) in random_roms(500).iter().enumerate() {
+        if let Some(divergence) = compare(rom, 30, TICKS_PER_FRAME as u64) {
+            panic!("random ROM {} diverged at {}", idx, divergence);
+        }
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    let mut chip8 = Chip8::new();
+    chip8
+        .load_rom(&[
+            0x62, 0x00, // LD V2, 00
+            0xA2, 0x00, // LD I, 200
+            0x60, 0x62, // LD V0, 62
+            0x61, 0x55, // LD V1, 55
+            0xF1, 0x55, // LD [I], V1 rewrites 200 to LD V2, 55
+            0x12, 0x00, // JP 200
+        ])
+        .unwrap();
+    let mut jit = Jit::new();
+
+    jit.run(&mut chip8, 6).unwrap();
+    assert_eq!((chip8.pc, chip8.v_reg[2]), (START_ADDR, 0));
+
+    // The block at 200 was translated before FX55 wrote over it
+    jit.run(&mut chip8, 1).unwrap();
+    assert_eq!(chip8.v_reg[2], 0x55);
+
+    // Writes from outside count too
+    chip8.write_memory(START_ADDR + 1, &[0x77]);
+    chip8.pc = START_ADDR;
+    jit.run(&mut chip8, 1).unwrap();
+    assert_eq!(chip8.v_reg[2], 0x77);
+
+    // And a fresh ROM replaces every block
+    chip8.reset();
+    chip8.load_rom(&[0x63, 0x12]).unwrap();
+    jit.run(&mut chip8, 1).unwrap();
+    assert_eq!(chip8.v_reg[3], 0x12);
+}
+
+#[test]
+fn shared_between_machines() {
+    let mut jit = Jit::new();
+
+    let mut first = Chip8::new();
+    first.load_rom(&[0x63, 0x12]).unwrap();
+    jit.run(&mut first, 1).unwrap();
+
+    // Same addresses, different code
+    let mut second = Chip8::new();
+    second.load_rom(&[0x63, 0x34]).unwrap();
+    jit.run(&mut second, 1).unwrap();
+    assert_eq!(second.v_reg[3], 0x34);
+}
+
+#[test]
+fn idle_loops_skip_ahead() {
+    let mut jit = Jit::new();
+
+    // FX0A, then a jump to itself
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&[0xF0, 0x0A, 0x12, 0x02]).unwrap();
+    jit.run(&mut chip8, 1000).unwrap();
+    assert_eq!((chip8.pc, chip8.cycles), (START_ADDR, 1000));
+
+    chip8.key_press(5, true);
+    jit.run(&mut chip8, 1).unwrap();
+    chip8.key_press(5, false);
+    jit.run(&mut chip8, 1000).unwrap();
+    assert_eq!((chip8.v_reg[0], chip8.pc), (5, START_ADDR + 2));
+    assert_eq!(chip8.cycles, 2001);
+}
+
+#[test]
+fn can_move_between_threads() {
+    fn send<T: Send>(_: T) {}
+    send(Jit::new());
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use chip8_rs::database::Database;
+use chip8_rs::launcher::{Entry, LIST_ROWS, Launcher, MENU_HEIGHT, MENU_WIDTH};
+use chip8_rs::palette::Palette;
+use chip8_rs::renderer::BYTES_PER_PIXEL;
+use chip8_rs::{Chip8, START_ADDR};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn rom_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("rom")
+}
+
+fn entries(count: usize) -> Vec<Entry> {
+    (0..count)
+        .map(|idx| Entry {
+            name: format!("ROM{:02}", idx),
+            path: PathBuf::from(format!("ROM{:02}", idx)),
+            info: None,
+        })
+        .collect()
+}
+
+#[test]
+fn scan() {
+    let database = Database::builtin();
+    let launcher = Launcher::scan(&rom_dir(), Some(database)).unwrap();
+    let names: Vec<_> = launcher.entries().iter().map(|e| e.name.clone()).collect();
+    assert_eq!(names, common::bundled_roms());
+
+    let pong = &launcher.entries()[names.iter().position(|n| n == "PONG").unwrap()];
+    assert_eq!(pong.path, rom_dir().join("PONG"));
+    assert_eq!(pong.info.as_ref().unwrap().title, "Pong");
+    assert_eq!(launcher.selected().unwrap().name, "15PUZZLE");
+
+    let launcher = Launcher::scan(&rom_dir(), None).unwrap();
+    assert!(launcher.entries().iter().all(|entry| entry.info.is_none()));
+
+    // Empty, too big, or not a file at all
+    let dir = std::env::temp_dir().join(format!("chip8-rs-{}-launcher", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("DIR")).unwrap();
+    fs::write(dir.join("GAME"), [0x12, 0x00]).unwrap();
+    fs::write(dir.join("EMPTY"), []).unwrap();
+    fs::write(dir.join("HUGE"), vec![0; 4096]).unwrap();
+    std::os::unix::fs::symlink(dir.join("MISSING"), dir.join("LINK")).unwrap();
+    let launcher = Launcher::scan(&dir, None).unwrap();
+    let names: Vec<_> = launcher.entries().iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["GAME"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn selection() {
+    let mut launcher = Launcher::new(entries(25));
+    assert_eq!(launcher.selected().unwrap().name, "ROM00");
+
+    launcher.move_selection(-1);
+    assert_eq!(launcher.selected().unwrap().name, "ROM00");
+    launcher.move_selection(LIST_ROWS as isize);
+    assert_eq!(launcher.selected().unwrap().name, "ROM10");
+    launcher.move_selection(isize::MAX);
+    assert_eq!(launcher.selected().unwrap().name, "ROM24");
+    launcher.move_selection(1);
+    assert_eq!(launcher.selected().unwrap().name, "ROM24");
+
+    launcher.select("ROM07");
+    assert_eq!(launcher.selected().unwrap().name, "ROM07");
+    launcher.select("MISSING");
+    assert_eq!(launcher.selected().unwrap().name, "ROM07");
+
+    let mut empty = Launcher::new(Vec::new());
+    empty.move_selection(1);
+    assert!(empty.selected().is_none());
+}
+
+#[test]
+fn render() {
+    let palette = Palette::parse("#000000,#ffffff,#808080").unwrap();
+    let mut launcher = Launcher::new(entries(25));
+
+    let first = launcher.render(&palette).to_vec();
+    assert_eq!(first.len(), MENU_WIDTH * MENU_HEIGHT * BYTES_PER_PIXEL);
+    // Only drawn in the palette's colours, with text in all of them
+    for color in first.chunks_exact(BYTES_PER_PIXEL) {
+        assert!(
+            palette.colors[..3].iter().any(|c| c == color),
+            "{:?}",
+            color
+        );
+    }
+    for color in &palette.colors[..3] {
+        assert!(first.chunks_exact(BYTES_PER_PIXEL).any(|c| c == color));
+    }
+
+    // Scrolling far down redraws the list
+    launcher.move_selection(20);
+    assert_ne!(launcher.render(&palette), first.as_slice());
+
+    // Nothing to list still draws
+    Launcher::new(Vec::new()).render(&palette);
+}
+
+#[test]
+fn switching_roms() {
+    // The launcher loads each ROM with reset + load_rom
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&[0xAA; 64]).unwrap();
+    chip8.run_frame().ok();
+
+    chip8.reset();
+    chip8.load_rom(&[0x12, 0x00]).unwrap();
+    let start = START_ADDR as usize;
+    assert_eq!(chip8.memory()[start..start + 2], [0x12, 0x00]);
+    assert!(chip8.memory()[start + 2..].iter().all(|byte| *byte == 0));
+    assert_eq!(chip8.pc, START_ADDR);
+}
@@ -0,0 +1,647 @@
+// One table per group of opcodes in Chip8::execute_instruction.
+//
+// These document the semantics the interpreter implements with the default
+// quirks, the quirks test covers the alternatives:
+//  - 8XY6/8XYE shift VX in place and ignore VY
+//  - FX55/FX65 leave I unchanged
+//  - BNNN jumps to NNN + V0
+//  - 8XY1/8XY2/8XY3 leave VF alone
+//  - DXYN wraps pixels that go past the edge of the screen
+//  - Flag results are written to VF after VX, so VF holds the flag when X is F
+
+use chip8_rs::quirks::Quirks;
+use chip8_rs::snapshot::{self, Snapshot};
+use chip8_rs::{Chip8, SCREEN_WIDTH, START_ADDR};
+
+// Registers to set up, as (register, value)
+type Regs = &'static [(usize, u8)];
+
+fn chip8_with(regs: Regs) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    for (reg, value) in regs {
+        chip8.v_reg[*reg] = *value;
+    }
+    chip8
+}
+
+fn assert_regs(chip8: &Chip8, expected: Regs, op: u16) {
+    for (reg, value) in expected {
+        assert_eq!(
+            chip8.v_reg[*reg], *value,
+            "{:04X}: V{:X} is {:02X}, expected {:02X}",
+            op, reg, chip8.v_reg[*reg], value
+        );
+    }
+}
+
+#[test]
+fn alu() {
+    // (opcode, registers before, registers after)
+    let cases: &[(u16, Regs, Regs)] = &[
+        // 6XNN
+        (0x6A42, &[], &[(0xA, 0x42)]),
+        (0x6F01, &[], &[(0xF, 0x01)]),
+        // 7XNN wraps and never touches VF
+        (0x7A01, &[(0xA, 0x10)], &[(0xA, 0x11)]),
+        (0x7A01, &[(0xA, 0xFF), (0xF, 7)], &[(0xA, 0x00), (0xF, 7)]),
+        // 8XY0
+        (0x8120, &[(1, 3), (2, 9)], &[(1, 9), (2, 9)]),
+        // 8XY1, 8XY2, 8XY3
+        (
+            0x8121,
+            &[(1, 0b1100), (2, 0b1010), (0xF, 7)],
+            &[(1, 0b1110), (0xF, 7)],
+        ),
+        (
+            0x8122,
+            &[(1, 0b1100), (2, 0b1010), (0xF, 7)],
+            &[(1, 0b1000), (0xF, 7)],
+        ),
+        (
+            0x8123,
+            &[(1, 0b1100), (2, 0b1010), (0xF, 7)],
+            &[(1, 0b0110), (0xF, 7)],
+        ),
+        // 8XY4 sets VF to the carry
+        (0x8124, &[(1, 1), (2, 2), (0xF, 7)], &[(1, 3), (0xF, 0)]),
+        (0x8124, &[(1, 0xFF), (2, 2)], &[(1, 1), (0xF, 1)]),
+        (0x8114, &[(1, 0x80)], &[(1, 0), (0xF, 1)]),
+        // 8XY4 with VF as an operand, the flag wins
+        (0x8FF4, &[(0xF, 0x90)], &[(0xF, 1)]),
+        (0x8F14, &[(0xF, 0x10), (1, 0x10)], &[(0xF, 0)]),
+        (0x81F4, &[(1, 0xFF), (0xF, 1)], &[(1, 0), (0xF, 1)]),
+        // 8XY5 sets VF when there is no borrow
+        (0x8125, &[(1, 5), (2, 3)], &[(1, 2), (0xF, 1)]),
+        (0x8125, &[(1, 3), (2, 3)], &[(1, 0), (0xF, 1)]),
+        (0x8125, &[(1, 3), (2, 5)], &[(1, 0xFE), (0xF, 0)]),
+        (0x8F15, &[(0xF, 5), (1, 3)], &[(0xF, 1)]),
+        (0x8F15, &[(0xF, 3), (1, 5)], &[(0xF, 0)]),
+        // 8XY7 is VY - VX
+        (0x8127, &[(1, 3), (2, 5)], &[(1, 2), (0xF, 1)]),
+        (0x8127, &[(1, 5), (2, 3)], &[(1, 0xFE), (0xF, 0)]),
+        (0x8F17, &[(0xF, 3), (1, 5)], &[(0xF, 1)]),
+        // 8XY6 shifts VX right, VF is the bit shifted out
+        (
+            0x8126,
+            &[(1, 0b101), (2, 0xFF)],
+            &[(1, 0b10), (2, 0xFF), (0xF, 1)],
+        ),
+        (0x8126, &[(1, 0b100)], &[(1, 0b10), (0xF, 0)]),
+        (0x8F06, &[(0xF, 0b11)], &[(0xF, 1)]),
+        // 8XYE shifts VX left
+        (0x812E, &[(1, 0x81), (2, 0)], &[(1, 0x02), (2, 0), (0xF, 1)]),
+        (0x812E, &[(1, 0x41)], &[(1, 0x82), (0xF, 0)]),
+        (0x8F0E, &[(0xF, 0x80)], &[(0xF, 1)]),
+    ];
+
+    for (op, before, after) in cases {
+        let mut chip8 = chip8_with(before);
+        chip8.execute_instruction(*op).unwrap();
+        assert_regs(&chip8, after, *op);
+    }
+}
+
+#[test]
+fn skips() {
+    // (opcode, registers, pressed key, skips)
+    let cases: &[(u16, Regs, Option<usize>, bool)] = &[
+        // 3XNN, 4XNN
+        (0x3142, &[(1, 0x42)], None, true),
+        (0x3142, &[(1, 0x41)], None, false),
+        (0x4142, &[(1, 0x42)], None, false),
+        (0x4142, &[(1, 0x41)], None, true),
+        // 5XY0, 9XY0
+        (0x5120, &[(1, 7), (2, 7)], None, true),
+        (0x5120, &[(1, 7), (2, 8)], None, false),
+        (0x9120, &[(1, 7), (2, 7)], None, false),
+        (0x9120, &[(1, 7), (2, 8)], None, true),
+        // EX9E, EXA1
+        (0xE19E, &[(1, 0xA)], Some(0xA), true),
+        (0xE19E, &[(1, 0xA)], Some(0xB), false),
+        (0xE19E, &[(1, 0xA)], None, false),
+        (0xE1A1, &[(1, 0xA)], Some(0xA), false),
+        (0xE1A1, &[(1, 0xA)], Some(0xB), true),
+        (0xE1A1, &[(1, 0xA)], None, true),
+    ];
+
+    for (op, regs, key, skips) in cases {
+        let mut chip8 = chip8_with(regs);
+        if let Some(key) = key {
+            chip8.key_press(*key, true);
+        }
+        chip8.execute_instruction(*op).unwrap();
+
+        let expected = if *skips { START_ADDR + 2 } else { START_ADDR };
+        assert_eq!(chip8.pc, expected, "{:04X}", op);
+    }
+}
+
+#[test]
+fn flow() {
+    let mut chip8 = Chip8::new();
+
+    // 1NNN
+    chip8.execute_instruction(0x1ABC).unwrap();
+    assert_eq!(chip8.pc, 0xABC);
+
+    // 2NNN pushes the return address
+    chip8.pc = 0x302;
+    chip8.execute_instruction(0x2400).unwrap();
+    assert_eq!(chip8.pc, 0x400);
+    assert_eq!(chip8.sp, 1);
+    assert_eq!(chip8.stack[0], 0x302);
+
+    chip8.pc = 0x40A;
+    chip8.execute_instruction(0x2500).unwrap();
+    assert_eq!(chip8.pc, 0x500);
+    assert_eq!(chip8.sp, 2);
+
+    // 00EE
+    chip8.execute_instruction(0x00EE).unwrap();
+    assert_eq!(chip8.pc, 0x40A);
+    assert_eq!(chip8.sp, 1);
+    chip8.execute_instruction(0x00EE).unwrap();
+    assert_eq!(chip8.pc, 0x302);
+    assert_eq!(chip8.sp, 0);
+
+    // BNNN adds V0
+    chip8.v_reg[0] = 0x10;
+    chip8.v_reg[1] = 0x20;
+    chip8.execute_instruction(0xB300).unwrap();
+    assert_eq!(chip8.pc, 0x310);
+
+    // 0000 does nothing
+    let pc = chip8.pc;
+    chip8.execute_instruction(0x0000).unwrap();
+    assert_eq!(chip8.pc, pc);
+}
+
+#[test]
+fn index_and_memory() {
+    let mut chip8 = Chip8::new();
+
+    // ANNN
+    chip8.execute_instruction(0xA123).unwrap();
+    assert_eq!(chip8.i_reg, 0x123);
+
+    // FX1E adds to I without touching VF
+    chip8.v_reg[1] = 0x0F;
+    chip8.v_reg[0xF] = 7;
+    chip8.execute_instruction(0xF11E).unwrap();
+    assert_eq!(chip8.i_reg, 0x132);
+    assert_eq!(chip8.v_reg[0xF], 7);
+
+    // I is 16 bit, past 0xFFF it keeps counting and only wraps at 0xFFFF
+    chip8.i_reg = 0xFFF;
+    chip8.v_reg[1] = 1;
+    chip8.execute_instruction(0xF11E).unwrap();
+    assert_eq!(chip8.i_reg, 0x1000);
+    assert_eq!(chip8.v_reg[0xF], 7);
+
+    chip8.i_reg = 0xFFFF;
+    chip8.v_reg[1] = 2;
+    chip8.execute_instruction(0xF11E).unwrap();
+    assert_eq!(chip8.i_reg, 1);
+
+    // FX29 points I at the font sprite of the low digit of VX
+    chip8.v_reg[2] = 0xA;
+    chip8.execute_instruction(0xF229).unwrap();
+    assert_eq!(chip8.i_reg, 0xA * 5);
+    assert_eq!(chip8.memory()[chip8.i_reg as usize], 0xF0);
+
+    // FX55 stores V0..=VX, I unchanged
+    chip8.v_reg[..4].copy_from_slice(&[1, 2, 3, 4]);
+    chip8.i_reg = 0x300;
+    chip8.execute_instruction(0xF255).unwrap();
+    assert_eq!(chip8.memory()[0x300..0x304], [1, 2, 3, 0]);
+    assert_eq!(chip8.i_reg, 0x300);
+
+    // FX65 loads V0..=VX, I unchanged
+    chip8.write_memory(0x310, &[9, 8, 7, 6]);
+    chip8.v_reg[..4].copy_from_slice(&[0, 0, 0, 0]);
+    chip8.i_reg = 0x310;
+    chip8.execute_instruction(0xF265).unwrap();
+    assert_eq!(chip8.v_reg[..4], [9, 8, 7, 0]);
+    assert_eq!(chip8.i_reg, 0x310);
+}
+
+#[test]
+fn bcd() {
+    // (VX, hundreds, tens, ones)
+    let cases = [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (10, [0, 1, 0]),
+        (99, [0, 9, 9]),
+        (100, [1, 0, 0]),
+        (109, [1, 0, 9]),
+        (128, [1, 2, 8]),
+        (199, [1, 9, 9]),
+        (200, [2, 0, 0]),
+        (255, [2, 5, 5]),
+    ];
+
+    for (value, digits) in cases {
+        let mut chip8 = Chip8::new();
+        chip8.v_reg[3] = value;
+        chip8.i_reg = 0x400;
+        chip8.execute_instruction(0xF333).unwrap();
+        assert_eq!(chip8.memory()[0x400..0x403], digits, "BCD of {}", value);
+        assert_eq!(chip8.i_reg, 0x400);
+    }
+}
+
+#[test]
+fn timers() {
+    let mut chip8 = Chip8::new();
+
+    // FX15, FX18, FX07
+    chip8.v_reg[1] = 3;
+    chip8.execute_instruction(0xF115).unwrap();
+    chip8.execute_instruction(0xF118).unwrap();
+    assert_eq!(chip8.delay_timer, 3);
+    assert_eq!(chip8.sound_timer, 3);
+
+    chip8.tick_timers();
+    chip8.execute_instruction(0xF207).unwrap();
+    assert_eq!(chip8.v_reg[2], 2);
+
+    for _ in 0..5 {
+        chip8.tick_timers();
+    }
+    chip8.execute_instruction(0xF207).unwrap();
+    assert_eq!(chip8.v_reg[2], 0);
+    assert_eq!(chip8.sound_timer, 0);
+}
+
+#[test]
+fn wait_for_key() {
+    let mut chip8 = Chip8::new();
+
+    // FX0A repeats itself until a key is down
+    chip8.pc = START_ADDR + 2;
+    chip8.execute_instruction(0xF30A).unwrap();
+    assert_eq!(chip8.pc, START_ADDR);
+
+    chip8.pc = START_ADDR + 2;
+    chip8.key_press(0xB, true);
+    chip8.execute_instruction(0xF30A).unwrap();
+    assert_eq!(chip8.pc, START_ADDR + 2);
+    assert_eq!(chip8.v_reg[3], 0xB);
+}
+
+#[test]
+fn random() {
+    let mut chip8 = Chip8::new();
+
+    // CXNN masks the random byte with NN
+    for _ in 0..100 {
+        chip8.execute_instruction(0xC100).unwrap();
+        assert_eq!(chip8.v_reg[1], 0);
+
+        chip8.execute_instruction(0xC10F).unwrap();
+        assert!(chip8.v_reg[1] <= 0x0F);
+    }
+}
+
+#[test]
+fn display() {
+    let mut chip8 = Chip8::new();
+    let lit = |chip8: &Chip8, x: usize, y: usize| chip8.screen[x + y * SCREEN_WIDTH];
+
+    // DXYN draws the font sprite for 0 at (2, 3)
+    chip8.v_reg[1] = 2;
+    chip8.v_reg[2] = 3;
+    chip8.i_reg = 0;
+    chip8.execute_instruction(0xD125).unwrap();
+    assert!(lit(&chip8, 2, 3) && lit(&chip8, 5, 3) && !lit(&chip8, 3, 4));
+    assert_eq!(chip8.screen.iter().filter(|p| **p).count(), 14);
+    assert_eq!(chip8.v_reg[0xF], 0);
+
+    // Drawing it again erases it and reports the collision
+    chip8.execute_instruction(0xD125).unwrap();
+    assert!(chip8.screen.iter().all(|p| !*p));
+    assert_eq!(chip8.v_reg[0xF], 1);
+
+    // Pixels past the right and bottom edges wrap around
+    chip8.v_reg[1] = 62;
+    chip8.v_reg[2] = 30;
+    chip8.execute_instruction(0xD125).unwrap();
+    assert!(lit(&chip8, 62, 30) && lit(&chip8, 1, 30));
+    assert!(lit(&chip8, 62, 0) && lit(&chip8, 1, 2));
+
+    // 00E0
+    chip8.execute_instruction(0x00E0).unwrap();
+    assert!(chip8.screen.iter().all(|p| !*p));
+}
+
+#[test]
+fn draw_wrap() {
+    // (VX, VY, pixels lit by a 4 pixel high line), rows past the bottom go
+    // back to the top, the start position wraps on both axes
+    type Pixels = [(usize, usize); 4];
+    let cases: &[(u8, u8, Pixels)] = &[
+        (0, 0, [(0, 0), (0, 1), (0, 2), (0, 3)]),
+        (5, 30, [(5, 30), (5, 31), (5, 0), (5, 1)]),
+        (63, 31, [(63, 31), (63, 0), (63, 1), (63, 2)]),
+        (67, 61, [(3, 29), (3, 30), (3, 31), (3, 0)]),
+        (200, 255, [(8, 31), (8, 0), (8, 1), (8, 2)]),
+    ];
+
+    for (vx, vy, pixels) in cases {
+        let mut chip8 = Chip8::new();
+        chip8.write_memory(0x300, &[0x80; 4]);
+        chip8.i_reg = 0x300;
+        chip8.v_reg[1] = *vx;
+        chip8.v_reg[2] = *vy;
+        chip8.execute_instruction(0xD124).unwrap();
+
+        let lit: Vec<(usize, usize)> = (0..chip8.screen.len())
+            .filter(|idx| chip8.screen[*idx])
+            .map(|idx| (idx % SCREEN_WIDTH, idx / SCREEN_WIDTH))
+            .collect();
+        let mut expected = pixels.to_vec();
+        expected.sort_by_key(|(x, y)| (*y, *x));
+        assert_eq!(lit, expected, "D124 at ({}, {})", vx, vy);
+    }
+}
+
+#[test]
+fn quirks() {
+    // (quirk, value, opcode, registers before, registers after)
+    let cases: &[(&str, bool, u16, Regs, Regs)] = &[
+        // 8XY6/8XYE shift VY into VX
+        (
+            "shift",
+            false,
+            0x8126,
+            &[(1, 0xFF), (2, 0b101)],
+            &[(1, 0b10), (0xF, 1)],
+        ),
+        (
+            "shift",
+            false,
+            0x812E,
+            &[(1, 0), (2, 0x81)],
+            &[(1, 0x02), (0xF, 1)],
+        ),
+        // 8XY1/8XY2/8XY3 reset VF
+        (
+            "logic",
+            true,
+            0x8121,
+            &[(1, 0b1100), (2, 0b1010), (0xF, 7)],
+            &[(1, 0b1110), (0xF, 0)],
+        ),
+        (
+            "logic",
+            true,
+            0x8122,
+            &[(1, 0b1100), (2, 0b1010), (0xF, 7)],
+            &[(1, 0b1000), (0xF, 0)],
+        ),
+        (
+            "logic",
+            true,
+            0x8123,
+            &[(1, 0b1100), (2, 0b1010), (0xF, 7)],
+            &[(1, 0b0110), (0xF, 0)],
+        ),
+    ];
+
+    for (quirk, value, op, before, after) in cases {
+        let mut chip8 = chip8_with(before);
+        chip8.quirks.set(quirk, *value).unwrap();
+        chip8.execute_instruction(*op).unwrap();
+        assert_regs(&chip8, after, *op);
+    }
+
+    // FX55/FX65 advance I past the last register, or by X
+    for (increment_by_x, expected) in [(false, 0x303), (true, 0x302)] {
+        let mut chip8 = Chip8::new();
+        chip8.quirks.memory_leave_i_unchanged = false;
+        chip8.quirks.memory_increment_by_x = increment_by_x;
+        for op in [0xF255, 0xF265] {
+            chip8.i_reg = 0x300;
+            chip8.execute_instruction(op).unwrap();
+            assert_eq!(chip8.i_reg, expected, "{:04X}", op);
+        }
+    }
+
+    // BNNN jumps to XNN + VX
+    let mut chip8 = chip8_with(&[(0, 0x10), (2, 0x20)]);
+    chip8.quirks.jump = true;
+    chip8.execute_instruction(0xB234).unwrap();
+    assert_eq!(chip8.pc, 0x254);
+
+    // DXYN clips at the edges, the start position still wraps
+    let mut chip8 = Chip8::new();
+    chip8.quirks.wrap = false;
+    chip8.v_reg[1] = 62 + 64;
+    chip8.v_reg[2] = 30;
+    chip8.execute_instruction(0xD125).unwrap();
+    assert!(chip8.screen[63 + 30 * SCREEN_WIDTH] && chip8.screen[62 + 31 * SCREEN_WIDTH]);
+    assert_eq!(chip8.screen.iter().filter(|p| **p).count(), 3);
+
+    // DXYN ends the frame early
+    let mut chip8 = Chip8::new();
+    chip8.quirks.vblank = true;
+    chip8
+        .load_rom(&[0xD0, 0x15, 0x70, 0x01, 0x12, 0x00])
+        .unwrap();
+    chip8.run_frame().unwrap();
+    assert_eq!(chip8.pc, START_ADDR + 2);
+    assert_eq!(chip8.v_reg[0], 0);
+
+    assert_eq!(Chip8::new().quirks, Quirks::default());
+    assert!(Quirks::default().set("noSuchQuirk", true).is_err());
+}
+
+#[test]
+fn out_of_range() {
+    use chip8_rs::{Error, RAM_SIZE};
+
+    // Fetching at the last byte of RAM wraps around to address 0
+    let mut chip8 = Chip8::new();
+    chip8.write_memory(0xFFF, &[0x61, 0x23]);
+    chip8.pc = 0xFFF;
+    assert_eq!(chip8.fetch_instruction(), 0x6123);
+    assert_eq!(chip8.pc, 1);
+
+    // FX33, FX55 and FX65 wrap I around the end of RAM
+    chip8.i_reg = 0xFFE;
+    chip8.v_reg[0] = 123;
+    chip8.execute_instruction(0xF033).unwrap();
+    assert_eq!(
+        [
+            chip8.memory()[0xFFE],
+            chip8.memory()[0xFFF],
+            chip8.memory()[0]
+        ],
+        [1, 2, 3]
+    );
+
+    chip8.i_reg = 0xFFFF;
+    chip8.v_reg[..3].copy_from_slice(&[7, 8, 9]);
+    chip8.execute_instruction(0xF255).unwrap();
+    assert_eq!(
+        [chip8.memory()[0xFFF], chip8.memory()[0], chip8.memory()[1]],
+        [7, 8, 9]
+    );
+
+    chip8.v_reg[..3].copy_from_slice(&[0, 0, 0]);
+    chip8.execute_instruction(0xF265).unwrap();
+    assert_eq!(chip8.v_reg[..3], [7, 8, 9]);
+
+    // DXYN reads sprites across the end of RAM
+    chip8.i_reg = 0xFFF;
+    chip8.execute_instruction(0xD002).unwrap();
+
+    // Keys use the low nibble of VX
+    chip8.pc = START_ADDR;
+    chip8.v_reg[1] = 0xFA;
+    chip8.key_press(0xA, true);
+    chip8.execute_instruction(0xE19E).unwrap();
+    assert_eq!(chip8.pc, START_ADDR + 2);
+    chip8.key_press(99, true);
+
+    // BNNN wraps the target
+    chip8.v_reg[0] = 0xFF;
+    chip8.execute_instruction(0xBFFF).unwrap();
+    assert_eq!(chip8.pc, 0x0FE);
+
+    // So do return addresses and a PC set from outside
+    let mut chip8 = Chip8::new();
+    chip8.stack[0] = 0xFFFF;
+    chip8.sp = 1;
+    chip8.execute_instruction(0x00EE).unwrap();
+    assert_eq!(chip8.pc, 0xFFF);
+    chip8.pc = 0x1300;
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc, 0x302);
+    chip8.pc = 0x1300;
+    chip8_rs::jit::Jit::new().run(&mut chip8, 1).unwrap();
+    assert_eq!(chip8.pc, 0x302);
+
+    // The stack and opcodes that can't wrap are errors
+    let mut chip8 = Chip8::new();
+    chip8.pc = 0x302;
+    assert_eq!(
+        chip8.execute_instruction(0x00EE),
+        Err(Error::StackUnderflow { pc: 0x300 })
+    );
+    for _ in 0..16 {
+        chip8.execute_instruction(0x2300).unwrap();
+    }
+    assert!(matches!(
+        chip8.execute_instruction(0x2300),
+        Err(Error::StackOverflow { .. })
+    ));
+    assert!(matches!(
+        chip8.execute_instruction(0x5121),
+        Err(Error::InvalidOpcode { op: 0x5121, .. })
+    ));
+
+    let rom = vec![0; RAM_SIZE];
+    assert_eq!(
+        Chip8::new().load_rom(&rom),
+        Err(Error::RomTooLarge { size: RAM_SIZE })
+    );
+}
+
+#[test]
+fn self_modifying_code() {
+    let mut chip8 = Chip8::new();
+    chip8
+        .load_rom(&[
+            0x62, 0x00, // LD V2, 00
+            0xA2, 0x00, // LD I, 200
+            0x60, 0x62, // LD V0, 62
+            0x61, 0x55, // LD V1, 55
+            0xF1, 0x55, // LD [I], V1 rewrites 200 to LD V2, 55
+            0x12, 0x00, // JP 200
+        ])
+        .unwrap();
+
+    for _ in 0..6 {
+        chip8.step().unwrap();
+    }
+    assert_eq!((chip8.pc, chip8.v_reg[2]), (START_ADDR, 0));
+
+    // The decoded copy of 200 must not be reused after FX55 wrote over it
+    chip8.step().unwrap();
+    assert_eq!(chip8.v_reg[2], 0x55);
+
+    // Writing the second byte of an instruction also counts
+    chip8.write_memory(START_ADDR + 1, &[0x77]);
+    chip8.pc = START_ADDR;
+    chip8.step().unwrap();
+    assert_eq!(chip8.v_reg[2], 0x77);
+}
+
+#[test]
+fn snapshot() {
+    let mut chip8 = Chip8::new();
+    chip8
+        .load_rom(&[
+            0xC0, 0xFF, // RND V0, FF
+            0xA3, 0x00, // LD I, 300
+            0xF0, 0x55, // LD [I], V0
+            0xD0, 0x05, // DRW V0, V0, 5
+            0x12, 0x00, // JP 200
+        ])
+        .unwrap();
+    chip8.seed(5);
+    chip8.step().unwrap();
+    let snapshot = chip8.snapshot();
+
+    let run = |chip8: &mut Chip8| {
+        for _ in 0..40 {
+            chip8.step().unwrap();
+        }
+        (
+            chip8.memory().to_vec(),
+            chip8.screen,
+            chip8.v_reg,
+            chip8.cycles,
+        )
+    };
+    let first = run(&mut chip8);
+
+    // Same random numbers and the same screen from the same point
+    chip8.restore(&snapshot);
+    assert_eq!(chip8.pc, START_ADDR + 2);
+    assert_eq!(run(&mut chip8), first);
+
+    // Code written and run after the snapshot is gone with it
+    chip8.write_memory(START_ADDR, &[0x60, 0x00]);
+    chip8.pc = START_ADDR;
+    chip8.step().unwrap();
+    chip8.restore(&snapshot);
+    chip8.pc = START_ADDR;
+    chip8.step().unwrap();
+    assert_eq!(chip8.opcode, 0xC0FF);
+
+    // Saved as bytes
+    let bytes = snapshot.to_bytes();
+    assert_eq!(bytes.len(), snapshot::SIZE);
+    assert_eq!(Snapshot::from_bytes(&bytes), Ok(snapshot.clone()));
+    let mut other = Chip8::new();
+    other.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    assert_eq!(run(&mut other), first);
+
+    assert!(Snapshot::from_bytes(&bytes[1..]).is_err());
+    let mut bad = bytes.clone();
+    bad[0] = b'X';
+    assert!(Snapshot::from_bytes(&bad).is_err());
+
+    // Return addresses past the end of RAM are refused, unused slots aren't
+    // checked
+    let mut chip8 = Chip8::new();
+    chip8.stack = [0xFFFF; 16];
+    chip8.stack[0] = 0x300;
+    chip8.sp = 1;
+    assert!(Snapshot::from_bytes(&chip8.snapshot().to_bytes()).is_ok());
+    chip8.sp = 2;
+    assert!(Snapshot::from_bytes(&chip8.snapshot().to_bytes()).is_err());
+}
@@ -0,0 +1,34 @@
+use chip8_rs::phosphor::{MAX_DECAY, Phosphor};
+
+#[test]
+fn fades() {
+    let mut phosphor = Phosphor::new(0.5, 2);
+    assert_eq!(phosphor.update(&[true, false]), [1.0, 0.0]);
+    assert_eq!(phosphor.update(&[false, false]), [0.5, 0.0]);
+    assert_eq!(phosphor.update(&[false, true]), [0.25, 1.0]);
+
+    // Dark once it's barely visible
+    for _ in 0..3 {
+        phosphor.update(&[false, true]);
+    }
+    assert_eq!(phosphor.update(&[false, true]), [0.0, 1.0]);
+}
+
+#[test]
+fn decay_range() {
+    // Pixels always fade out, nonsense turns persistence off
+    for (decay, expected) in [
+        (-1.0, 0.0),
+        (0.0, 0.0),
+        (0.6, 0.6),
+        (1.0, MAX_DECAY),
+        (f32::INFINITY, MAX_DECAY),
+        (f32::NAN, 0.0),
+    ] {
+        let mut phosphor = Phosphor::new(decay, 1);
+        assert_eq!(phosphor.decay(), expected, "{}", decay);
+        phosphor.set_decay(0.3);
+        phosphor.set_decay(decay);
+        assert_eq!(phosphor.decay(), expected, "{}", decay);
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use chip8_rs::Chip8;
+use chip8_rs::profile::{Coverage, Profile};
+use common::rom;
+use serde_json::json;
+
+fn profile(program: &[u8], frames: usize) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(program).unwrap();
+    chip8.profile = Some(Profile::new());
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+    chip8
+}
+
+#[test]
+fn counts() {
+    let program = rom("ibm.ch8");
+    let chip8 = profile(&program, 10);
+    let profile = chip8.profile.as_ref().unwrap();
+    assert_eq!(profile.total, chip8.cycles);
+
+    // 20 instructions draw the logo, then it loops at 228
+    assert_eq!(profile.hits(0x200), 1);
+    assert_eq!(profile.hits(0x228), chip8.cycles - 20);
+    assert_eq!(profile.hotspots()[0], (0x228, chip8.cycles - 20));
+    assert_eq!(profile.classes()[..2], [("1NNN", 80), ("ANNN", 6)]);
+    assert!(profile.classes().contains(&("DXYN", 6)));
+
+    // The rest is the logo's sprites
+    let end = 0x200 + program.len() as u16;
+    assert_eq!(
+        profile.coverage(0x200..end),
+        Coverage {
+            bytes: program.len(),
+            code: 0x2A,
+            data: program.len() - 0x2A,
+            unused: 0,
+        }
+    );
+    assert!(profile.executed(0x229) && !profile.read(0x229));
+    assert!(profile.read(0x22A) && !profile.executed(0x22A));
+
+    let report = profile.report(chip8.memory(), 0x200..end);
+    assert!(report.starts_with("100 instructions\n"));
+    assert!(report.contains("  228 1228 JP 228"));
+}
+
+#[test]
+fn data_and_listing() {
+    // LD I, 20C; LD V1, [I]; CALL 20A; JP 206; RET; two bytes of data, four
+    // unused
+    let program = [
+        0xA2, 0x0C, 0xF1, 0x65, 0x22, 0x0A, 0x12, 0x06, 0x00, 0x00, 0x00, 0xEE, 0x81, 0x7E, 0x00,
+        0x00, 0x00, 0x00,
+    ];
+    let mut chip8 = profile(&program, 1);
+    let rom = 0x200..0x200 + program.len() as u16;
+
+    // Counting goes on across resets, until the profile is taken away
+    chip8.reset();
+    chip8.load_rom(&program).unwrap();
+    chip8.step().unwrap();
+    let profile = chip8.profile.take().unwrap();
+    assert_eq!(profile.hits(0x200), 2);
+
+    let listing = profile.listing(chip8.memory(), rom.clone());
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "200 A20C LD I, 20C                   2",
+            "202 F165 LD V1, [I]                  1",
+            "204 220A CALL 20A                    1",
+            "206 1206 JP 206                      6",
+            "208-209                   2 bytes unused",
+            "20A 00EE RET                         1",
+            "20C   81 #......#                 data",
+            "20D   7E .######.                 data",
+            "20E-211                   4 bytes unused",
+        ]
+    );
+
+    let report = profile.json(chip8.memory(), rom);
+    assert_eq!(report["instructions"], json!(profile.total));
+    assert_eq!(report["rom"], json!([0x200, 0x211]));
+    assert_eq!(
+        report["coverage"],
+        json!({"bytes": 18, "code": 10, "data": 2, "unused": 6})
+    );
+    assert_eq!(report["code"], json!([[0x200, 0x207], [0x20A, 0x20B]]));
+    assert_eq!(report["data"], json!([[0x20C, 0x20D]]));
+    assert_eq!(
+        report["hotspots"][0],
+        json!({"addr": 0x206, "op": 0x1206, "instruction": "JP 206", "count": 6})
+    );
+}
@@ -0,0 +1,105 @@
+use chip8_rs::reload::{self, Watcher};
+use chip8_rs::{Chip8, START_ADDR};
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_rom(name: &str, rom: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chip8-rs-{}-{}", std::process::id(), name));
+    fs::write(&path, rom).unwrap();
+    path
+}
+
+#[test]
+fn watcher() {
+    let path = temp_rom("watcher", &[0x12, 0x00]);
+    let mut watcher = Watcher::new(&path);
+    assert_eq!(watcher.path(), path);
+    assert_eq!(watcher.poll(), None);
+
+    // Picked up once it stays the same for a poll
+    fs::write(&path, [0x12, 0x00, 0xAB]).unwrap();
+    assert_eq!(watcher.poll(), None);
+    assert_eq!(watcher.poll(), Some(vec![0x12, 0x00, 0xAB]));
+    assert_eq!(watcher.poll(), None);
+
+    // Still being written
+    fs::write(&path, [0x12]).unwrap();
+    assert_eq!(watcher.poll(), None);
+    fs::write(&path, [0x12, 0x00, 0xAB, 0xCD]).unwrap();
+    assert_eq!(watcher.poll(), None);
+    assert_eq!(watcher.poll(), Some(vec![0x12, 0x00, 0xAB, 0xCD]));
+
+    // Deleted, then back
+    fs::remove_file(&path).unwrap();
+    assert_eq!(watcher.poll(), None);
+    assert_eq!(watcher.poll(), None);
+    fs::write(&path, [0x12, 0x02]).unwrap();
+    assert_eq!(watcher.poll(), None);
+    assert_eq!(watcher.poll(), Some(vec![0x12, 0x02]));
+
+    fs::remove_file(&path).unwrap();
+}
+
+// 200: V0 = 5
+// 202: I = 208
+// 204: V1 = [I]
+// 206: jump 206
+// 208: data
+const ROM: [u8; 10] = [0x60, 0x05, 0xA2, 0x08, 0xF1, 0x65, 0x12, 0x06, 0x11, 0x22];
+
+#[test]
+fn patch_data() {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&ROM).unwrap();
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+    assert_eq!(chip8.v_reg[..2], [0x11, 0x22]);
+
+    // Changed data and a new byte at the end
+    let mut new = ROM.to_vec();
+    new[8] = 0x33;
+    new.push(0x44);
+    assert!(reload::patch(&mut chip8, &ROM, &new));
+    let start = START_ADDR as usize;
+    assert_eq!(chip8.memory()[start + 8..start + 11], [0x33, 0x22, 0x44]);
+    assert_eq!(chip8.v_reg[..2], [0x11, 0x22]);
+    assert_eq!(chip8.pc, START_ADDR + 6);
+
+    // The loop hasn't changed, the new data is read from there on
+    chip8.pc = START_ADDR + 4;
+    chip8.step().unwrap();
+    assert_eq!(chip8.v_reg[..2], [0x33, 0x22]);
+
+    // Bytes that are gone are cleared
+    assert!(reload::patch(&mut chip8, &new, &ROM));
+    assert_eq!(chip8.memory()[start + 10], 0);
+}
+
+#[test]
+fn patch_code() {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&ROM).unwrap();
+    for _ in 0..4 {
+        chip8.step().unwrap();
+    }
+
+    // The second byte of an instruction that ran
+    let mut new = ROM.to_vec();
+    new[3] = 0x09;
+    assert!(!reload::patch(&mut chip8, &ROM, &new));
+    assert_eq!(chip8.memory()[START_ADDR as usize + 3], 0x08);
+
+    // Code that hasn't run yet is fine
+    let mut chip8 = Chip8::new();
+    chip8.load_rom(&ROM).unwrap();
+    chip8.step().unwrap();
+    assert!(reload::patch(&mut chip8, &ROM, &new));
+    assert!(!chip8.ran_at(START_ADDR + 3));
+    assert!(chip8.ran_at(START_ADDR + 1));
+    chip8.step().unwrap();
+    assert_eq!(chip8.i_reg, 0x209);
+
+    // Too big for memory
+    assert!(!reload::patch(&mut chip8, &ROM, &vec![0; 4096]));
+}
@@ -0,0 +1,390 @@
+mod common;
+
+use chip8_rs::Chip8;
+use chip8_rs::cheat::Cheats;
+use chip8_rs::remote::{Action, MAX_QUEUED, Remote};
+use common::rom;
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+// Stands in for the frontend: polls the remote between frames and loads ROMs
+// from rom/ when asked to
+struct Frontend {
+    remote: Remote,
+    chip8: Chip8,
+    rom: Option<Vec<u8>>,
+    cheats: Cheats,
+}
+
+impl Frontend {
+    fn new(addr: &str) -> Self {
+        Self {
+            remote: Remote::listen(addr).unwrap(),
+            chip8: Chip8::new(),
+            rom: None,
+            cheats: Cheats::default(),
+        }
+    }
+
+    fn poll(&mut self) {
+        for (action, pending) in self.remote.poll(&mut self.chip8, &mut self.cheats) {
+            let result = match action {
+                Action::Load(path) => std::fs::read(
+                    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                        .join("rom")
+                        .join(path),
+                )
+                .map_err(|err| err.to_string())
+                .map(|rom| self.rom = Some(rom)),
+                Action::Reset if self.rom.is_none() => Err("No ROM loaded".to_string()),
+                Action::Reset => Ok(()),
+            };
+            if result.is_ok() {
+                self.chip8.reset();
+                self.chip8.load_rom(self.rom.as_ref().unwrap()).unwrap();
+            }
+            self.remote.finish(pending, result);
+        }
+    }
+
+    fn frames(&mut self, count: usize) {
+        for _ in 0..count {
+            self.poll();
+            self.cheats.apply(&mut self.chip8);
+            let _ = self.remote.run_frame(&mut self.chip8);
+        }
+    }
+}
+
+struct Client<S> {
+    reader: BufReader<S>,
+    writer: S,
+    buffer: Vec<u8>,
+}
+
+impl<S: Read + Write> Client<S> {
+    fn send(&mut self, request: &str) {
+        writeln!(self.writer, "{}", request).unwrap();
+    }
+
+    // Next line from the emulator, polling it meanwhile
+    fn receive(&mut self, frontend: &mut Frontend) -> Value {
+        for _ in 0..1000 {
+            frontend.poll();
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(_) if self.buffer.ends_with(b"\n") => {
+                    let line = std::mem::take(&mut self.buffer);
+                    return serde_json::from_slice(&line).unwrap();
+                }
+                Ok(_) => {}
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(err) => panic!("{}", err),
+            }
+        }
+        panic!("No reply");
+    }
+
+    fn request(&mut self, frontend: &mut Frontend, request: Value) -> Value {
+        self.send(&request.to_string());
+        self.receive(frontend)
+    }
+}
+
+fn connect(frontend: &Frontend) -> Client<TcpStream> {
+    let stream = TcpStream::connect(frontend.remote.addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(5)))
+        .unwrap();
+    Client {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+        buffer: Vec::new(),
+    }
+}
+
+#[test]
+fn commands() {
+    let mut frontend = Frontend::new("127.0.0.1:0");
+    let mut client = connect(&frontend);
+
+    let reply = client.request(
+        &mut frontend,
+        json!({"id": 1, "cmd": "load", "path": "ibm.ch8"}),
+    );
+    assert_eq!(reply, json!({"id": 1, "ok": true}));
+    assert_eq!(
+        client.request(
+            &mut frontend,
+            json!({"cmd": "read_memory", "addr": "0x200", "len": 2})
+        ),
+        json!({"ok": true, "data": [0x00, 0xE0]})
+    );
+
+    // Paused, nothing runs until stepped
+    client.request(&mut frontend, json!({"cmd": "pause"}));
+    frontend.frames(5);
+    assert_eq!(frontend.chip8.cycles, 0);
+    let reply = client.request(&mut frontend, json!({"cmd": "step", "count": 3}));
+    assert_eq!(reply, json!({"ok": true, "pc": 0x206}));
+
+    let reply = client.request(&mut frontend, json!({"cmd": "registers"}));
+    assert_eq!(reply["i"], json!(0x22A));
+    assert_eq!(reply["v"][0], json!(0x0C));
+    assert_eq!(reply["stack"], json!([]));
+
+    // Stepping stops at breakpoints, and goes on from one
+    client.request(&mut frontend, json!({"cmd": "break", "addr": "0x20A"}));
+    let reply = client.request(&mut frontend, json!({"cmd": "step", "count": 100}));
+    assert_eq!(reply, json!({"ok": true, "pc": 0x20A}));
+    let reply = client.request(&mut frontend, json!({"cmd": "step"}));
+    assert_eq!(reply, json!({"ok": true, "pc": 0x20C}));
+    client.request(&mut frontend, json!({"cmd": "clear", "addr": "0x20A"}));
+    let reply = client.request(&mut frontend, json!({"cmd": "step", "count": 100_001}));
+    assert_eq!(reply["error"], json!("count is at most 100000"));
+    assert_eq!(frontend.chip8.pc, 0x20C);
+
+    client.request(
+        &mut frontend,
+        json!({"cmd": "set_register", "name": "VA", "value": 7}),
+    );
+    client.request(
+        &mut frontend,
+        json!({"cmd": "write_memory", "addr": 0x300, "data": [1, "0xFF"]}),
+    );
+    assert_eq!(frontend.chip8.v_reg[0xA], 7);
+    assert_eq!(frontend.chip8.memory()[0x300..0x302], [1, 0xFF]);
+
+    client.request(&mut frontend, json!({"cmd": "press", "key": "0xE"}));
+    assert!(frontend.chip8.keys[0xE]);
+    client.request(&mut frontend, json!({"cmd": "release", "key": 14}));
+    assert!(!frontend.chip8.keys[0xE]);
+
+    client.request(&mut frontend, json!({"cmd": "resume"}));
+    frontend.frames(30);
+    let reply = client.request(&mut frontend, json!({"cmd": "screen"}));
+    let rows = reply["rows"].as_array().unwrap();
+    assert_eq!((reply["width"].clone(), rows.len()), (json!(64), 32));
+    let lit: usize = rows
+        .iter()
+        .map(|row| row.as_str().unwrap().matches('1').count())
+        .sum();
+    assert!(lit > 100);
+
+    // Reset starts the ROM over
+    let reply = client.request(&mut frontend, json!({"id": "r", "cmd": "reset"}));
+    assert_eq!(reply, json!({"id": "r", "ok": true}));
+    assert_eq!(frontend.chip8.cycles, 0);
+}
+
+#[test]
+fn errors() {
+    let mut frontend = Frontend::new("127.0.0.1:0");
+    let mut client = connect(&frontend);
+
+    for (request, error) in [
+        ("not json", "Invalid JSON"),
+        ("[1, 2]", "Expected an object"),
+        (r#"{"id": 5}"#, "Missing cmd"),
+        (r#"{"cmd": "fly"}"#, "Unknown command fly"),
+        (r#"{"cmd": "step"}"#, "Not paused"),
+        (r#"{"cmd": "reset"}"#, "No ROM loaded"),
+        (
+            r#"{"cmd": "read_memory", "addr": 4096, "len": 1}"#,
+            "Address 1000 out of range",
+        ),
+        (r#"{"cmd": "press", "key": 16}"#, "No key 10"),
+        (
+            r#"{"cmd": "set_register", "name": "v0", "value": 256}"#,
+            "v0 is 8 bits",
+        ),
+        (
+            r#"{"cmd": "set_register", "name": "vg", "value": 1}"#,
+            "No register vg",
+        ),
+        (
+            r#"{"cmd": "write_memory", "addr": 0, "data": [300]}"#,
+            "Invalid data",
+        ),
+        (
+            r#"{"cmd": "subscribe", "events": ["nope"]}"#,
+            "Unknown event \"nope\"",
+        ),
+        (
+            r#"{"cmd": "search", "filter": "equal"}"#,
+            "No search started",
+        ),
+        (
+            r#"{"cmd": "search", "filter": "bigger"}"#,
+            "Unknown filter bigger",
+        ),
+        (r#"{"cmd": "unfreeze", "addr": 0}"#, "Not frozen"),
+    ] {
+        client.send(request);
+        let reply = client.receive(&mut frontend);
+        assert_eq!(reply["ok"], json!(false), "{}", request);
+        let message = reply["error"].as_str().unwrap();
+        assert!(message.starts_with(error), "{}: {}", request, message);
+    }
+
+    let reply = client.request(
+        &mut frontend,
+        json!({"id": 2, "cmd": "load", "path": "MISSING"}),
+    );
+    assert_eq!(
+        (reply["id"].clone(), reply["ok"].clone()),
+        (json!(2), json!(false))
+    );
+}
+
+#[test]
+fn cheats() {
+    let mut frontend = Frontend::new("127.0.0.1:0");
+    let mut client = connect(&frontend);
+    // ADD V0, 1; LD I, 300; LD [I], V0; JP 200
+    frontend
+        .chip8
+        .load_rom(&[0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+        .unwrap();
+    client.request(&mut frontend, json!({"cmd": "pause"}));
+
+    let reply = client.request(&mut frontend, json!({"cmd": "search"}));
+    assert_eq!(reply["count"], json!(4096));
+    client.request(&mut frontend, json!({"cmd": "step", "count": 3}));
+    let reply = client.request(
+        &mut frontend,
+        json!({"cmd": "search", "filter": "increased"}),
+    );
+    assert_eq!(reply["count"], json!(1));
+    let reply = client.request(
+        &mut frontend,
+        json!({"cmd": "search", "filter": "value", "value": 1}),
+    );
+    assert_eq!(reply["count"], json!(1));
+    let reply = client.request(&mut frontend, json!({"cmd": "search_results"}));
+    assert_eq!(reply["results"], json!([[0x300, 1]]));
+
+    let reply = client.request(
+        &mut frontend,
+        json!({"cmd": "freeze", "addr": "0x300", "value": 0x42, "name": "counter"}),
+    );
+    assert_eq!(reply["ok"], json!(true));
+    frontend.frames(1);
+    assert_eq!(frontend.chip8.memory()[0x300], 0x42);
+    let reply = client.request(&mut frontend, json!({"cmd": "cheats"}));
+    assert_eq!(
+        reply["cheats"],
+        json!([{"name": "counter", "addr": 0x300, "value": 0x42}])
+    );
+
+    client.request(&mut frontend, json!({"cmd": "unfreeze", "addr": 0x300}));
+    assert!(frontend.cheats.list.is_empty());
+}
+
+#[test]
+fn events() {
+    let mut frontend = Frontend::new("127.0.0.1:0");
+    let mut client = connect(&frontend);
+    let mut other = connect(&frontend);
+
+    // BRIX sets up a long beep at 2CA when the ball is missed
+    client.request(&mut frontend, json!({"cmd": "load", "path": "BRIX"}));
+    client.request(
+        &mut frontend,
+        json!({"cmd": "subscribe", "events": ["breakpoint", "error"]}),
+    );
+    client.request(&mut frontend, json!({"cmd": "break", "addr": "0x2CA"}));
+
+    let mut frames = 0;
+    while !frontend.remote.paused {
+        frontend.frames(1);
+        frames += 1;
+        assert!(frames < 2000, "Breakpoint never hit");
+    }
+    assert_eq!(frontend.chip8.pc, 0x2CA);
+    assert_eq!(
+        client.receive(&mut frontend),
+        json!({"event": "breakpoint", "pc": 0x2CA})
+    );
+    let status = client.request(&mut frontend, json!({"cmd": "status"}));
+    assert_eq!(status["paused"], json!(true));
+    assert_eq!(status["breakpoints"], json!([0x2CA]));
+
+    // Continues past the breakpoint it stopped at, and the sound goes on and
+    // off. Only the client that asked hears about it.
+    other.request(
+        &mut frontend,
+        json!({"cmd": "subscribe", "events": ["sound"]}),
+    );
+    client.request(&mut frontend, json!({"cmd": "clear", "addr": 0x2CA}));
+    client.request(&mut frontend, json!({"cmd": "resume"}));
+    frontend.frames(40);
+    assert_ne!(frontend.chip8.pc, 0x2CA);
+    assert_eq!(
+        other.receive(&mut frontend),
+        json!({"event": "sound", "on": true})
+    );
+    assert_eq!(
+        other.receive(&mut frontend),
+        json!({"event": "sound", "on": false})
+    );
+
+    // Errors stop the program
+    let pc = frontend.chip8.pc;
+    client.request(
+        &mut frontend,
+        json!({"cmd": "write_memory", "addr": pc, "data": [0, 0xEE]}),
+    );
+    frontend.frames(1);
+    let event = client.receive(&mut frontend);
+    assert_eq!(event["event"], json!("error"));
+}
+
+#[test]
+fn client_not_reading() {
+    let mut frontend = Frontend::new("127.0.0.1:0");
+    let mut client = connect(&frontend);
+    let mut stuck = connect(&frontend);
+    client.request(&mut frontend, json!({"cmd": "load", "path": "PONG"}));
+
+    // Far more replies than the socket buffers and the queue hold, polling
+    // must not wait for them to be read
+    let request = json!({"cmd": "screen"}).to_string();
+    for _ in 0..MAX_QUEUED * 4 {
+        stuck.send(&request);
+    }
+    frontend.frames(10);
+
+    // Dropped, the others are still served
+    let reply = client.request(&mut frontend, json!({"cmd": "status"}));
+    assert_eq!(reply["ok"], json!(true));
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket() {
+    use std::os::unix::net::UnixStream;
+
+    let path = std::env::temp_dir().join(format!("chip8-rs-{}.sock", std::process::id()));
+    let mut frontend = Frontend::new(&format!("unix:{}", path.display()));
+    assert_eq!(frontend.remote.addr(), format!("unix:{}", path.display()));
+
+    let stream = UnixStream::connect(&path).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(5)))
+        .unwrap();
+    let mut client = Client {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+        buffer: Vec::new(),
+    };
+    let reply = client.request(&mut frontend, json!({"cmd": "load", "path": "PONG"}));
+    assert_eq!(reply, json!({"ok": true}));
+    assert_eq!(rom("PONG")[..2], frontend.chip8.memory()[0x200..0x202]);
+
+    // A socket left behind is replaced
+    drop(frontend);
+    Frontend::new(&format!("unix:{}", path.display()));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,197 @@
+#![cfg(feature = "scripting")]
+
+mod common;
+
+use chip8_rs::database::Database;
+use chip8_rs::script::{Error, Script};
+use chip8_rs::{Chip8, SCREEN_WIDTH};
+use common::rom;
+use std::path::Path;
+
+// With the ROM database's quirks, as the frontend runs it
+fn start(program: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.seed(1);
+    chip8.load_rom(program).unwrap();
+    if let Some(info) = Database::builtin().lookup(program) {
+        info.apply(&mut chip8);
+    }
+    chip8
+}
+
+fn example(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("scripts")
+        .join(name)
+}
+
+fn run(script: &mut Script, chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        script.run_frame(chip8).unwrap();
+    }
+}
+
+#[test]
+fn registers_memory_and_keys() {
+    let mut chip8 = start(&rom("ibm.ch8"));
+    let mut script = Script::new(
+        r#"
+            // Runs once, at load
+            poke(0x300, peek(0x200) + 1);
+            on_frame(|| {
+                set_v(3, frame());
+                set_i(0x123);
+                if frame() == 2 { press(0xA) }
+                if frame() == 3 { release(0xA); set_delay_timer(9) }
+            });
+        "#,
+        &mut chip8,
+    )
+    .unwrap();
+    assert_eq!(chip8.memory()[0x300], 1);
+
+    run(&mut script, &mut chip8, 2);
+    assert_eq!((chip8.v_reg[3], chip8.i_reg), (2, 0x123));
+    assert!(chip8.keys[0xA]);
+    run(&mut script, &mut chip8, 1);
+    assert!(!chip8.keys[0xA]);
+    assert_eq!(chip8.delay_timer, 9);
+}
+
+#[test]
+fn pc_and_write_hooks() {
+    // LD V0, 123; LD I, 300; LD B, V0; JP 206
+    let mut chip8 = start(&[0x60, 0x7B, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x06]);
+    let mut script = Script::new(
+        r#"
+            let hits = 0;
+            on_pc(0x206, |pc| { hits += 1; set_v(1, hits) });
+            on_write(0x302, |addr, value| set_v(2, addr - 0x300 + value));
+            on_write(0x303, |addr, value| set_v(3, 1));
+        "#,
+        &mut chip8,
+    )
+    .unwrap();
+
+    run(&mut script, &mut chip8, 1);
+    // Before each time the loop runs, after the last digit is stored
+    assert_eq!(chip8.v_reg[1], 7);
+    assert_eq!(chip8.v_reg[2], 2 + 3);
+    assert_eq!(chip8.v_reg[3], 0);
+}
+
+#[test]
+fn overlay_and_screenshots() {
+    let mut chip8 = start(&rom("ibm.ch8"));
+    let mut script = Script::new(
+        r#"
+            on_frame(|| {
+                if frame() == 1 {
+                    draw(62, 30, 5, 5);
+                    // Sizes that would overflow reach the edge too
+                    draw(63, 31, 9223372036854775807, 9223372036854775807);
+                    screenshot();
+                    screenshot("ibm.png");
+                }
+            });
+        "#,
+        &mut chip8,
+    )
+    .unwrap();
+
+    run(&mut script, &mut chip8, 1);
+    let lit: Vec<usize> = (0..script.overlay().len())
+        .filter(|idx| script.overlay()[*idx])
+        .collect();
+    // Clipped to the screen
+    let corner = |x, y| y * SCREEN_WIDTH + x;
+    assert_eq!(
+        lit,
+        [
+            corner(62, 30),
+            corner(63, 30),
+            corner(62, 31),
+            corner(63, 31)
+        ]
+    );
+    assert_eq!(
+        script.take_screenshots(),
+        [None, Some("ibm.png".to_string())]
+    );
+    assert!(script.take_screenshots().is_empty());
+
+    // Gone the next frame
+    run(&mut script, &mut chip8, 1);
+    assert!(!script.overlay().contains(&true));
+}
+
+#[test]
+fn errors() {
+    let mut chip8 = start(&rom("ibm.ch8"));
+    assert!(matches!(
+        Script::new("on_frame(|| {", &mut chip8),
+        Err(Error::Script(_))
+    ));
+    assert!(matches!(
+        Script::new("poke(0x1000, 1)", &mut chip8),
+        Err(Error::Script(err)) if err.contains("Address 4096 out of range")
+    ));
+
+    let mut script = Script::new("on_frame(|| set_v(0, 256))", &mut chip8).unwrap();
+    assert!(matches!(
+        script.run_frame(&mut chip8),
+        Err(Error::Script(_))
+    ));
+
+    // Endless loops run out of operations
+    assert!(matches!(
+        Script::new("loop {}", &mut chip8),
+        Err(Error::Script(err)) if err.contains("Too many operations")
+    ));
+    let mut script = Script::new("on_frame(|| { while true {} })", &mut chip8).unwrap();
+    assert!(matches!(
+        script.run_frame(&mut chip8),
+        Err(Error::Script(err)) if err.contains("Too many operations")
+    ));
+
+    // The program's errors come through as they are
+    let mut chip8 = start(&[0x00, 0xEE]);
+    let mut script = Script::new("", &mut chip8).unwrap();
+    assert!(matches!(
+        script.run_frame(&mut chip8),
+        Err(Error::Program(chip8_rs::Error::StackUnderflow {
+            pc: 0x200
+        }))
+    ));
+}
+
+// BRIX keeps the score in V5 and the lives left in VE
+fn play_brix(script: &mut Script, chip8: &mut Chip8) -> (u8, u8) {
+    run(script, chip8, 3000);
+    (chip8.v_reg[5], chip8.v_reg[0xE])
+}
+
+#[test]
+fn examples() {
+    let mut chip8 = start(&rom("BRIX"));
+    let (score, lives) = play_brix(&mut Script::new("", &mut chip8).unwrap(), &mut chip8);
+    assert_eq!(lives, 0);
+    let mut chip8 = start(&rom("BRIX"));
+    let mut bot = Script::load(&example("brix_bot.rhai"), &mut chip8).unwrap();
+    let (bot_score, bot_lives) = play_brix(&mut bot, &mut chip8);
+    assert!(
+        bot_score > score && bot_lives > 0,
+        "{} {}",
+        bot_score,
+        bot_lives
+    );
+
+    let mut chip8 = start(&rom("BRIX"));
+    let mut script = Script::load(&example("brix_lives.rhai"), &mut chip8).unwrap();
+    run(&mut script, &mut chip8, 3000);
+    assert_eq!(chip8.v_reg[0xE], 5);
+
+    let mut chip8 = start(&rom("TETRIS"));
+    let mut script = Script::load(&example("tetris_score.rhai"), &mut chip8).unwrap();
+    run(&mut script, &mut chip8, 300);
+}
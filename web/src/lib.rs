@@ -0,0 +1,90 @@
+// The emulator for web pages, built for wasm32-unknown-unknown with
+// wasm-pack: `wasm-pack build --target web web`. index.html shows how to use it.
+//
+// There's no clock or randomness in here, the page runs frames at 60Hz and
+// passes in a seed.
+
+use chip8_rs::database::Database;
+use chip8_rs::palette::Palette;
+use chip8_rs::quirks::Quirks;
+use chip8_rs::{Chip8, NUM_KEYS, SCREEN_HEIGHT, SCREEN_WIDTH, TICKS_PER_FRAME};
+use wasm_bindgen::prelude::*;
+
+pub const BYTES_PER_PIXEL: usize = 4;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    chip8: Chip8,
+    palette: Palette,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    // The seed decides what CXNN returns
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Self {
+        let mut chip8 = Chip8::new();
+        chip8.seed(seed as u64);
+        Self {
+            chip8,
+            palette: Palette::classic(),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        SCREEN_WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        SCREEN_HEIGHT
+    }
+
+    // Resets and loads a ROM, with the quirks, speed and palette from the ROM
+    // database when it knows the ROM
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.chip8.reset();
+        self.chip8.load_rom(rom).map_err(|err| err.to_string())?;
+        self.chip8.quirks = Quirks::default();
+        self.chip8.tick_rate = TICKS_PER_FRAME;
+        self.palette = Palette::classic();
+        if let Some(info) = Database::builtin().lookup(rom) {
+            info.apply(&mut self.chip8);
+            if let Some(palette) = info.palette {
+                self.palette = palette;
+            }
+        }
+        Ok(())
+    }
+
+    // One 60Hz frame. After an error the program can't go on until the next load.
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        self.chip8.run_frame().map_err(|err| err.to_string())
+    }
+
+    // Keypad keys 0-F
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if key < NUM_KEYS {
+            self.chip8.key_press(key, pressed);
+        }
+    }
+
+    // Whether the sound timer is running
+    pub fn sound(&self) -> bool {
+        self.chip8.sound_timer > 0
+    }
+
+    // The screen as RGBA, row by row, ready for an ImageData
+    pub fn rgba(&self) -> Vec<u8> {
+        let [background, foreground, ..] = self.palette.colors;
+        self.chip8
+            .screen
+            .iter()
+            .flat_map(|lit| {
+                let [r, g, b] = if *lit { foreground } else { background };
+                [r, g, b, 0xFF]
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,86 @@
+// The JS-facing API, run natively
+
+use chip8_web::{BYTES_PER_PIXEL, Emulator};
+use std::fs;
+use std::path::PathBuf;
+
+fn rom(name: &str) -> Vec<u8> {
+    fs::read(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../rom")
+            .join(name),
+    )
+    .unwrap()
+}
+
+fn lit(emulator: &Emulator) -> usize {
+    let rgba = emulator.rgba();
+    rgba.chunks_exact(BYTES_PER_PIXEL)
+        .filter(|pixel| pixel[..3] != [0, 0, 0])
+        .count()
+}
+
+#[test]
+fn frames() {
+    let mut emulator = Emulator::new(1);
+    assert_eq!((emulator.width(), emulator.height()), (64, 32));
+    emulator.load(&rom("ibm.ch8")).unwrap();
+    for _ in 0..30 {
+        emulator.run_frame().unwrap();
+    }
+
+    let rgba = emulator.rgba();
+    assert_eq!(rgba.len(), 64 * 32 * BYTES_PER_PIXEL);
+    assert!(
+        rgba.chunks_exact(BYTES_PER_PIXEL)
+            .all(|pixel| pixel[3] == 0xFF)
+    );
+    assert!(lit(&emulator) > 100);
+
+    // Loading starts over
+    emulator.load(&rom("ibm.ch8")).unwrap();
+    assert_eq!(lit(&emulator), 0);
+}
+
+#[test]
+fn seeded() {
+    let play = |seed| {
+        let mut emulator = Emulator::new(seed);
+        emulator.load(&rom("BRIX")).unwrap();
+        for _ in 0..120 {
+            emulator.run_frame().unwrap();
+        }
+        emulator.rgba()
+    };
+    assert_eq!(play(3), play(3));
+    assert!((0..8).any(|seed| play(seed) != play(3)));
+}
+
+#[test]
+fn keys_and_sound() {
+    // LD V0, K; LD ST, V0; JP 204
+    let mut emulator = Emulator::new(0);
+    emulator
+        .load(&[0xF0, 0x0A, 0xF0, 0x18, 0x12, 0x04])
+        .unwrap();
+    emulator.run_frame().unwrap();
+    assert!(!emulator.sound());
+
+    emulator.set_key(0x9, true);
+    emulator.run_frame().unwrap();
+    emulator.set_key(0x9, false);
+    emulator.set_key(0x10, true);
+    emulator.run_frame().unwrap();
+    assert!(emulator.sound());
+}
+
+#[test]
+fn errors() {
+    let mut emulator = Emulator::new(0);
+    assert!(emulator.load(&vec![0; 4096]).is_err());
+    emulator.load(&[0x00, 0xEE]).unwrap();
+    assert_eq!(
+        emulator.run_frame(),
+        Err("Return with an empty stack at 200".to_string())
+    );
+}